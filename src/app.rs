@@ -1,12 +1,70 @@
 use crate::database::Database;
+use crate::context_window::{ContextBudget, ContextStrategy, ContextSummary};
+use crate::credentials::{CredentialBackend, CredentialSettingsState, EncryptedFileStore};
+use crate::generation_params::RoleGenerationParams;
+use crate::llm_provider::{ChatRequest, GenerationParams};
+use crate::profiles::ProviderProfile;
+use crate::streaming::{RetryPolicy, StreamEvent};
+use crate::usage::{ModelPrice, TokenUsage};
 use egui_commonmark::CommonMarkCache;
-use futures::StreamExt as _;
 use std::sync::mpsc;
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct ChatMessage {
+    #[serde(default)]
+    pub id: String, // stable id from the database, empty until saved
     pub role: String,
     pub content: String,
+    #[serde(default)]
+    pub truncated: bool, // response was stopped before the stream finished
+    #[serde(default)]
+    pub usage: Option<TokenUsage>, // only set on assistant answers
+    #[serde(default)]
+    pub prompt_revision_id: Option<i64>, // system prompt revision that produced an answer
+    #[serde(default)]
+    pub timestamp_ms: i64, // Unix milliseconds, 0 if unknown
+}
+
+impl ChatMessage {
+    /// An unsaved message sent or received now.
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            id: String::new(),
+            role: role.to_owned(),
+            content: content.into(),
+            truncated: false,
+            usage: None,
+            prompt_revision_id: None,
+            timestamp_ms: crate::timestamps::now_ms(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Conversation {
+    pub id: i64,
+    pub title: String,
+    pub is_archived: bool,
+    pub updated_at: String,
+}
+
+#[derive(Clone)]
+pub struct AssistantRole {
+    pub id: i64,
+    pub role_name: String,
+    pub display_name: String,
+    pub description: String,
+    pub is_active: bool,
+}
+
+#[derive(Clone)]
+pub struct PromptRevision {
+    pub id: i64,
+    pub role_id: i64,
+    pub panel_type: String,
+    pub prompt_text: String,
+    pub author: String,
+    pub created_at: String,
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
@@ -14,7 +72,8 @@ pub struct DigestItem {
     pub id: String,
     pub content: String,
     pub source: String, // "user" or "assistant"
-    pub timestamp: String,
+    #[serde(default)]
+    pub timestamp_ms: i64, // Unix milliseconds, 0 if unknown
     #[serde(skip)]
     pub selected: bool,
 }
@@ -24,7 +83,8 @@ pub struct LongTermMemoryItem {
     pub id: String,
     pub content: String,
     pub source: String, // "user" or "assistant"
-    pub timestamp: String,
+    #[serde(default)]
+    pub timestamp_ms: i64, // Unix milliseconds, 0 if unknown
     #[serde(skip)]
     pub selected: bool,
 }
@@ -39,21 +99,69 @@ pub struct TemplateApp {
     // Chat interface
     pub chat_input: String,
     pub chat_messages: Vec<ChatMessage>,
+    #[serde(skip)]
+    pub request_params: GenerationParams, // overrides for the next chat message only
+    #[serde(skip)]
+    pub show_request_params: bool,
+
+    // Conversations
+    pub current_conversation_id: Option<i64>,
+    #[serde(skip)]
+    pub conversations: Vec<Conversation>,
+    #[serde(skip)]
+    pub show_archived_conversations: bool,
+    #[serde(skip)]
+    pub renaming_conversation: Option<(i64, String)>, // (conversation id, title being edited)
 
     // Information display
     pub info_text: String,
 
     // API configuration
     #[serde(skip)]
-    pub api_base_url: String,
+    pub provider_profiles: Vec<ProviderProfile>,
+    #[serde(skip)]
+    pub active_profile: ProviderProfile,
+    pub active_profile_name: String, // reselected when the app or another workspace opens
     #[serde(skip)]
     pub api_key: String,
+    pub credential_backend: CredentialBackend,
+    #[serde(skip)]
+    pub credential_file: Option<EncryptedFileStore>, // unlocked encrypted file
+    pub retry_policy: RetryPolicy,
+    pub model_prices: Vec<ModelPrice>,
+
+    // Network log
+    pub network_log_enabled: bool,
+    pub network_log_bodies: bool, // bodies hold conversation content
+    #[serde(skip)]
+    pub network_log: crate::network_log::NetworkLogState,
     #[serde(skip)]
-    pub model: String,
+    pub show_network_log: bool,
+
+    // Context window
+    pub context_strategy: ContextStrategy,
+    pub context_budgets: Vec<ContextBudget>,
+    pub reserved_output_tokens: usize,
+    #[serde(skip)]
+    pub context_summary: Option<ContextSummary>, // of the current conversation
+    #[serde(skip)]
+    pub context_summary_receiver: Option<mpsc::Receiver<StreamEvent>>,
+    #[serde(skip)]
+    pub context_summary_task: Option<tokio::task::JoinHandle<()>>,
+    #[serde(skip)]
+    pub pending_context_summary: Option<ContextSummary>, // text accumulates while streaming
 
     // Streaming state
     #[serde(skip)]
-    pub streaming_receiver: Option<mpsc::Receiver<String>>,
+    pub streaming_receiver: Option<mpsc::Receiver<StreamEvent>>,
+    #[serde(skip)]
+    pub streaming_task: Option<tokio::task::JoinHandle<()>>,
+    #[serde(skip)]
+    pub stream_finish_reason: Option<String>,
+    #[serde(skip)]
+    pub stream_usage: Option<TokenUsage>,
+    #[serde(skip)]
+    pub stream_status: Option<String>, // e.g. "retrying (2/3)…" while waiting for the first token
     #[serde(skip)]
     pub is_waiting_response: bool,
     #[serde(skip)]
@@ -78,6 +186,8 @@ pub struct TemplateApp {
     pub long_term_memory_items: Vec<LongTermMemoryItem>,
     #[serde(skip)]
     pub memory_search: String,
+    pub use_memory_in_chat: bool,
+    pub memory_context_limit: usize,
 
     // Markdown cache for digest panel
     #[serde(skip)]
@@ -86,20 +196,49 @@ pub struct TemplateApp {
     // Database connection
     #[serde(skip)]
     pub database: Option<Database>,
+    pub recent_workspaces: Vec<std::path::PathBuf>, // most recent first
+    #[serde(skip)]
+    pub show_workspace_dialog: bool,
+    #[serde(skip)]
+    pub workspace_dialog: crate::workspace::WorkspaceDialogState,
+
+    // Global search window
+    #[serde(skip)]
+    pub show_search: bool,
+    #[serde(skip)]
+    pub search: crate::search::SearchState,
+
+    // Export dialog
+    #[serde(skip)]
+    pub show_export_dialog: bool,
+    #[serde(skip)]
+    pub export_dialog: crate::export::ExportDialogState,
+
+    // Import dialog
+    #[serde(skip)]
+    pub show_import_dialog: bool,
+    #[serde(skip)]
+    pub import_dialog: crate::import::ImportDialogState,
 
     // Settings window
     #[serde(skip)]
     pub show_settings: bool,
     #[serde(skip)]
-    pub temp_api_base_url: String,
+    pub profile_draft: ProviderProfile,
     #[serde(skip)]
     pub temp_api_key: String,
     #[serde(skip)]
-    pub temp_model: String,
+    pub credential_settings: CredentialSettingsState,
+    #[serde(skip)]
+    pub model_discovery: crate::model_discovery::ModelDiscoveryState,
 
     // Color test window
     #[serde(skip)]
     pub show_color_test: bool,
+    #[serde(skip)]
+    pub show_role_editor: bool,
+    #[serde(skip)]
+    pub role_editor: crate::role_editor::RoleEditorState,
 
     // Assistant role management
     #[serde(skip)]
@@ -111,6 +250,12 @@ pub struct TemplateApp {
     #[serde(skip)]
     pub current_system_prompts: std::collections::HashMap<String, String>, // panel_type -> prompt_text
     #[serde(skip)]
+    pub current_prompt_revisions: std::collections::HashMap<String, i64>, // panel_type -> revision id
+    #[serde(skip)]
+    pub current_role_params: RoleGenerationParams,
+    #[serde(skip)]
+    pub pinned_chat_prompt: Option<PromptRevision>, // overrides the role's chat prompt in this conversation
+    #[serde(skip)]
     pub is_reloading_prompts: bool,
     #[serde(skip)]
     pub reload_start_time: Option<std::time::Instant>,
@@ -125,20 +270,50 @@ impl Default for TemplateApp {
             // Chat interface
             chat_input: String::new(),
             chat_messages: Vec::new(),
+            request_params: GenerationParams::default(),
+            show_request_params: false,
+
+            // Conversations
+            current_conversation_id: None,
+            conversations: Vec::new(),
+            show_archived_conversations: false,
+            renaming_conversation: None,
 
             // Information display
             info_text: "DeepSeek Chat API Integration\nModel: deepseek-chat\nStreaming: Enabled\n中文支持: 已启用 (Chinese Support: Enabled)\n测试字符: 杂 (Test character: 杂)".to_owned(),
 
-            // API configuration from environment variables
-            api_base_url: std::env::var("LLM_BASE_URL")
-                .unwrap_or_else(|_| "https://api.deepseek.com".to_owned()),
+            // API configuration from environment variables until the workspace's profiles load
+            provider_profiles: Vec::new(),
+            active_profile: ProviderProfile::default(),
+            active_profile_name: crate::profiles::DEFAULT_PROFILE_NAME.to_owned(),
             api_key: std::env::var("LLM_API_KEY")
                 .unwrap_or_else(|_| String::new()),
-            model: std::env::var("LLM_MODEL")
-                .unwrap_or_else(|_| "deepseek-chat".to_owned()),
+            credential_backend: CredentialBackend::default(),
+            credential_file: None,
+            retry_policy: RetryPolicy::default(),
+            model_prices: crate::usage::default_model_prices(),
+
+            // Network log
+            network_log_enabled: false,
+            network_log_bodies: false,
+            network_log: Default::default(),
+            show_network_log: false,
+
+            // Context window
+            context_strategy: ContextStrategy::DropOldest,
+            context_budgets: crate::context_window::default_context_budgets(),
+            reserved_output_tokens: crate::context_window::DEFAULT_RESERVED_OUTPUT_TOKENS,
+            context_summary: None,
+            context_summary_receiver: None,
+            context_summary_task: None,
+            pending_context_summary: None,
 
             // Streaming state
             streaming_receiver: None,
+            streaming_task: None,
+            stream_finish_reason: None,
+            stream_usage: None,
+            stream_status: None,
             is_waiting_response: false,
             last_error: None,
             current_response: String::new(),
@@ -154,36 +329,73 @@ impl Default for TemplateApp {
             // Long term memory functionality
             long_term_memory_items: Vec::new(),
             memory_search: String::new(),
+            use_memory_in_chat: true,
+            memory_context_limit: crate::memory_context::DEFAULT_MEMORY_CONTEXT_LIMIT,
 
             // Markdown cache for digest panel
             markdown_cache: CommonMarkCache::default(),
 
             // Database connection
             database: None,
+            recent_workspaces: Vec::new(),
+            show_workspace_dialog: false,
+            workspace_dialog: Default::default(),
+
+            // Global search window
+            show_search: false,
+            search: Default::default(),
+
+            // Export dialog
+            show_export_dialog: false,
+            export_dialog: Default::default(),
+
+            // Import dialog
+            show_import_dialog: false,
+            import_dialog: Default::default(),
 
             // Settings window
             show_settings: false,
-            temp_api_base_url: std::env::var("LLM_BASE_URL")
-                .unwrap_or_else(|_| "https://api.deepseek.com".to_owned()),
+            profile_draft: ProviderProfile::default(),
             temp_api_key: std::env::var("LLM_API_KEY")
                 .unwrap_or_else(|_| String::new()),
-            temp_model: std::env::var("LLM_MODEL")
-                .unwrap_or_else(|_| "deepseek-chat".to_owned()),
+            credential_settings: Default::default(),
+            model_discovery: Default::default(),
 
             // Color test window
             show_color_test: false,
+            show_role_editor: false,
+            role_editor: Default::default(),
 
             // Assistant role management
             current_assistant_role_id: None,
             temp_assistant_role_id: None,
             available_roles: Vec::new(),
             current_system_prompts: std::collections::HashMap::new(),
+            current_prompt_revisions: std::collections::HashMap::new(),
+            current_role_params: RoleGenerationParams::default(),
+            pinned_chat_prompt: None,
             is_reloading_prompts: false,
             reload_start_time: None,
         }
     }
 }
 
+/// Ask the model to summarize digest or memory items given as `(source, timestamp_ms, content)`.
+pub fn build_items_summary_request<'a>(
+    what: &str,
+    items: impl Iterator<Item = (&'a str, i64, &'a str)>,
+) -> String {
+    let mut request = format!("Please provide a comprehensive summary of the following {what}:\n\n");
+    for (i, (source, timestamp_ms, content)) in items.enumerate() {
+        let num = i + 1;
+        let source_label = if source == "user" { "User" } else { "Assistant" };
+        let timestamp = crate::timestamps::format_local(timestamp_ms);
+        request.push_str(&format!("{num}. {source_label} ({timestamp}):\n{content}\n\n"));
+    }
+    request.push_str("Please provide a clear, structured summary that captures the key points, main topics discussed, and important conclusions from the above content.");
+    request
+}
+
 impl TemplateApp {
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
@@ -193,15 +405,6 @@ impl TemplateApp {
         // Configure fonts to support Chinese characters
         Self::configure_fonts(&cc.egui_ctx);
 
-        // Initialize database
-        let database = match Database::new() {
-            Ok(db) => Some(db),
-            Err(e) => {
-                log::error!("Failed to initialize database: {e}");
-                None
-            }
-        };
-
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
         let mut app: Self = if let Some(storage) = cc.storage {
@@ -210,11 +413,33 @@ impl TemplateApp {
             Default::default()
         };
 
-        // Set the database connection
-        app.database = database;
+        // Initialize database
+        let workspace = crate::workspace::startup_workspace(&app.recent_workspaces);
+        let same_workspace = app.recent_workspaces.first() == Some(&workspace);
+        match Database::open(&workspace) {
+            Ok(db) => {
+                app.database = Some(db);
+                app.remember_workspace(&workspace);
+            }
+            Err(e) => {
+                log::error!("Failed to initialize database {}: {e}", workspace.display());
+                app.last_error = Some(format!("Database error: {e}"));
+            }
+        }
+
+        if same_workspace {
+            // Load assistant roles and set default role
+            app.load_assistant_roles();
+            app.load_provider_profiles();
+            app.load_network_log();
 
-        // Load assistant roles and set default role
-        app.load_assistant_roles();
+            // Load conversations and make sure one is selected
+            app.load_conversations();
+        } else {
+            // Saved state belongs to another database
+            app.reset_workspace_state();
+            app.reload_workspace();
+        }
 
         app
     }
@@ -400,7 +625,7 @@ impl TemplateApp {
         });
     }
 
-    fn load_assistant_roles(&mut self) {
+    pub fn load_assistant_roles(&mut self) {
         if let Some(ref db) = self.database {
             // Load available roles
             match db.get_assistant_roles() {
@@ -421,6 +646,20 @@ impl TemplateApp {
         }
     }
 
+    /// Re-read roles after they were edited, moving off the current role if it was deactivated.
+    pub fn reload_assistant_roles(&mut self) {
+        self.load_assistant_roles();
+
+        let current_exists = self
+            .current_assistant_role_id
+            .is_some_and(|role_id| self.available_roles.iter().any(|(id, ..)| *id == role_id));
+        if !current_exists {
+            self.current_assistant_role_id = self.available_roles.first().map(|(id, ..)| *id);
+            self.temp_assistant_role_id = self.current_assistant_role_id;
+        }
+        self.load_system_prompts_for_current_role();
+    }
+
     fn load_system_prompts_for_current_role(&mut self) {
         if let (Some(role_id), Some(db)) = (self.current_assistant_role_id, &self.database) {
             match db.get_system_prompts_for_role(role_id) {
@@ -431,125 +670,304 @@ impl TemplateApp {
                     log::error!("Failed to load system prompts for role {role_id}: {e}");
                 }
             }
+            match db.get_prompt_revision_ids_for_role(role_id) {
+                Ok(revision_ids) => self.current_prompt_revisions = revision_ids,
+                Err(e) => {
+                    log::error!("Failed to load prompt revisions for role {role_id}: {e}");
+                }
+            }
+            match db.get_role_generation_params(role_id) {
+                Ok(params) => self.current_role_params = params,
+                Err(e) => {
+                    log::error!("Failed to load generation parameters for role {role_id}: {e}");
+                }
+            }
+        }
+    }
+
+    /// Pin the current conversation to a chat prompt revision, or unpin it with `None`.
+    pub fn pin_prompt_revision(&mut self, revision_id: Option<i64>) {
+        let (Some(db), Some(conversation_id)) = (&self.database, self.current_conversation_id)
+        else {
+            return;
+        };
+
+        if let Err(e) = db.set_pinned_prompt_revision(conversation_id, revision_id) {
+            log::error!("Failed to pin prompt revision for conversation {conversation_id}: {e}");
+            self.last_error = Some(format!("Database error: {e}"));
+            return;
+        }
+        self.load_conversation_context();
+    }
+
+    pub fn load_conversations(&mut self) {
+        let Some(ref db) = self.database else {
+            return;
+        };
+
+        match db.get_conversations(self.show_archived_conversations) {
+            Ok(conversations) => self.conversations = conversations,
+            Err(e) => {
+                log::error!("Failed to load conversations: {e}");
+                return;
+            }
+        }
+
+        let current_exists = self
+            .current_conversation_id
+            .is_some_and(|id| self.conversations.iter().any(|c| c.id == id));
+        if !current_exists {
+            match self.conversations.iter().find(|c| !c.is_archived) {
+                Some(conversation) => {
+                    let conversation_id = conversation.id;
+                    self.switch_conversation(conversation_id);
+                }
+                None => self.new_conversation(),
+            }
+        }
+    }
+
+    pub fn new_conversation(&mut self) {
+        let Some(ref db) = self.database else {
+            return;
+        };
+
+        match db.create_conversation("New conversation") {
+            Ok(conversation_id) => {
+                self.current_conversation_id = Some(conversation_id);
+                self.chat_messages.clear();
+                self.context_summary = None;
+                self.pinned_chat_prompt = None;
+                self.current_response.clear();
+                self.last_error = None;
+                self.load_conversations();
+                self.should_focus_input = true;
+            }
+            Err(e) => {
+                log::error!("Failed to create conversation: {e}");
+                self.last_error = Some(format!("Database error: {e}"));
+            }
+        }
+    }
+
+    pub fn switch_conversation(&mut self, conversation_id: i64) {
+        self.current_conversation_id = Some(conversation_id);
+        self.chat_messages.clear();
+        self.current_response.clear();
+        self.last_error = None;
+
+        if let Some(ref db) = self.database {
+            match db.load_chat_messages(conversation_id) {
+                Ok(messages) => self.chat_messages = messages,
+                Err(e) => {
+                    log::error!("Failed to load messages for conversation {conversation_id}: {e}");
+                }
+            }
+        }
+        self.load_conversation_context();
+
+        self.should_scroll_chat = true;
+    }
+
+    pub fn rename_conversation(&mut self, conversation_id: i64, title: &str) {
+        let title = title.trim();
+        if title.is_empty() {
+            return;
+        }
+
+        if let Some(ref db) = self.database {
+            if let Err(e) = db.rename_conversation(conversation_id, title) {
+                log::error!("Failed to rename conversation {conversation_id}: {e}");
+                return;
+            }
         }
+        self.load_conversations();
+    }
+
+    pub fn set_conversation_archived(&mut self, conversation_id: i64, is_archived: bool) {
+        if let Some(ref db) = self.database {
+            if let Err(e) = db.set_conversation_archived(conversation_id, is_archived) {
+                log::error!("Failed to archive conversation {conversation_id}: {e}");
+                return;
+            }
+        }
+
+        // Archiving the open conversation moves us to another one
+        if is_archived && self.current_conversation_id == Some(conversation_id) {
+            self.current_conversation_id = None;
+        }
+        self.load_conversations();
     }
 
     pub fn add_to_digest(&mut self, content: &str, source: &str) {
-        use std::time::{SystemTime, UNIX_EPOCH};
+        let timestamp_ms = crate::timestamps::now_ms();
 
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        // Auto-save to database, which hands out the item's stable id
+        let mut id = format!("{source}_{timestamp_ms}");
+        if let Some(ref db) = self.database {
+            match db.save_panel_content(content, source, timestamp_ms, "digest") {
+                Ok(original_id) => id = original_id,
+                Err(e) => log::error!("Failed to save digest item to database: {e}"),
+            }
+        }
 
-        let id = format!("{source}_{timestamp}");
-        let formatted_time = chrono::DateTime::from_timestamp(timestamp as i64, 0)
-            .unwrap_or_else(chrono::Utc::now)
-            .format("%H:%M")
-            .to_string();
+        // Adding the same content again just reselects the existing item
+        if let Some(existing) = self.digest_items.iter_mut().find(|item| item.id == id) {
+            existing.selected = true;
+            return;
+        }
 
         let digest_item = DigestItem {
             id,
             content: content.to_owned(),
             source: source.to_owned(),
-            timestamp: formatted_time.clone(),
+            timestamp_ms,
             selected: true, // Default to selected when adding new items
         };
 
         self.digest_items.push(digest_item);
-
-        // Auto-save to database
-        if let Some(ref db) = self.database {
-            if let Err(e) = db.save_content(
-                content,
-                source,
-                timestamp as i64,
-                &formatted_time,
-                &["digest"],
-            ) {
-                log::error!("Failed to save digest item to database: {e}");
-            }
-        }
     }
 
     pub fn add_to_long_term_memory(&mut self, content: &str, source: &str) {
-        use std::time::{SystemTime, UNIX_EPOCH};
+        let timestamp_ms = crate::timestamps::now_ms();
 
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+        // Auto-save to database, which hands out the item's stable id
+        let mut id = format!("{source}_{timestamp_ms}");
+        if let Some(ref db) = self.database {
+            match db.save_panel_content(content, source, timestamp_ms, "longterm") {
+                Ok(original_id) => id = original_id,
+                Err(e) => log::error!("Failed to save longterm memory item to database: {e}"),
+            }
+        }
 
-        let id = format!("{source}_{timestamp}");
-        let formatted_time = chrono::DateTime::from_timestamp(timestamp as i64, 0)
-            .unwrap_or_else(chrono::Utc::now)
-            .format("%H:%M")
-            .to_string();
+        // Adding the same content again just reselects the existing item
+        if let Some(existing) = self.long_term_memory_items.iter_mut().find(|item| item.id == id) {
+            existing.selected = true;
+            return;
+        }
 
         let memory_item = LongTermMemoryItem {
             id,
             content: content.to_owned(),
             source: source.to_owned(),
-            timestamp: formatted_time.clone(),
+            timestamp_ms,
             selected: true, // Default to selected when adding new items
         };
 
         self.long_term_memory_items.push(memory_item);
+    }
 
+    /// Persist a chat message in the current conversation and return its stable id.
+    pub fn save_chat_message_to_db(&self, message: &ChatMessage) -> Option<String> {
         // Auto-save to database
-        if let Some(ref db) = self.database {
-            if let Err(e) = db.save_content(
-                content,
-                source,
-                timestamp as i64,
-                &formatted_time,
-                &["longterm"],
-            ) {
-                log::error!("Failed to save longterm memory item to database: {e}");
+        let db = self.database.as_ref()?;
+        let result = db
+            .save_content(
+                &message.content,
+                &message.role,
+                message.timestamp_ms,
+                self.current_conversation_id,
+                &["chat"],
+            )
+            .and_then(|original_id| {
+                if message.truncated {
+                    db.set_content_truncated(&original_id, true)?;
+                }
+                if let Some(usage) = &message.usage {
+                    db.set_content_usage(&original_id, usage)?;
+                }
+                if let Some(revision_id) = message.prompt_revision_id {
+                    db.set_content_prompt_revision(&original_id, revision_id)?;
+                }
+                Ok(original_id)
+            });
+        match result {
+            Ok(original_id) => Some(original_id),
+            Err(e) => {
+                log::error!("Failed to save chat message to database: {e}");
+                None
             }
         }
     }
 
-    pub fn save_chat_message_to_db(&self, message: &ChatMessage) {
-        use std::time::{SystemTime, UNIX_EPOCH};
+    /// Save the last chat message and remember the id the database gave it.
+    fn persist_last_chat_message(&mut self) {
+        let Some(last_msg) = self.chat_messages.last_mut() else {
+            return;
+        };
+        // Answers are dated when they finish, not when their placeholder was added
+        last_msg.timestamp_ms = crate::timestamps::now_ms();
+        let message = last_msg.clone();
+        if let Some(id) = self.save_chat_message_to_db(&message) {
+            if let Some(last_msg) = self.chat_messages.last_mut() {
+                last_msg.id = id;
+            }
+        }
+    }
 
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+    /// Delete a chat message from the panel and from its conversation in the database.
+    pub fn delete_chat_message(&mut self, index: usize) {
+        if index >= self.chat_messages.len() {
+            return;
+        }
 
-        let formatted_time = chrono::DateTime::from_timestamp(timestamp as i64, 0)
-            .unwrap_or_else(chrono::Utc::now)
-            .format("%H:%M")
-            .to_string();
+        // Deleting the answer that is still streaming stops it first
+        if index == self.chat_messages.len() - 1 && self.is_waiting_response {
+            self.stop_streaming();
+        }
 
-        // Auto-save to database
+        let message = self.chat_messages.remove(index);
+        if let (Some(db), false) = (&self.database, message.id.is_empty()) {
+            if let Err(e) = db.remove_panel_association(&message.id, "chat") {
+                log::error!("Failed to delete chat message from database: {e}");
+                self.last_error = Some(format!("Database error: {e}"));
+            }
+        }
+    }
+
+    pub fn delete_digest_item(&mut self, index: usize) {
+        if index >= self.digest_items.len() {
+            return;
+        }
+
+        let item = self.digest_items.remove(index);
         if let Some(ref db) = self.database {
-            if let Err(e) = db.save_content(
-                &message.content,
-                &message.role,
-                timestamp as i64,
-                &formatted_time,
-                &["chat"],
-            ) {
-                log::error!("Failed to save chat message to database: {e}");
+            if let Err(e) = db.remove_panel_association(&item.id, "digest") {
+                log::error!("Failed to delete digest item from database: {e}");
+                self.last_error = Some(format!("Database error: {e}"));
+            }
+        }
+    }
+
+    pub fn delete_long_term_memory_item(&mut self, index: usize) {
+        if index >= self.long_term_memory_items.len() {
+            return;
+        }
+
+        let item = self.long_term_memory_items.remove(index);
+        if let Some(ref db) = self.database {
+            if let Err(e) = db.remove_panel_association(&item.id, "longterm") {
+                log::error!("Failed to delete longterm memory item from database: {e}");
+                self.last_error = Some(format!("Database error: {e}"));
             }
         }
     }
 
     pub fn load_data_from_database(&mut self) {
         if let Some(ref db) = self.database {
-            // Load chat messages
-            match db.load_chat_messages() {
-                Ok(messages) => {
-                    self.chat_messages = messages;
-                    log::info!(
-                        "Loaded {} chat messages from database",
-                        self.chat_messages.len()
-                    );
-                }
-                Err(e) => {
-                    log::error!("Failed to load chat messages from database: {e}");
+            // Load chat messages of the current conversation
+            if let Some(conversation_id) = self.current_conversation_id {
+                match db.load_chat_messages(conversation_id) {
+                    Ok(messages) => {
+                        self.chat_messages = messages;
+                        log::info!(
+                            "Loaded {} chat messages from database",
+                            self.chat_messages.len()
+                        );
+                    }
+                    Err(e) => {
+                        log::error!("Failed to load chat messages from database: {e}");
+                    }
                 }
             }
 
@@ -596,6 +1014,9 @@ impl TemplateApp {
             self.info_text = "Database not available. Cannot load data.".to_owned();
             log::error!("Database not initialized. Cannot load data.");
         }
+
+        self.load_conversation_context();
+        self.load_conversations();
     }
 
     pub fn export_digest_items(&self) -> String {
@@ -612,7 +1033,7 @@ impl TemplateApp {
                 "## Item {} - {} ({})\n\n",
                 i + 1,
                 source_label,
-                item.timestamp
+                crate::timestamps::format_local(item.timestamp_ms)
             ));
             export_text.push_str(&item.content);
             export_text.push_str("\n\n");
@@ -638,7 +1059,7 @@ impl TemplateApp {
                 "## Item {} - {} ({})\n\n",
                 i + 1,
                 source_label,
-                item.timestamp
+                crate::timestamps::format_local(item.timestamp_ms)
             ));
             export_text.push_str(&item.content);
             export_text.push_str("\n\n");
@@ -661,38 +1082,18 @@ impl TemplateApp {
             return;
         }
 
-        // Prepare digest content for summarization
-        let mut content_to_summarize = String::new();
-        content_to_summarize
-            .push_str("Please provide a comprehensive summary of the following digest items:\n\n");
-
-        for (i, item) in selected_items.iter().enumerate() {
-            let num = i + 1;
-            let source_label = if item.source == "user" {
-                "User"
-            } else {
-                "Assistant"
-            };
-            let timestamp = &item.timestamp;
-            let content = &item.content;
-            content_to_summarize.push_str(&format!(
-                "{num}. {source_label} ({timestamp}):\n{content}\n\n"
-            ));
-        }
-
-        content_to_summarize.push_str("Please provide a clear, structured summary that captures the key points, main topics discussed, and important conclusions from the above content.");
+        let content_to_summarize = build_items_summary_request(
+            "digest items",
+            selected_items
+                .iter()
+                .map(|item| (item.source.as_str(), item.timestamp_ms, item.content.as_str())),
+        );
 
         // Add user message for summary request to chat history
-        self.chat_messages.push(ChatMessage {
-            role: "user".to_owned(),
-            content: content_to_summarize.clone(),
-        });
+        self.chat_messages.push(ChatMessage::new("user", content_to_summarize.clone()));
 
         // Add placeholder for assistant response
-        self.chat_messages.push(ChatMessage {
-            role: "assistant".to_owned(),
-            content: String::new(),
-        });
+        self.chat_messages.push(ChatMessage::new("assistant", ""));
 
         self.is_waiting_response = true;
         self.current_response.clear();
@@ -712,39 +1113,18 @@ impl TemplateApp {
             return;
         }
 
-        // Prepare memory content for summarization
-        let mut content_to_summarize = String::new();
-        content_to_summarize.push_str(
-            "Please provide a comprehensive summary of the following long term memory items:\n\n",
+        let content_to_summarize = build_items_summary_request(
+            "long term memory items",
+            selected_items
+                .iter()
+                .map(|item| (item.source.as_str(), item.timestamp_ms, item.content.as_str())),
         );
 
-        for (i, item) in selected_items.iter().enumerate() {
-            let num = i + 1;
-            let source_label = if item.source == "user" {
-                "User"
-            } else {
-                "Assistant"
-            };
-            let timestamp = &item.timestamp;
-            let content = &item.content;
-            content_to_summarize.push_str(&format!(
-                "{num}. {source_label} ({timestamp}):\n{content}\n\n"
-            ));
-        }
-
-        content_to_summarize.push_str("Please provide a clear, structured summary that captures the key points, main topics discussed, and important conclusions from the above content.");
-
         // Add user message for summary request
-        self.chat_messages.push(ChatMessage {
-            role: "user".to_owned(),
-            content: content_to_summarize.clone(),
-        });
+        self.chat_messages.push(ChatMessage::new("user", content_to_summarize.clone()));
 
         // Add placeholder for assistant response
-        self.chat_messages.push(ChatMessage {
-            role: "assistant".to_owned(),
-            content: String::new(),
-        });
+        self.chat_messages.push(ChatMessage::new("assistant", ""));
 
         self.is_waiting_response = true;
         self.current_response.clear();
@@ -753,259 +1133,408 @@ impl TemplateApp {
         self.send_summary_to_api("memory", content_to_summarize, ctx);
     }
 
+    /// Memory context for the next chat request, based on the latest user message.
+    fn build_chat_memory_prompt(&self) -> Option<String> {
+        let query = self
+            .chat_messages
+            .iter()
+            .rev()
+            .find(|msg| msg.role == "user")
+            .map(|msg| msg.content.as_str())
+            .unwrap_or_default();
+
+        let items = crate::memory_context::select_memory_items(
+            &self.long_term_memory_items,
+            query,
+            self.memory_context_limit,
+        );
+        crate::memory_context::build_memory_prompt(
+            self.current_system_prompts.get("memory").map(String::as_str),
+            &items,
+        )
+    }
+
+    /// The stream ended normally - add final response to chat history.
+    fn finish_streaming_response(&mut self) {
+        // Answers cut off by the output token limit are kept but flagged
+        let hit_limit = matches!(
+            self.stream_finish_reason.as_deref(),
+            Some("length" | "max_tokens")
+        );
+
+        if !self.current_response.is_empty() {
+            // Find the last assistant message and update it
+            if let Some(last_msg) = self.chat_messages.last_mut() {
+                if last_msg.role == "assistant" {
+                    last_msg.content = self.current_response.clone();
+                    last_msg.truncated = hit_limit;
+                    last_msg.usage = self.stream_usage.take();
+                    // Save assistant response to database
+                    self.persist_last_chat_message();
+                }
+            }
+        }
+        self.streaming_receiver = None;
+        self.streaming_task = None;
+        self.stream_finish_reason = None;
+        self.stream_usage = None;
+        self.stream_status = None;
+        self.is_waiting_response = false;
+        self.last_error = None;
+        self.should_focus_input = true; // Request focus after response completes
+        // Don't clear current_response - keep it visible
+    }
+
+    /// Abort the in-flight request, keeping any partial answer as a truncated message.
+    pub fn stop_streaming(&mut self) {
+        if let Some(task) = self.streaming_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.context_summary_task.take() {
+            task.abort();
+        }
+        self.context_summary_receiver = None;
+        self.pending_context_summary = None;
+        self.streaming_receiver = None;
+        self.stream_finish_reason = None;
+        self.stream_status = None;
+        let usage = self.stream_usage.take();
+
+        if !self.is_waiting_response {
+            return;
+        }
+        self.is_waiting_response = false;
+        self.should_focus_input = true;
+
+        if let Some(last_msg) = self.chat_messages.last_mut() {
+            if last_msg.role == "assistant" && last_msg.content.is_empty() {
+                if self.current_response.is_empty() {
+                    // Nothing arrived yet, drop the placeholder
+                    self.chat_messages.pop();
+                } else {
+                    last_msg.content = self.current_response.clone();
+                    last_msg.truncated = true;
+                    last_msg.usage = usage;
+                    self.persist_last_chat_message();
+                }
+            }
+        }
+    }
+
     fn send_to_api(&mut self, ctx: &egui::Context) {
+        if self.context_strategy == ContextStrategy::RollingSummary
+            && self.start_context_summary_if_needed(ctx)
+        {
+            // The chat request is sent once the summary is ready
+            return;
+        }
         self.send_to_api_with_panel("chat", ctx);
     }
 
-    fn send_summary_to_api(
-        &mut self,
-        panel_type: &str,
-        summary_content: String,
-        ctx: &egui::Context,
-    ) {
-        let api_base_url = self.api_base_url.clone();
-        let api_key = self.api_key.clone();
-        let model = self.model.clone();
-        let system_prompt = self.current_system_prompts.get(panel_type).cloned();
-        let ctx_clone = ctx.clone();
+    /// Load the rolling summary and pinned prompt of the current conversation.
+    fn load_conversation_context(&mut self) {
+        self.context_summary = None;
+        self.pinned_chat_prompt = None;
+        let (Some(db), Some(conversation_id)) = (&self.database, self.current_conversation_id)
+        else {
+            return;
+        };
 
-        let (tx, rx) = mpsc::channel();
-        self.streaming_receiver = Some(rx);
+        match db.get_context_summary(conversation_id) {
+            Ok(summary) => self.context_summary = summary,
+            Err(e) => log::error!("Failed to load context summary: {e}"),
+        }
 
-        tokio::spawn(async move {
-            let client = reqwest::Client::new();
-            let api_url = format!("{api_base_url}/chat/completions");
+        let pinned = db
+            .get_pinned_prompt_revision(conversation_id)
+            .and_then(|revision_id| match revision_id {
+                Some(revision_id) => db.get_prompt_revision(revision_id),
+                None => Ok(None),
+            });
+        match pinned {
+            Ok(revision) => self.pinned_chat_prompt = revision,
+            Err(e) => log::error!("Failed to load pinned prompt revision: {e}"),
+        }
+    }
+
+    /// The chat prompt in effect and its revision: the pinned one, if any, else the role's.
+    pub fn chat_prompt(&self) -> Option<(Option<i64>, &str)> {
+        if let Some(pinned) = &self.pinned_chat_prompt {
+            return Some((Some(pinned.id), pinned.prompt_text.as_str()));
+        }
+        self.current_system_prompts.get("chat").map(|prompt| {
+            (
+                self.current_prompt_revisions.get("chat").copied(),
+                prompt.as_str(),
+            )
+        })
+    }
+
+    /// Tokens left for chat messages once system prompts and the answer are accounted for.
+    fn available_context_tokens(&self, system_prompts: &[String]) -> usize {
+        let budget = crate::context_window::context_budget(&self.context_budgets, &self.active_profile.config.model);
+        let system_tokens: usize = system_prompts
+            .iter()
+            .map(|prompt| crate::context_window::estimate_tokens(prompt))
+            .sum();
+        budget
+            .saturating_sub(self.reserved_output_tokens)
+            .saturating_sub(system_tokens)
+    }
 
-            let mut api_messages = Vec::new();
+    /// Position of the last message covered by the rolling summary, if it still applies.
+    pub fn context_summary_end(&self) -> Option<usize> {
+        if self.context_strategy != ContextStrategy::RollingSummary {
+            return None;
+        }
+        let summary = self.context_summary.as_ref()?;
+        self.chat_messages
+            .iter()
+            .position(|msg| !msg.id.is_empty() && msg.id == summary.through_message_id)
+    }
+
+    /// System prompts of a chat request, except the rolling summary.
+    fn chat_system_prompts(&self) -> Vec<String> {
+        let mut system_prompts = Vec::new();
+        if let Some((_, system_prompt)) = self.chat_prompt() {
+            system_prompts.push(system_prompt.to_owned());
+        }
+        if self.use_memory_in_chat {
+            system_prompts.extend(self.build_chat_memory_prompt());
+        }
+        system_prompts
+    }
 
-            // Add system prompt if available
-            if let Some(system_prompt) = system_prompt {
-                api_messages.push(serde_json::json!({
-                    "role": "system",
-                    "content": system_prompt
-                }));
+    /// Index of the oldest chat message that fits into the model's context window.
+    ///
+    /// The chat panel calls this every frame, so memory (which depends on the query)
+    /// is left out of the estimate there.
+    pub fn context_start(&self, include_memory: bool) -> usize {
+        let mut system_prompts = if include_memory {
+            self.chat_system_prompts()
+        } else {
+            self.chat_prompt()
+                .map(|(_, prompt)| prompt.to_owned())
+                .into_iter()
+                .collect()
+        };
+        if self.context_summary_end().is_some() {
+            if let Some(summary) = &self.context_summary {
+                system_prompts.push(crate::context_window::summary_system_prompt(&summary.text));
             }
+        }
+        crate::context_window::first_in_context(
+            &self.chat_messages,
+            self.available_context_tokens(&system_prompts),
+        )
+    }
 
-            // Add ONLY the summary request (no chat history)
-            api_messages.push(serde_json::json!({
-                "role": "user",
-                "content": summary_content
-            }));
-
-            let payload = serde_json::json!({
-                "model": model,
-                "messages": api_messages,
-                "stream": true,
-                "temperature": 0.3
-            });
+    /// Summarize messages that fell out of the context window since the last summary.
+    ///
+    /// Returns `true` when a summary request was started.
+    fn start_context_summary_if_needed(&mut self, ctx: &egui::Context) -> bool {
+        let start = self.context_start(true);
+        if start == 0 {
+            return false;
+        }
 
-            // Debug: Print the HTTP body being sent to LLM
-            println!("🔍 DEBUG - Summary HTTP Body sent to LLM API:");
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&payload)
-                    .unwrap_or_else(|_| "Failed to serialize payload".to_owned())
-            );
-            println!("─────────────────────────────────────");
-
-            match client
-                .post(&api_url)
-                .header("Authorization", format!("Bearer {api_key}"))
-                .header("Content-Type", "application/json")
-                .json(&payload)
-                .send()
-                .await
-            {
-                Ok(resp) => {
-                    if resp.status().is_success() {
-                        let mut stream = resp.bytes_stream();
-                        let mut buffer = String::new();
-
-                        while let Some(chunk_result) = stream.next().await {
-                            match chunk_result {
-                                Ok(chunk) => {
-                                    let chunk_str = String::from_utf8_lossy(&chunk);
-                                    buffer.push_str(&chunk_str);
-
-                                    // Process complete lines
-                                    while let Some(line_end) = buffer.find('\n') {
-                                        let line = buffer[..line_end].trim().to_owned();
-                                        buffer = buffer[line_end + 1..].to_owned();
-
-                                        if let Some(data) = line.strip_prefix("data: ") {
-                                            if data == "[DONE]" {
-                                                _ = tx.send("__STREAM_END__".to_owned());
-                                                ctx_clone.request_repaint();
-                                                return;
-                                            }
-
-                                            if let Ok(json) =
-                                                serde_json::from_str::<serde_json::Value>(data)
-                                            {
-                                                if let Some(choices) = json["choices"].as_array() {
-                                                    if let Some(choice) = choices.first() {
-                                                        if let Some(delta) =
-                                                            choice["delta"].as_object()
-                                                        {
-                                                            if let Some(content) =
-                                                                delta["content"].as_str()
-                                                            {
-                                                                _ =
-                                                                    tx.send(content.to_owned());
-                                                                ctx_clone.request_repaint();
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(_) => break,
-                            }
-                        }
-                    } else {
-                        let status = resp.status();
-                        let error_msg = format!("HTTP error: {status}");
-                        _ = tx.send(error_msg);
-                        ctx_clone.request_repaint();
+        let summary_end = self.context_summary_end();
+        // Unsaved messages cannot be referenced later; they are just dropped
+        let Some(range) = crate::context_window::messages_to_summarize(&self.chat_messages, start, summary_end) else {
+            return false;
+        };
+        let through_message_id = self.chat_messages[range.end - 1].id.clone();
+
+        let previous = summary_end
+            .and(self.context_summary.as_ref())
+            .map(|summary| summary.text.as_str());
+        let summary_content =
+            crate::context_window::build_summary_request(previous, &self.chat_messages[range]);
+
+        let request = ChatRequest {
+            model: self.active_profile.config.model.clone(),
+            system_prompts: self
+                .current_system_prompts
+                .get("digest")
+                .cloned()
+                .into_iter()
+                .collect(),
+            messages: vec![("user".to_owned(), summary_content)],
+            params: self.generation_params("digest"),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        self.context_summary_receiver = Some(rx);
+        self.pending_context_summary = Some(ContextSummary {
+            through_message_id,
+            text: String::new(),
+        });
+        self.stream_status = Some("summarizing earlier messages…".to_owned());
+
+        self.context_summary_task = Some(tokio::spawn(crate::streaming::stream_chat(
+            self.connection(),
+            request,
+            self.retry_policy,
+            tx,
+            ctx.clone(),
+            self.network_recorder(),
+        )));
+        true
+    }
+
+    /// Collect the rolling summary and send the chat request once it is complete.
+    fn poll_context_summary(&mut self, ctx: &egui::Context) {
+        let Some(receiver) = &self.context_summary_receiver else {
+            return;
+        };
+
+        let mut finished = false;
+        let mut failed = false;
+        loop {
+            match receiver.try_recv() {
+                Ok(StreamEvent::Delta(content)) => {
+                    if let Some(pending) = &mut self.pending_context_summary {
+                        pending.text.push_str(&content);
                     }
                 }
-                Err(e) => {
-                    let error_msg = format!("Connection error: {e}");
-                    _ = tx.send(error_msg);
-                    ctx_clone.request_repaint();
+                Ok(StreamEvent::Done) => {
+                    finished = true;
+                    break;
+                }
+                Ok(StreamEvent::Error { kind, status, body }) => {
+                    let message = StreamEvent::error_message(kind, status, &body);
+                    log::warn!("Failed to summarize earlier messages: {message}");
+                    failed = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    failed = true;
+                    break;
                 }
             }
-        });
+        }
+
+        self.context_summary_receiver = None;
+        self.context_summary_task = None;
+        self.stream_status = None;
+        let pending = self.pending_context_summary.take();
+
+        if finished {
+            if let Some(summary) = pending.filter(|summary| !summary.text.trim().is_empty()) {
+                if let (Some(db), Some(conversation_id)) =
+                    (&self.database, self.current_conversation_id)
+                {
+                    if let Err(e) = db.set_context_summary(conversation_id, Some(&summary)) {
+                        log::error!("Failed to save context summary: {e}");
+                    }
+                }
+                self.context_summary = Some(summary);
+            }
+        }
+        if failed {
+            // Fall back to dropping the turns that did not fit
+            log::warn!("Sending the chat request without an updated summary");
+        }
+
+        self.send_to_api_with_panel("chat", ctx);
     }
 
-    #[expect(clippy::too_many_lines)]
-    fn send_to_api_with_panel(&mut self, panel_type: &str, ctx: &egui::Context) {
-        let api_base_url = self.api_base_url.clone();
-        let api_key = self.api_key.clone();
-        let model = self.model.clone();
-        let messages = self.chat_messages.clone();
-        let system_prompt = self.current_system_prompts.get(panel_type).cloned();
+    fn send_summary_to_api(
+        &mut self,
+        panel_type: &str,
+        summary_content: String,
+        ctx: &egui::Context,
+    ) {
+        let connection = self.connection();
         let ctx_clone = ctx.clone();
 
+        // Send ONLY the summary request (no chat history)
+        let request = ChatRequest {
+            model: self.active_profile.config.model.clone(),
+            system_prompts: self
+                .current_system_prompts
+                .get(panel_type)
+                .cloned()
+                .into_iter()
+                .collect(),
+            messages: vec![("user".to_owned(), summary_content)],
+            params: self.generation_params(panel_type),
+        };
+
         let (tx, rx) = mpsc::channel();
         self.streaming_receiver = Some(rx);
 
-        tokio::spawn(async move {
-            let client = reqwest::Client::new();
-            let api_url = format!("{api_base_url}/chat/completions");
+        self.streaming_task = Some(tokio::spawn(crate::streaming::stream_chat(
+            connection,
+            request,
+            self.retry_policy,
+            tx,
+            ctx_clone,
+            self.network_recorder(),
+        )));
+    }
 
-            let mut api_messages = Vec::new();
+    fn send_to_api_with_panel(&mut self, panel_type: &str, ctx: &egui::Context) {
+        let connection = self.connection();
+        let ctx_clone = ctx.clone();
 
-            // Add system prompt if available
-            if let Some(system_prompt) = system_prompt {
-                api_messages.push(serde_json::json!({
-                    "role": "system",
-                    "content": system_prompt
-                }));
-            }
+        let mut system_prompts = Vec::new();
+        let mut context_start = 0;
+
+        if panel_type == "chat" {
+            // System prompt plus relevant long-term memory as extra context
+            system_prompts = self.chat_system_prompts();
 
-            // Add user and assistant messages
-            for msg in &messages {
-                if !msg.content.is_empty() {
-                    api_messages.push(serde_json::json!({
-                        "role": msg.role,
-                        "content": msg.content
-                    }));
+            // Remember which prompt revision produces the answer
+            let prompt_revision_id = self.chat_prompt().and_then(|(revision_id, _)| revision_id);
+            if let Some(placeholder) = self.chat_messages.last_mut() {
+                if placeholder.role == "assistant" && placeholder.content.is_empty() {
+                    placeholder.prompt_revision_id = prompt_revision_id;
                 }
             }
 
-            let payload = serde_json::json!({
-                "model": model,
-                "messages": api_messages,
-                "stream": true,
-                "temperature": 0.3
-            });
-
-            // Debug: Print the HTTP body being sent to LLM
-            println!("🔍 DEBUG - HTTP Body sent to LLM API:");
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&payload)
-                    .unwrap_or_else(|_| "Failed to serialize payload".to_owned())
-            );
-            println!("─────────────────────────────────────");
-
-            match client
-                .post(&api_url)
-                .header("Authorization", format!("Bearer {api_key}"))
-                .header("Content-Type", "application/json")
-                .json(&payload)
-                .send()
-                .await
-            {
-                Ok(resp) => {
-                    if resp.status().is_success() {
-                        let mut stream = resp.bytes_stream();
-                        let mut buffer = String::new();
-
-                        while let Some(chunk_result) = stream.next().await {
-                            match chunk_result {
-                                Ok(chunk) => {
-                                    let chunk_str = String::from_utf8_lossy(&chunk);
-                                    buffer.push_str(&chunk_str);
-
-                                    let lines: Vec<String> =
-                                        buffer.split('\n').map(|s| s.to_owned()).collect();
-                                    let processed_lines: Vec<String> =
-                                        lines[..lines.len().saturating_sub(1)].to_vec();
-                                    buffer = lines.last().cloned().unwrap_or_default();
-
-                                    for line in processed_lines {
-                                        if let Some(data) = line.strip_prefix("data: ") {
-                                            if data == "[DONE]" {
-                                                _ = tx.send("__STREAM_END__".to_owned());
-                                                ctx_clone.request_repaint();
-                                                return;
-                                            }
-
-                                            if let Ok(json) =
-                                                serde_json::from_str::<serde_json::Value>(data)
-                                            {
-                                                if let Some(choices) = json["choices"].as_array() {
-                                                    if let Some(choice) = choices.first() {
-                                                        if let Some(delta) =
-                                                            choice["delta"].as_object()
-                                                        {
-                                                            if let Some(content) =
-                                                                delta["content"].as_str()
-                                                            {
-                                                                _ =
-                                                                    tx.send(content.to_owned());
-                                                                ctx_clone.request_repaint();
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(_) => break,
-                            }
-                        }
-                    } else {
-                        let status = resp.status();
-                        let error_body = match resp.text().await {
-                            Ok(body) => body,
-                            Err(_) => "Unknown error".to_owned(),
-                        };
-                        _ = tx.send(format!("Error: HTTP {status} - {error_body}"));
-                        ctx_clone.request_repaint();
-                    }
-                }
-                Err(e) => {
-                    _ = tx.send(format!("Connection error: {e}"));
-                    ctx_clone.request_repaint();
+            // Leave out turns that no longer fit into the context window
+            context_start = self.context_start(true);
+            if self.context_summary_end().is_some() {
+                if let Some(summary) = &self.context_summary {
+                    system_prompts
+                        .push(crate::context_window::summary_system_prompt(&summary.text));
                 }
             }
-        });
+        } else if let Some(system_prompt) = self.current_system_prompts.get(panel_type) {
+            system_prompts.push(system_prompt.clone());
+        }
+
+        // Add user and assistant messages
+        let request = ChatRequest {
+            model: self.active_profile.config.model.clone(),
+            system_prompts,
+            messages: self.chat_messages[context_start..]
+                .iter()
+                .filter(|msg| !msg.content.is_empty())
+                .map(|msg| (msg.role.clone(), msg.content.clone()))
+                .collect(),
+            params: self.generation_params(panel_type),
+        };
+
+        if panel_type == "chat" {
+            // The override from the input area is spent on this message
+            self.request_params = GenerationParams::default();
+        }
+
+        let (tx, rx) = mpsc::channel();
+        self.streaming_receiver = Some(rx);
+
+        self.streaming_task = Some(tokio::spawn(crate::streaming::stream_chat(
+            connection,
+            request,
+            self.retry_policy,
+            tx,
+            ctx_clone,
+            self.network_recorder(),
+        )));
     }
 }
 
@@ -1018,55 +1547,72 @@ impl eframe::App for TemplateApp {
     /// Called each time the UI needs repainting, which may be many times per second.
     #[expect(clippy::too_many_lines)]
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_context_summary(ctx);
+        self.poll_model_discovery(ctx);
+        self.poll_network_log();
+
         // Handle streaming responses
         if let Some(receiver) = &self.streaming_receiver {
             // Request frequent repaints while streaming
             ctx.request_repaint();
 
-            // Process all available messages in this update cycle
+            // Process all available events in this update cycle
             let mut received_any = false;
             loop {
                 match receiver.try_recv() {
-                    Ok(content) => {
+                    Ok(event) => {
                         received_any = true;
-                        if content == "__STREAM_END__" {
-                            // Stream finished - add final response to chat history
-                            if !self.current_response.is_empty() {
-                                // Find the last assistant message and update it
-                                if let Some(last_msg) = self.chat_messages.last_mut() {
-                                    if last_msg.role == "assistant" {
-                                        last_msg.content = self.current_response.clone();
-                                        // Save assistant response to database
-                                        let msg_to_save = last_msg.clone();
-                                        self.save_chat_message_to_db(&msg_to_save);
-                                    }
-                                }
+                        match event {
+                            StreamEvent::Delta(content) => {
+                                // Add content to current streaming response
+                                self.current_response.push_str(&content);
+                                self.last_error = None;
+                                self.stream_status = None;
+                            }
+                            StreamEvent::Retrying {
+                                retry,
+                                max_retries,
+                                delay_ms,
+                                reason,
+                            } => {
+                                log::warn!(
+                                    "Retrying request ({retry}/{max_retries}) in {delay_ms} ms: {reason}"
+                                );
+                                self.stream_status = Some(format!(
+                                    "retrying ({retry}/{max_retries})… {reason}"
+                                ));
+                            }
+                            StreamEvent::Usage {
+                                prompt_tokens,
+                                completion_tokens,
+                            } => {
+                                // Some APIs report prompt and completion tokens in separate events
+                                let usage = self.stream_usage.get_or_insert_with(|| TokenUsage {
+                                    model: self.active_profile.config.model.clone(),
+                                    ..Default::default()
+                                });
+                                usage.prompt_tokens = usage.prompt_tokens.max(prompt_tokens);
+                                usage.completion_tokens =
+                                    usage.completion_tokens.max(completion_tokens);
+                            }
+                            StreamEvent::FinishReason(reason) => {
+                                self.stream_finish_reason = Some(reason);
+                            }
+                            StreamEvent::Done => {
+                                self.finish_streaming_response();
+                                break;
+                            }
+                            StreamEvent::Error { kind, status, body } => {
+                                // Whatever arrived is kept as a truncated answer, as with Stop
+                                self.last_error = Some(StreamEvent::error_message(kind, status, &body));
+                                self.stop_streaming();
+                                break;
                             }
-                            self.streaming_receiver = None;
-                            self.is_waiting_response = false;
-                            self.last_error = None;
-                            self.should_focus_input = true; // Request focus after response completes
-                            // Don't clear current_response - keep it visible
-                            break;
-                        } else if content.is_empty() {
-                            // Empty content can be part of streaming, don't close
-                        } else if content.starts_with("Error:")
-                            || content.starts_with("Connection error:")
-                        {
-                            // Handle errors
-                            self.last_error = Some(content.clone());
-                            self.current_response = format!("❌ {content}");
-                            self.streaming_receiver = None;
-                            self.is_waiting_response = false;
-                            break;
-                        } else {
-                            // Add content to current streaming response
-                            self.current_response.push_str(&content);
-                            self.last_error = None;
                         }
                     }
                     Err(std::sync::mpsc::TryRecvError::Disconnected) => {
                         self.streaming_receiver = None;
+                        self.streaming_task = None;
                         self.is_waiting_response = false;
                         break;
                     }
@@ -1087,6 +1633,45 @@ impl eframe::App for TemplateApp {
                 let is_web = cfg!(target_arch = "wasm32");
                 if !is_web {
                     ui.menu_button("File", |ui| {
+                        if ui.button("📂 Open Workspace…").clicked() {
+                            self.open_workspace_dialog();
+                        }
+                        let mut switch_to = None;
+                        ui.menu_button("Recent Workspaces", |ui| {
+                            let current_path = self.database.as_ref().map(|db| db.path());
+                            for recent in &self.recent_workspaces {
+                                let is_current = current_path == Some(recent.as_path());
+                                if ui
+                                    .add_enabled(
+                                        !is_current,
+                                        egui::Button::new(crate::workspace::workspace_name(recent)),
+                                    )
+                                    .on_hover_text(recent.display().to_string())
+                                    .clicked()
+                                {
+                                    switch_to = Some(recent.clone());
+                                }
+                            }
+                            if self.recent_workspaces.is_empty() {
+                                ui.label("No recent workspaces");
+                            }
+                        });
+                        if let Some(path) = switch_to {
+                            self.switch_workspace(&path);
+                        }
+                        if ui
+                            .button("🔎 Search Everything")
+                            .on_hover_text("Ctrl+Shift+F")
+                            .clicked()
+                        {
+                            self.open_search_window();
+                        }
+                        if ui.button("📤 Export…").clicked() {
+                            self.open_export_dialog();
+                        }
+                        if ui.button("📥 Import…").clicked() {
+                            self.open_import_dialog();
+                        }
                         if ui.button("Load from DB").clicked() {
                             self.load_data_from_database();
                         }
@@ -1094,6 +1679,12 @@ impl eframe::App for TemplateApp {
                         if ui.button("Settings").clicked() {
                             self.show_settings = true;
                         }
+                        if ui.button("👥 Manage Roles").clicked() {
+                            self.open_role_editor();
+                        }
+                        if ui.button("📡 Network Log").clicked() {
+                            self.show_network_log = true;
+                        }
                         ui.separator();
                         if ui.button("🎨 Color Test").clicked() {
                             self.show_color_test = true;
@@ -1108,6 +1699,10 @@ impl eframe::App for TemplateApp {
                 }
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     egui::widgets::global_theme_preference_buttons(ui);
+                    if let Some(db) = &self.database {
+                        ui.label(format!("🗂 {}", crate::workspace::workspace_name(db.path())))
+                            .on_hover_text(db.path().display().to_string());
+                    }
                 });
             });
         });
@@ -1118,12 +1713,13 @@ impl eframe::App for TemplateApp {
             .min_height(60.0)
             .show(ctx, |ui| {
                 ui.add_space(6.0);
+                self.render_request_params_drawer(ui);
                 ui.horizontal(|ui| {
                     // Add prompt indicator with phosphor icon (fill variant, enlarged)
                     ui.label(egui::RichText::new(egui_phosphor::fill::CARET_LINE_RIGHT).color(egui::Color32::from_rgb(0x8E, 0x94, 0x9B)).size(18.0));
 
                     let input_response = ui.add_sized(
-                        [ui.available_width() - 85.0, 25.0],
+                        [ui.available_width() - 115.0, 25.0],
                         egui::TextEdit::singleline(&mut self.chat_input)
                             .hint_text("Type your message... (输入你的消息...)")
                             .font(egui::TextStyle::Body),
@@ -1133,18 +1729,12 @@ impl eframe::App for TemplateApp {
                     if input_response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))
                         && !self.chat_input.trim().is_empty() && !self.is_waiting_response {
                         // Send message logic here
-                        let user_message = ChatMessage {
-                            role: "user".to_owned(),
-                            content: self.chat_input.clone(),
-                        };
-                        self.save_chat_message_to_db(&user_message);
+                        let user_message = ChatMessage::new("user", self.chat_input.clone());
                         self.chat_messages.push(user_message);
+                        self.persist_last_chat_message();
 
                         // Add placeholder for assistant response
-                        self.chat_messages.push(ChatMessage {
-                            role: "assistant".to_owned(),
-                            content: String::new(),
-                        });
+                        self.chat_messages.push(ChatMessage::new("assistant", ""));
 
                         self.is_waiting_response = true;
                         self.current_response.clear();
@@ -1161,6 +1751,19 @@ impl eframe::App for TemplateApp {
                         self.should_focus_input = false;
                     }
 
+                    self.render_request_params_toggle(ui);
+
+                    if self.is_waiting_response {
+                        if ui
+                            .button("⏹ Stop")
+                            .on_hover_text("Stop generating and keep the partial answer")
+                            .clicked()
+                        {
+                            self.stop_streaming();
+                        }
+                        return;
+                    }
+
                     let send_enabled =
                         !self.chat_input.trim().is_empty() && !self.is_waiting_response;
                     if ui
@@ -1168,18 +1771,12 @@ impl eframe::App for TemplateApp {
                         .clicked()
                         && !self.chat_input.trim().is_empty() && !self.is_waiting_response {
                         // Same send logic as Enter key
-                        let user_message = ChatMessage {
-                            role: "user".to_owned(),
-                            content: self.chat_input.clone(),
-                        };
-                        self.save_chat_message_to_db(&user_message);
+                        let user_message = ChatMessage::new("user", self.chat_input.clone());
                         self.chat_messages.push(user_message);
+                        self.persist_last_chat_message();
 
                         // Add placeholder for assistant response
-                        self.chat_messages.push(ChatMessage {
-                            role: "assistant".to_owned(),
-                            content: String::new(),
-                        });
+                        self.chat_messages.push(ChatMessage::new("assistant", ""));
 
                         self.is_waiting_response = true;
                         self.current_response.clear();
@@ -1270,6 +1867,13 @@ impl eframe::App for TemplateApp {
                                 self.load_system_prompts_for_current_role();
                                 ctx.request_repaint(); // Keep repainting to show progress bar
                             }
+                            if ui
+                                .small_button("✏ Edit")
+                                .on_hover_text("Edit roles and their system prompts")
+                                .clicked()
+                            {
+                                self.open_role_editor();
+                            }
                         } else {
                             ui.add(egui::ProgressBar::new(0.5).animate(true).desired_width(60.0));
 
@@ -1327,6 +1931,37 @@ impl eframe::App for TemplateApp {
                         ui.add_enabled(false, egui::Button::new("🔄 Reload").small())
                             .on_hover_text("Select a role first to enable reload");
                     }
+
+                    self.render_profile_selector(ui);
+
+                    // Conversation pinned to an older chat prompt
+                    if let Some(pinned) = &self.pinned_chat_prompt {
+                        let hover = format!(
+                            "This conversation uses chat prompt revision r{} ({}, {}) instead of the role's current prompt. Click to unpin.",
+                            pinned.id, pinned.author, pinned.created_at
+                        );
+                        if ui
+                            .small_button(format!("📌 r{}", pinned.id))
+                            .on_hover_text(hover)
+                            .clicked()
+                        {
+                            self.pin_prompt_revision(None);
+                        }
+                    }
+
+                    // Long-term memory toggle for chat requests
+                    let selected_memory = self
+                        .long_term_memory_items
+                        .iter()
+                        .filter(|item| item.selected)
+                        .count();
+                    let memory_hint = if selected_memory > 0 {
+                        format!("{selected_memory} selected memory items will be sent with each message")
+                    } else {
+                        "The most relevant memory items will be sent with each message".to_owned()
+                    };
+                    ui.toggle_value(&mut self.use_memory_in_chat, "🗄 Memory")
+                        .on_hover_text(memory_hint);
                 });
             });
 
@@ -1335,6 +1970,13 @@ impl eframe::App for TemplateApp {
             crate::color_test::show_color_test_window(ctx, &mut self.show_color_test);
         }
 
+        self.render_role_editor_window(ctx);
+        self.render_workspace_dialog(ctx);
+        self.render_search_window(ctx);
+        self.render_export_dialog(ctx);
+        self.render_import_dialog(ctx);
+        self.render_network_log_window(ctx);
+
         // Show settings window if requested
         let mut show_settings = self.show_settings;
         if show_settings {
@@ -1347,19 +1989,18 @@ impl eframe::App for TemplateApp {
                         .default_open(true)
                         .show(ui, |ui| {
 
-                    ui.horizontal(|ui| {
-                        ui.label("Base URL:");
-                        ui.text_edit_singleline(&mut self.temp_api_base_url);
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("API Key:");
-                        ui.text_edit_singleline(&mut self.temp_api_key);
-                    });
+                    self.render_profile_settings(ui);
 
                     ui.horizontal(|ui| {
-                        ui.label("Model:");
-                        ui.text_edit_singleline(&mut self.temp_model);
+                        ui.label("Retries:");
+                        ui.add(egui::DragValue::new(&mut self.retry_policy.max_retries).range(0..=10))
+                            .on_hover_text("Connection errors, HTTP 429 and 5xx are retried before any text arrives");
+                        ui.label("Initial backoff (ms):");
+                        ui.add(
+                            egui::DragValue::new(&mut self.retry_policy.initial_backoff_ms)
+                                .range(100..=60_000)
+                                .speed(100),
+                        );
                     });
                         });
 
@@ -1415,6 +2056,121 @@ impl eframe::App for TemplateApp {
 
                     ui.separator();
 
+                    egui::CollapsingHeader::new("Long-term Memory")
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            ui.checkbox(&mut self.use_memory_in_chat, "Include long-term memory in chat")
+                                .on_hover_text("Selected memory items are always sent; otherwise the most relevant ones are picked");
+
+                            ui.add_enabled_ui(self.use_memory_in_chat, |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("Max relevant items:");
+                                    ui.add(egui::DragValue::new(&mut self.memory_context_limit).range(1..=20));
+                                });
+                            });
+                        });
+
+                    ui.separator();
+
+                    egui::CollapsingHeader::new("Context Window")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("When history is too long:");
+                                egui::ComboBox::from_id_salt("context_strategy")
+                                    .selected_text(self.context_strategy.display_name())
+                                    .show_ui(ui, |ui| {
+                                        for strategy in ContextStrategy::ALL {
+                                            ui.selectable_value(&mut self.context_strategy, strategy, strategy.display_name());
+                                        }
+                                    });
+                            });
+                            if self.context_strategy == ContextStrategy::RollingSummary {
+                                ui.colored_label(egui::Color32::GRAY, "Older turns are summarized with the digest prompt of the current role");
+                            }
+
+                            ui.horizontal(|ui| {
+                                ui.label("Reserved for the answer (tokens):");
+                                ui.add(egui::DragValue::new(&mut self.reserved_output_tokens).speed(64).range(256..=65_536));
+                            });
+
+                            ui.label("Context size per model (tokens):");
+                            let mut budget_to_remove = None;
+                            egui::Grid::new("context_budgets_grid").num_columns(3).striped(true).show(ui, |ui| {
+                                for (index, budget) in self.context_budgets.iter_mut().enumerate() {
+                                    ui.add(egui::TextEdit::singleline(&mut budget.model).desired_width(140.0));
+                                    ui.add(egui::DragValue::new(&mut budget.max_tokens).speed(256).range(1024..=2_000_000));
+                                    if ui.small_button("✖").on_hover_text("Remove model").clicked() {
+                                        budget_to_remove = Some(index);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                            if let Some(index) = budget_to_remove {
+                                self.context_budgets.remove(index);
+                            }
+
+                            ui.horizontal(|ui| {
+                                if ui.button("➕ Add model").clicked() {
+                                    self.context_budgets.push(ContextBudget {
+                                        model: self.active_profile.config.model.clone(),
+                                        max_tokens: crate::context_window::FALLBACK_CONTEXT_TOKENS,
+                                    });
+                                }
+                                if ui.button("Reset to defaults").clicked() {
+                                    self.context_budgets = crate::context_window::default_context_budgets();
+                                }
+                            });
+                            ui.colored_label(
+                                egui::Color32::GRAY,
+                                format!("Models not listed use {} tokens", crate::context_window::FALLBACK_CONTEXT_TOKENS),
+                            );
+                        });
+
+                    ui.separator();
+
+                    egui::CollapsingHeader::new("Pricing")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.colored_label(egui::Color32::GRAY, "Price per million tokens, used to estimate the cost of each answer");
+
+                            let mut price_to_remove = None;
+                            egui::Grid::new("model_prices_grid").num_columns(4).striped(true).show(ui, |ui| {
+                                ui.label("Model");
+                                ui.label("Input");
+                                ui.label("Output");
+                                ui.end_row();
+
+                                for (index, price) in self.model_prices.iter_mut().enumerate() {
+                                    ui.add(egui::TextEdit::singleline(&mut price.model).desired_width(140.0));
+                                    ui.add(egui::DragValue::new(&mut price.input_per_million).speed(0.01).range(0.0..=1000.0).prefix("$"));
+                                    ui.add(egui::DragValue::new(&mut price.output_per_million).speed(0.01).range(0.0..=1000.0).prefix("$"));
+                                    if ui.small_button("✖").on_hover_text("Remove price").clicked() {
+                                        price_to_remove = Some(index);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                            if let Some(index) = price_to_remove {
+                                self.model_prices.remove(index);
+                            }
+
+                            ui.horizontal(|ui| {
+                                if ui.button("➕ Add model").clicked() {
+                                    self.model_prices.push(ModelPrice {
+                                        model: self.active_profile.config.model.clone(),
+                                        input_per_million: 0.0,
+                                        output_per_million: 0.0,
+                                    });
+                                }
+                                if ui.button("Reset to defaults").clicked() {
+                                    self.model_prices = crate::usage::default_model_prices();
+                                }
+                            });
+                        });
+
+                    ui.separator();
+
                     egui::CollapsingHeader::new("Database Information")
                         .default_open(true)
                         .show(ui, |ui| {
@@ -1422,19 +2178,26 @@ impl eframe::App for TemplateApp {
                     // Display database path
                     ui.horizontal(|ui| {
                         ui.label("Database Path:");
-                        let db_path = crate::database::Database::get_database_path();
-                        ui.selectable_label(false, db_path.to_string_lossy().to_string())
-                            .on_hover_text("Click to select and copy the database path");
+                        match &self.database {
+                            Some(db) => {
+                                ui.selectable_label(false, db.path().to_string_lossy().to_string())
+                                    .on_hover_text("Click to select and copy the database path");
+                            }
+                            None => {
+                                ui.colored_label(egui::Color32::RED, "No database open");
+                            }
+                        }
                     });
+                    if ui.button("📂 Open Workspace…").clicked() {
+                        self.open_workspace_dialog();
+                    }
                         });
 
                     ui.separator();
                     ui.horizontal(|ui| {
                         if ui.button("Apply").clicked() {
                             // Apply settings
-                            self.api_base_url = self.temp_api_base_url.clone();
-                            self.api_key = self.temp_api_key.clone();
-                            self.model = self.temp_model.clone();
+                            let profile_saved = self.apply_profile_draft();
 
                             // Apply role change
                             if self.current_assistant_role_id != self.temp_assistant_role_id {
@@ -1442,22 +2205,25 @@ impl eframe::App for TemplateApp {
                                 self.load_system_prompts_for_current_role();
                             }
 
-                            self.show_settings = false;
+                            // Stay open so a profile or key that couldn't be stored doesn't go unnoticed
+                            self.show_settings = !profile_saved;
                         }
                         if ui.button("Cancel").clicked() {
                             // Reset temporary values to current values
-                            self.temp_api_base_url = self.api_base_url.clone();
+                            self.profile_draft = self.active_profile.clone();
                             self.temp_api_key = self.api_key.clone();
-                            self.temp_model = self.model.clone();
                             self.temp_assistant_role_id = self.current_assistant_role_id;
+                            self.credential_settings.status = None;
                             self.show_settings = false;
                         }
                     });
                 });
         }
-        self.show_settings = show_settings;
+        // Closed by its title bar button, or by Apply and Cancel above
+        self.show_settings = show_settings && self.show_settings;
 
-        // Render the three panels using the separate modules
+        // Render the panels using the separate modules
+        self.render_conversation_panel(ctx);
         let (digest_actions, memory_actions_from_chat) = self.render_chat_panel(ctx);
         self.render_long_mem_panel(ctx);
         let memory_actions_from_digest = self.render_digest_panel(ctx);
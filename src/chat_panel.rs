@@ -22,24 +22,47 @@ impl TemplateApp {
                 // ui.add_space(6.0);
                 ui.horizontal(|ui| {
                     ui.heading("💬 Chat History");
+                    let (prompt_tokens, completion_tokens, cost) = crate::usage::summarize(
+                        &self.model_prices,
+                        self.chat_messages.iter().filter_map(|message| message.usage.as_ref()),
+                    );
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if ui
                             .button("🗑 Clear")
-                            .on_hover_text("Clear all chat messages")
+                            .on_hover_text("Clear all messages in this conversation")
                             .clicked()
                         {
+                            // Stop first: a partial answer is saved, then cleared with the rest
+                            self.stop_streaming();
+
                             // Clear chat panel associations from database (soft delete)
-                            if let Some(ref db) = self.database {
-                                if let Err(e) = db.clear_chat_panel_associations() {
+                            if let (Some(db), Some(conversation_id)) =
+                                (&self.database, self.current_conversation_id)
+                            {
+                                if let Err(e) = db.clear_chat_panel_associations(conversation_id) {
                                     log::error!("Failed to clear chat panel associations: {e}");
                                     self.last_error = Some(format!("Database error: {e}"));
                                 }
+                                if let Err(e) = db.set_context_summary(conversation_id, None) {
+                                    log::error!("Failed to clear context summary: {e}");
+                                }
                             }
 
                             // Clear UI state
+                            self.context_summary = None;
                             self.chat_messages.clear();
                             self.current_response.clear();
-                            self.is_waiting_response = false;
+                        }
+                        if prompt_tokens + completion_tokens > 0 {
+                            ui.colored_label(
+                                egui::Color32::GRAY,
+                                crate::usage::format_usage(
+                                    prompt_tokens,
+                                    completion_tokens,
+                                    cost,
+                                ),
+                            )
+                            .on_hover_text("Tokens used by this conversation (prompt → completion) and estimated cost");
                         }
                     });
                 });
@@ -66,6 +89,8 @@ impl TemplateApp {
                             "开始对话... (Start a conversation...)",
                         );
                     } else {
+                        let context_start = self.context_start(false);
+                        let summary_end = self.context_summary_end();
                         let search_term = self.chat_search.to_lowercase();
                         let search_query = self.chat_search.clone(); // Keep original case for highlighting
                         let filtered_indices: Vec<usize> = self
@@ -86,14 +111,59 @@ impl TemplateApp {
                         if filtered_indices.is_empty() && !search_term.is_empty() {
                             ui.colored_label(egui::Color32::GRAY, "No messages match your search.");
                         } else {
+                            let now = chrono::Local::now();
+                            let mut previous_date = None;
                             for i in filtered_indices {
+                                let message_date =
+                                    crate::timestamps::local_date(self.chat_messages[i].timestamp_ms);
+                                if message_date.is_some() && message_date != previous_date {
+                                    if let Some(date) = message_date {
+                                        ui.vertical_centered(|ui| {
+                                            ui.colored_label(
+                                                egui::Color32::GRAY,
+                                                crate::timestamps::day_label(date, now),
+                                            );
+                                        });
+                                    }
+                                    previous_date = message_date;
+                                }
+                                let message_id = self.chat_messages[i].id.clone();
+                                self.scroll_to_search_hit(ui, &message_id);
                                 let message = &self.chat_messages[i];
                                 let message_content = message.content.clone(); // Clone to avoid borrowing issues
                                 let message_role = message.role.clone();
+                                let message_truncated = message.truncated;
+                                let prompt_revision_id = message.prompt_revision_id;
+                                let timestamp_ms = message.timestamp_ms;
+                                let time_label = crate::timestamps::format_relative(timestamp_ms, now);
+                                let context_label = if summary_end.is_some_and(|end| i <= end) {
+                                    Some("⤴ Summarized, no longer sent in full")
+                                } else if i < context_start {
+                                    Some("⤴ Out of context, not sent to the model")
+                                } else {
+                                    None
+                                };
+                                let usage_label = message.usage.as_ref().map(|usage| {
+                                    crate::usage::format_usage(
+                                        usage.prompt_tokens,
+                                        usage.completion_tokens,
+                                        crate::usage::estimate_cost(&self.model_prices, usage),
+                                    )
+                                });
+
+                                if let Some(context_label) = context_label {
+                                    ui.colored_label(egui::Color32::GRAY, context_label);
+                                }
 
                                 if message_role == "user" {
                                     ui.vertical(|ui| {
-                                        ui.colored_label(egui::Color32::DARK_RED, "You:");
+                                        ui.horizontal(|ui| {
+                                            ui.colored_label(egui::Color32::DARK_RED, "You:");
+                                            if !time_label.is_empty() {
+                                                ui.colored_label(egui::Color32::GRAY, &time_label)
+                                                    .on_hover_text(crate::timestamps::format_full(timestamp_ms));
+                                            }
+                                        });
                                         // Add background frame for user messages
                                         let frame = egui::Frame::new()
                                             .fill(egui::Color32::from_rgb(238, 235, 226))
@@ -198,6 +268,11 @@ impl TemplateApp {
                                                     );
                                                 });
                                             });
+                                        } else if let Some(status) = &self.stream_status {
+                                            ui.colored_label(
+                                                egui::Color32::BROWN,
+                                                format!("⟳ {status}"),
+                                            );
                                         } else {
                                             ui.colored_label(egui::Color32::BROWN, "🖊 typing...");
                                         }
@@ -222,6 +297,31 @@ impl TemplateApp {
                                                 }
                                             });
 
+                                            if message_truncated {
+                                                ui.colored_label(
+                                                    egui::Color32::GRAY,
+                                                    "⏹ Response truncated",
+                                                );
+                                            }
+                                            if !time_label.is_empty() || usage_label.is_some() || prompt_revision_id.is_some() {
+                                                ui.horizontal(|ui| {
+                                                    if !time_label.is_empty() {
+                                                        ui.colored_label(egui::Color32::GRAY, &time_label)
+                                                            .on_hover_text(crate::timestamps::format_full(timestamp_ms));
+                                                    }
+                                                    if let Some(usage_label) = &usage_label {
+                                                        ui.colored_label(egui::Color32::GRAY, usage_label);
+                                                    }
+                                                    if let Some(revision_id) = prompt_revision_id {
+                                                        ui.colored_label(
+                                                            egui::Color32::GRAY,
+                                                            format!("📝 prompt r{revision_id}"),
+                                                        )
+                                                        .on_hover_text("System prompt revision that produced this answer");
+                                                    }
+                                                });
+                                            }
+
                                             // Add buttons at the end of message
                                             ui.horizontal(|ui| {
                                                 ui.with_layout(
@@ -274,7 +374,7 @@ impl TemplateApp {
 
                 // Handle message deletion
                 if let Some(index) = message_to_delete {
-                    self.chat_messages.remove(index);
+                    self.delete_chat_message(index);
                 }
             });
 
@@ -0,0 +1,785 @@
+//! Headless commands for scripts and CI, run instead of the window when the
+//! first argument names one of them.
+
+use crate::app::LongTermMemoryItem;
+use crate::credentials::CredentialError;
+use crate::database::Database;
+use crate::export::{ExportFormat, ExportSource};
+use crate::llm_provider::{AuthStyle, ChatRequest};
+use crate::profiles::ProviderProfile;
+use crate::streaming::{RetryPolicy, StreamEvent};
+use crate::workspace::SavedSettings;
+use std::io::{Read as _, Write as _};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::mpsc;
+
+pub const USAGE: &str = "\
+Usage: pbot [--workspace <path>] <command> [options]
+
+Commands:
+  ask [--role <name>] [--conversation <id> | --save] [--no-memory] [<prompt>...]
+        Send a prompt with the role's chat prompt and stream the answer to stdout.
+        The prompt is read from stdin when omitted or `-`. With --conversation the
+        earlier messages are sent too and both turns are saved there; --save
+        stores them in a new conversation instead.
+  digest summarize [--role <name>]
+  memory summarize [--role <name>]
+        Summarize every digest or long term memory item with the role's prompt.
+  export (conversation <id> | digest | memory) [--format md|json|html|pdf] [--output <file>]
+        Write an export to a file, or to stdout without --output.
+  roles            List assistant roles.
+  profiles         List provider profiles.
+  conversations    List conversations with their ids.
+  help             Show this message.
+
+Options for ask and summarize:
+  --profile <name> Provider profile from the workspace to send requests with.
+  --model <name>   Model to use instead of the profile's or LLM_MODEL.
+  --usage          Print token usage to stderr afterwards.
+
+Without --workspace the database named by PBOT_WORKSPACE is used, or else the
+workspace last opened in the window.
+
+Without --profile the API is configured with LLM_PROVIDER, LLM_BASE_URL and LLM_MODEL.
+LLM_API_KEY overrides the profile's key saved in Settings, which is read from the
+system keyring, or from the encrypted credentials file when PBOT_CREDENTIALS_PASSPHRASE
+is set.";
+
+const COMMANDS: &[&str] = &[
+    "ask",
+    "digest",
+    "memory",
+    "export",
+    "roles",
+    "profiles",
+    "conversations",
+    "help",
+];
+
+/// Exit code for invalid arguments, as opposed to failed requests.
+const USAGE_ERROR: u8 = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+struct AskArgs {
+    role: Option<String>,
+    conversation_id: Option<i64>,
+    save: bool,
+    use_memory: bool,
+    /// `None` reads the prompt from stdin.
+    prompt: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Ask(AskArgs),
+    Summarize {
+        panel_type: &'static str,
+        role: Option<String>,
+    },
+    Export {
+        source: ExportSource,
+        format: Option<ExportFormat>,
+        output: Option<PathBuf>,
+    },
+    Roles,
+    Profiles,
+    Conversations,
+    Help,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct CliArgs {
+    command: Command,
+    profile: Option<String>,
+    model: Option<String>,
+    show_usage: bool,
+}
+
+/// Arguments other than `--workspace`, which `workspace::startup_workspace` reads.
+fn without_workspace(args: &[String]) -> Vec<&str> {
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == crate::workspace::WORKSPACE_ARG {
+            args.next();
+        } else if !arg.starts_with(&format!("{}=", crate::workspace::WORKSPACE_ARG)) {
+            rest.push(arg.as_str());
+        }
+    }
+    rest
+}
+
+/// Whether `args` (without the program name) ask for a command rather than the window.
+pub fn is_cli_invocation(args: &[String]) -> bool {
+    without_workspace(args)
+        .first()
+        .is_some_and(|first| COMMANDS.contains(first) || *first == "--help" || *first == "-h")
+}
+
+fn parse_args(args: &[String]) -> Result<CliArgs, String> {
+    let args = without_workspace(args);
+    let mut profile = None;
+    let mut model = None;
+    let mut show_usage = false;
+    let mut role = None;
+    let mut conversation_id = None;
+    let mut save = false;
+    let mut use_memory = true;
+    let mut format = None;
+    let mut output = None;
+    let mut positional = Vec::new();
+
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .map(str::to_owned)
+                .ok_or_else(|| format!("{name} needs a value"))
+        };
+        match arg {
+            "--profile" => profile = Some(value(arg)?),
+            "--model" => model = Some(value(arg)?),
+            "--usage" => show_usage = true,
+            "--role" => role = Some(value(arg)?),
+            "--conversation" => {
+                let id = value(arg)?;
+                conversation_id = Some(
+                    id.parse()
+                        .map_err(|_err| format!("invalid conversation id `{id}`"))?,
+                );
+            }
+            "--save" => save = true,
+            "--no-memory" => use_memory = false,
+            "--format" => {
+                let name = value(arg)?;
+                format = Some(
+                    ExportFormat::from_name(&name)
+                        .ok_or_else(|| format!("unknown format `{name}`"))?,
+                );
+            }
+            "--output" | "-o" => output = Some(PathBuf::from(value(arg)?)),
+            "--help" | "-h" => positional.insert(0, "help"),
+            "-" => positional.push(arg),
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{arg}`")),
+            _ => positional.push(arg),
+        }
+    }
+
+    let (name, rest) = positional.split_first().ok_or("no command given")?;
+    let command = match (*name, rest) {
+        ("ask", prompt) => Command::Ask(AskArgs {
+            role,
+            conversation_id,
+            save,
+            use_memory,
+            prompt: match prompt {
+                [] | ["-"] => None,
+                words => Some(words.join(" ")),
+            },
+        }),
+        ("digest", ["summarize"]) => Command::Summarize {
+            panel_type: "digest",
+            role,
+        },
+        ("memory", ["summarize"]) => Command::Summarize {
+            panel_type: "memory",
+            role,
+        },
+        ("export", ["conversation", id]) => Command::Export {
+            source: ExportSource::Conversation(
+                id.parse()
+                    .map_err(|_err| format!("invalid conversation id `{id}`"))?,
+            ),
+            format,
+            output,
+        },
+        ("export", ["digest"]) => Command::Export {
+            source: ExportSource::Digest,
+            format,
+            output,
+        },
+        ("export", ["memory"]) => Command::Export {
+            source: ExportSource::Memory,
+            format,
+            output,
+        },
+        ("roles", []) => Command::Roles,
+        ("profiles", []) => Command::Profiles,
+        ("conversations", []) => Command::Conversations,
+        ("help", _) => Command::Help,
+        (name, _) => return Err(format!("unknown or incomplete command `{name}`")),
+    };
+    Ok(CliArgs {
+        command,
+        profile,
+        model,
+        show_usage,
+    })
+}
+
+/// Run the command in `args` and report how it went as the process exit code.
+pub async fn run(args: &[String]) -> ExitCode {
+    let cli_args = match parse_args(args) {
+        Ok(cli_args) => cli_args,
+        Err(e) => {
+            eprintln!("pbot: {e}\n\n{USAGE}");
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+    if cli_args.command == Command::Help {
+        _ = write_stdout(format!("{USAGE}\n").as_bytes());
+        return ExitCode::SUCCESS;
+    }
+
+    let settings = SavedSettings::load();
+    let workspace = crate::workspace::startup_workspace(&settings.recent_workspaces);
+    let db = match Database::open(&workspace) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("pbot: could not open {}: {e}", workspace.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match execute(&db, &settings, cli_args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("pbot: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn execute(db: &Database, settings: &SavedSettings, cli_args: CliArgs) -> Result<(), String> {
+    let db_error = |e: rusqlite::Error| format!("database error: {e}");
+    let CliArgs {
+        command,
+        profile,
+        model,
+        show_usage,
+    } = cli_args;
+    let mut profile = match profile {
+        Some(name) => resolve_profile(db, &name)?,
+        None => ProviderProfile::default(),
+    };
+    if let Some(model) = model {
+        profile.config.model = model;
+    }
+
+    match command {
+        Command::Ask(ask_args) => ask(db, settings, &profile, ask_args, show_usage).await,
+        Command::Summarize { panel_type, role } => {
+            summarize(db, &profile, panel_type, role.as_deref(), show_usage).await
+        }
+        Command::Export {
+            source,
+            format,
+            output,
+        } => {
+            if let ExportSource::Conversation(conversation_id) = source {
+                require_conversation(db, conversation_id)?;
+            }
+            let format = format
+                .or_else(|| {
+                    output
+                        .as_ref()
+                        .and_then(|path| path.extension())
+                        .and_then(|extension| ExportFormat::from_name(&extension.to_string_lossy()))
+                })
+                .unwrap_or_default();
+            let document = crate::export::build_document(db, source, crate::timestamps::now_ms())
+                .map_err(db_error)?;
+            let bytes = crate::export::render(&document, format).map_err(|e| e.to_string())?;
+            match output {
+                Some(path) => std::fs::write(&path, bytes)
+                    .map_err(|e| format!("could not write {}: {e}", path.display())),
+                None => write_stdout(&bytes),
+            }
+        }
+        Command::Roles => {
+            let mut listing = String::new();
+            for (_, role_name, display_name, description) in
+                db.get_assistant_roles().map_err(db_error)?
+            {
+                listing.push_str(&format!("{role_name}\t{display_name}\t{description}\n"));
+            }
+            write_stdout(listing.as_bytes())
+        }
+        Command::Profiles => {
+            let mut listing = String::new();
+            for profile in db.get_provider_profiles().map_err(db_error)? {
+                listing.push_str(&format!(
+                    "{}\t{}\t{}\n",
+                    profile.name, profile.config.model, profile.config.base_url
+                ));
+            }
+            write_stdout(listing.as_bytes())
+        }
+        Command::Conversations => {
+            let mut listing = String::new();
+            for conversation in db.get_conversations(true).map_err(db_error)? {
+                let archived = if conversation.is_archived {
+                    "\tarchived"
+                } else {
+                    ""
+                };
+                listing.push_str(&format!(
+                    "{}\t{}\t{}{archived}\n",
+                    conversation.id, conversation.updated_at, conversation.title
+                ));
+            }
+            write_stdout(listing.as_bytes())
+        }
+        Command::Help => Ok(()),
+    }
+}
+
+/// Write to stdout, treating a reader that went away (`pbot roles | head`) as success.
+fn write_stdout(bytes: &[u8]) -> Result<(), String> {
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(bytes).and_then(|()| stdout.flush()) {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut prompt = String::new();
+    std::io::stdin()
+        .read_to_string(&mut prompt)
+        .map_err(|e| format!("could not read the prompt from stdin: {e}"))?;
+    Ok(prompt)
+}
+
+/// Role named `name` (its `role_name` or display name), or the first role like the window picks.
+fn resolve_role(db: &Database, name: Option<&str>) -> Result<(i64, String), String> {
+    let roles = db
+        .get_assistant_roles()
+        .map_err(|e| format!("database error: {e}"))?;
+    let role = match name {
+        Some(name) => roles.iter().find(|(_, role_name, display_name, _)| {
+            role_name.eq_ignore_ascii_case(name) || display_name.eq_ignore_ascii_case(name)
+        }),
+        None => roles.first(),
+    };
+    role.map(|(id, role_name, ..)| (*id, role_name.clone()))
+        .ok_or_else(|| {
+            let names: Vec<&str> = roles
+                .iter()
+                .map(|(_, role_name, ..)| role_name.as_str())
+                .collect();
+            match name {
+                Some(name) => format!("no role `{name}`; available: {}", names.join(", ")),
+                None => "no assistant roles defined".to_owned(),
+            }
+        })
+}
+
+fn resolve_profile(db: &Database, name: &str) -> Result<ProviderProfile, String> {
+    let profiles = db
+        .get_provider_profiles()
+        .map_err(|e| format!("database error: {e}"))?;
+    if let Some(profile) = profiles
+        .iter()
+        .find(|profile| profile.name.eq_ignore_ascii_case(name))
+    {
+        return Ok(profile.clone());
+    }
+    let names: Vec<&str> = profiles
+        .iter()
+        .map(|profile| profile.name.as_str())
+        .collect();
+    Err(format!(
+        "no profile `{name}`; available: {}",
+        names.join(", ")
+    ))
+}
+
+/// Fail with "no conversation <id>" unless the conversation exists.
+fn require_conversation(db: &Database, conversation_id: i64) -> Result<(), String> {
+    match db.get_conversation(conversation_id) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(format!("no conversation {conversation_id}")),
+        Err(e) => Err(format!("database error: {e}")),
+    }
+}
+
+/// Summarize every digest or long term memory item with the role's prompt.
+async fn summarize(
+    db: &Database,
+    profile: &ProviderProfile,
+    panel_type: &str,
+    role: Option<&str>,
+    show_usage: bool,
+) -> Result<(), String> {
+    let db_error = |e: rusqlite::Error| format!("database error: {e}");
+    let (role_id, _) = resolve_role(db, role)?;
+    let prompts = db.get_system_prompts_for_role(role_id).map_err(db_error)?;
+    let request = if panel_type == "digest" {
+        let items = db.load_digest_items().map_err(db_error)?;
+        if items.is_empty() {
+            return Err("the digest is empty".to_owned());
+        }
+        crate::app::build_items_summary_request(
+            "digest items",
+            items.iter().map(|item| {
+                (
+                    item.source.as_str(),
+                    item.timestamp_ms,
+                    item.content.as_str(),
+                )
+            }),
+        )
+    } else {
+        let items = db.load_longterm_memory_items().map_err(db_error)?;
+        if items.is_empty() {
+            return Err("long term memory is empty".to_owned());
+        }
+        crate::app::build_items_summary_request(
+            "long term memory items",
+            items.iter().map(|item| {
+                (
+                    item.source.as_str(),
+                    item.timestamp_ms,
+                    item.content.as_str(),
+                )
+            }),
+        )
+    };
+    let request = ChatRequest {
+        model: profile.config.model.clone(),
+        system_prompts: prompts.get(panel_type).cloned().into_iter().collect(),
+        messages: vec![("user".to_owned(), request)],
+        params: db
+            .get_role_generation_params(role_id)
+            .map_err(db_error)?
+            .resolve(panel_type, &profile.config.params),
+    };
+    stream_to_stdout(profile, request, show_usage)
+        .await
+        .map(|_| ())
+}
+
+/// Send one prompt, saving both turns when a conversation is given or `--save` is set.
+#[expect(clippy::too_many_lines)]
+async fn ask(
+    db: &Database,
+    settings: &SavedSettings,
+    profile: &ProviderProfile,
+    ask_args: AskArgs,
+    show_usage: bool,
+) -> Result<(), String> {
+    let db_error = |e: rusqlite::Error| format!("database error: {e}");
+    let AskArgs {
+        role,
+        conversation_id,
+        save,
+        use_memory,
+        prompt,
+    } = ask_args;
+    let prompt = match prompt {
+        Some(prompt) => prompt,
+        None => read_stdin()?,
+    };
+    if prompt.trim().is_empty() {
+        return Err("the prompt is empty".to_owned());
+    }
+    if let Some(conversation_id) = conversation_id {
+        require_conversation(db, conversation_id)?;
+    }
+
+    let (role_id, role_name) = resolve_role(db, role.as_deref())?;
+    let prompts = db.get_system_prompts_for_role(role_id).map_err(db_error)?;
+
+    // A prompt revision pinned to the conversation wins over the role's current one, as in the window
+    let pinned_revision = match conversation_id {
+        Some(id) => match db.get_pinned_prompt_revision(id).map_err(db_error)? {
+            Some(revision_id) => db.get_prompt_revision(revision_id).map_err(db_error)?,
+            None => None,
+        },
+        None => None,
+    };
+    let (prompt_revision_id, chat_prompt) = if let Some(revision) = pinned_revision {
+        (Some(revision.id), Some(revision.prompt_text))
+    } else {
+        let revision_ids = db
+            .get_prompt_revision_ids_for_role(role_id)
+            .map_err(db_error)?;
+        (
+            revision_ids.get("chat").copied(),
+            prompts.get("chat").cloned(),
+        )
+    };
+    let mut system_prompts: Vec<String> = chat_prompt.into_iter().collect();
+    if use_memory {
+        let memory_items: Vec<LongTermMemoryItem> =
+            db.load_longterm_memory_items().map_err(db_error)?;
+        let selected = crate::memory_context::select_memory_items(
+            &memory_items,
+            &prompt,
+            crate::memory_context::DEFAULT_MEMORY_CONTEXT_LIMIT,
+        );
+        system_prompts.extend(crate::memory_context::build_memory_prompt(
+            prompts.get("memory").map(String::as_str),
+            &selected,
+        ));
+    }
+
+    let mut history = match conversation_id {
+        Some(id) => db.load_chat_messages(id).map_err(db_error)?,
+        None => Vec::new(),
+    };
+    history.push(crate::app::ChatMessage::new("user", prompt.clone()));
+    let system_tokens: usize = system_prompts
+        .iter()
+        .map(|prompt| crate::context_window::estimate_tokens(prompt))
+        .sum();
+    let available_tokens =
+        crate::context_window::context_budget(&settings.context_budgets, &profile.config.model)
+            .saturating_sub(settings.reserved_output_tokens)
+            .saturating_sub(system_tokens);
+    let start = crate::context_window::first_in_context(&history, available_tokens);
+
+    let request = ChatRequest {
+        model: profile.config.model.clone(),
+        system_prompts,
+        messages: history[start..]
+            .iter()
+            .filter(|message| !message.content.is_empty())
+            .map(|message| (message.role.clone(), message.content.clone()))
+            .collect(),
+        params: db
+            .get_role_generation_params(role_id)
+            .map_err(db_error)?
+            .resolve("chat", &profile.config.params),
+    };
+    let user_timestamp_ms = crate::timestamps::now_ms();
+    let answer = stream_to_stdout(profile, request, show_usage).await?;
+
+    let conversation_id = match (conversation_id, save) {
+        (Some(id), _) => id,
+        (None, true) => {
+            let title: String = prompt.trim().chars().take(40).collect();
+            db.create_conversation(&title).map_err(db_error)?
+        }
+        (None, false) => return Ok(()),
+    };
+    db.save_content(
+        &prompt,
+        "user",
+        user_timestamp_ms,
+        Some(conversation_id),
+        &["chat"],
+    )
+    .map_err(db_error)?;
+    let answer_id = db
+        .save_content(
+            &answer.text,
+            "assistant",
+            crate::timestamps::now_ms(),
+            Some(conversation_id),
+            &["chat"],
+        )
+        .map_err(db_error)?;
+    if answer.truncated {
+        db.set_content_truncated(&answer_id, true)
+            .map_err(db_error)?;
+    }
+    if let Some(usage) = &answer.usage {
+        db.set_content_usage(&answer_id, usage).map_err(db_error)?;
+    }
+    if let Some(revision_id) = prompt_revision_id {
+        db.set_content_prompt_revision(&answer_id, revision_id)
+            .map_err(db_error)?;
+    }
+    eprintln!("Saved to conversation {conversation_id} (role {role_name})");
+    Ok(())
+}
+
+struct Answer {
+    text: String,
+    truncated: bool,
+    usage: Option<crate::usage::TokenUsage>,
+}
+
+/// Send `request` with `profile` and its stored key, printing the answer as it streams in.
+async fn stream_to_stdout(
+    profile: &ProviderProfile,
+    request: ChatRequest,
+    show_usage: bool,
+) -> Result<Answer, String> {
+    let api_key = if profile.config.auth_style == AuthStyle::None {
+        String::new()
+    } else {
+        match crate::credentials::api_key_without_ui(profile) {
+            Ok(api_key) => api_key,
+            // No keyring on this machine; local servers often need no key anyway
+            Err(CredentialError::Keyring(e)) => {
+                eprintln!("pbot: no stored API key: {e}");
+                String::new()
+            }
+            Err(e) => return Err(format!("could not read the API key: {e}")),
+        }
+    };
+    let model = request.model.clone();
+
+    let (tx, rx) = mpsc::channel();
+    // Nothing repaints without a window; the context only satisfies `stream_chat`
+    let task = tokio::spawn(crate::streaming::stream_chat(
+        profile.config.connection(api_key),
+        request,
+        RetryPolicy::default(),
+        tx,
+        egui::Context::default(),
+        None,
+    ));
+
+    let mut answer = Answer {
+        text: String::new(),
+        truncated: false,
+        usage: None,
+    };
+    let result = tokio::task::block_in_place(|| {
+        let mut stdout = std::io::stdout().lock();
+        for event in rx {
+            match event {
+                StreamEvent::Delta(delta) => {
+                    answer.text.push_str(&delta);
+                    _ = stdout.write_all(delta.as_bytes());
+                    _ = stdout.flush();
+                }
+                StreamEvent::Usage {
+                    prompt_tokens,
+                    completion_tokens,
+                } => {
+                    // Anthropic reports input tokens up front and output tokens at the end
+                    let usage = answer
+                        .usage
+                        .get_or_insert_with(|| crate::usage::TokenUsage {
+                            model: model.clone(),
+                            ..Default::default()
+                        });
+                    usage.prompt_tokens = usage.prompt_tokens.max(prompt_tokens);
+                    usage.completion_tokens = usage.completion_tokens.max(completion_tokens);
+                }
+                StreamEvent::FinishReason(reason) => {
+                    answer.truncated = matches!(reason.as_str(), "length" | "max_tokens");
+                }
+                StreamEvent::Retrying {
+                    retry,
+                    max_retries,
+                    delay_ms,
+                    reason,
+                } => {
+                    eprintln!(
+                        "{reason}; retry {retry}/{max_retries} in {:.1}s",
+                        delay_ms as f64 / 1000.0
+                    );
+                }
+                StreamEvent::Error { kind, status, body } => {
+                    return Err(StreamEvent::error_message(kind, status, &body));
+                }
+                StreamEvent::Done => break,
+            }
+        }
+        if !answer.text.ends_with('\n') {
+            _ = stdout.write_all(b"\n");
+        }
+        Ok(())
+    });
+    _ = task.await;
+    result?;
+
+    if answer.truncated {
+        eprintln!("⏹ Response truncated");
+    }
+    if show_usage {
+        if let Some(usage) = &answer.usage {
+            eprintln!(
+                "{}",
+                crate::usage::format_usage(usage.prompt_tokens, usage.completion_tokens, None)
+            );
+        }
+    }
+    Ok(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn commands_are_told_apart_from_the_window() {
+        assert!(is_cli_invocation(&args("ask hello")));
+        assert!(is_cli_invocation(&args(
+            "--workspace acme.db digest summarize"
+        )));
+        assert!(!is_cli_invocation(&args("--workspace acme.db")));
+        assert!(!is_cli_invocation(&[]));
+    }
+
+    #[test]
+    fn ask_takes_role_and_prompt_words() {
+        let parsed = parse_args(&args("--workspace=acme.db ask --role contract_template_selection Is this ok? --profile Ollama --usage"))
+            .expect("parse");
+        assert_eq!(
+            parsed,
+            CliArgs {
+                command: Command::Ask(AskArgs {
+                    role: Some("contract_template_selection".to_owned()),
+                    conversation_id: None,
+                    save: false,
+                    use_memory: true,
+                    prompt: Some("Is this ok?".to_owned()),
+                }),
+                profile: Some("Ollama".to_owned()),
+                model: None,
+                show_usage: true,
+            }
+        );
+    }
+
+    #[test]
+    fn export_format_follows_the_flag() {
+        let parsed =
+            parse_args(&args("export conversation 3 --format pdf -o call.pdf")).expect("parse");
+        assert_eq!(
+            parsed.command,
+            Command::Export {
+                source: ExportSource::Conversation(3),
+                format: Some(ExportFormat::Pdf),
+                output: Some(PathBuf::from("call.pdf")),
+            }
+        );
+        assert!(
+            parse_args(&args("export conversation x")).is_err(),
+            "ids are numbers"
+        );
+        assert!(
+            parse_args(&args("digest")).is_err(),
+            "digest needs a subcommand"
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_conversations_are_reported_before_anything_is_written() {
+        let db = Database::open(std::path::Path::new(":memory:")).expect("open database");
+        for line in [
+            "ask --conversation 42 Is the NDA mutual?",
+            "export conversation 42",
+        ] {
+            let parsed = parse_args(&args(line)).expect("parse");
+            assert_eq!(
+                execute(&db, &SavedSettings::default(), parsed).await,
+                Err("no conversation 42".to_owned()),
+                "{line}"
+            );
+        }
+        assert!(db.get_conversations(true).expect("load").is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+use crate::app::ChatMessage;
+
+/// What to do with turns that no longer fit into the model's context window.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub enum ContextStrategy {
+    /// Leave the oldest turns out of the request.
+    DropOldest,
+    /// Replace the oldest turns with a summary written by the `digest` prompt.
+    RollingSummary,
+}
+
+impl ContextStrategy {
+    pub const ALL: [Self; 2] = [Self::DropOldest, Self::RollingSummary];
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::DropOldest => "Drop oldest turns",
+            Self::RollingSummary => "Summarize oldest turns",
+        }
+    }
+}
+
+/// Context window size of a model, in tokens.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ContextBudget {
+    pub model: String,
+    pub max_tokens: usize,
+}
+
+/// Tokens kept free for the answer unless configured otherwise.
+pub const DEFAULT_RESERVED_OUTPUT_TOKENS: usize = 4096;
+
+/// Used for models missing from the budget table.
+pub const FALLBACK_CONTEXT_TOKENS: usize = 8192;
+
+/// Role markers and separators cost a few tokens per message.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+pub fn default_context_budgets() -> Vec<ContextBudget> {
+    vec![
+        ContextBudget {
+            model: "deepseek-chat".to_owned(),
+            max_tokens: 64_000,
+        },
+        ContextBudget {
+            model: "deepseek-reasoner".to_owned(),
+            max_tokens: 64_000,
+        },
+    ]
+}
+
+pub fn context_budget(budgets: &[ContextBudget], model: &str) -> usize {
+    budgets
+        .iter()
+        .find(|budget| budget.model == model)
+        .map_or(FALLBACK_CONTEXT_TOKENS, |budget| budget.max_tokens)
+}
+
+/// Rough token count without a tokenizer: about four characters per token for
+/// alphabetic scripts, one token per CJK character.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut cjk_chars = 0;
+    let mut other_chars = 0_usize;
+    for c in text.chars() {
+        if crate::memory_context::is_cjk(c) {
+            cjk_chars += 1;
+        } else {
+            other_chars += 1;
+        }
+    }
+    cjk_chars + other_chars.div_ceil(4)
+}
+
+pub fn estimate_message_tokens(message: &ChatMessage) -> usize {
+    estimate_tokens(&message.content) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Index of the oldest message that still fits into `available_tokens`.
+///
+/// Messages are kept newest first. Everything from the latest user message on is
+/// always kept, and the window never starts with an assistant turn since some
+/// APIs reject that.
+pub fn first_in_context(messages: &[ChatMessage], available_tokens: usize) -> usize {
+    let last_user = messages
+        .iter()
+        .rposition(|message| message.role == "user")
+        .unwrap_or(0);
+
+    let mut used = 0;
+    let mut start = messages.len();
+    for (index, message) in messages.iter().enumerate().rev() {
+        used += estimate_message_tokens(message);
+        if used > available_tokens && index < last_user {
+            break;
+        }
+        start = index;
+    }
+
+    while start < last_user && messages[start].role != "user" {
+        start += 1;
+    }
+    start
+}
+
+/// A summary standing in for every message up to and including `through_message_id`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContextSummary {
+    pub through_message_id: String,
+    pub text: String,
+}
+
+/// Messages the rolling summary should take in before `start`, the first message
+/// still in the context window: those after `summary_end`, the last one the
+/// current summary covers.
+///
+/// `None` when there are none, or when the last of them is unsaved and so can't
+/// be referenced as the summary's end later.
+pub fn messages_to_summarize(
+    messages: &[ChatMessage],
+    start: usize,
+    summary_end: Option<usize>,
+) -> Option<std::ops::Range<usize>> {
+    let first_unsummarized = summary_end.map_or(0, |end| end + 1);
+    if first_unsummarized >= start || messages[start - 1].id.is_empty() {
+        return None;
+    }
+    Some(first_unsummarized..start)
+}
+
+/// Ask for a summary of `messages`, extending `previous` if there is one.
+pub fn build_summary_request(previous: Option<&str>, messages: &[ChatMessage]) -> String {
+    let mut content = String::new();
+    if let Some(previous) = previous {
+        content.push_str("Summary of the conversation so far:\n\n");
+        content.push_str(previous);
+        content.push_str("\n\nLater messages:\n\n");
+    } else {
+        content.push_str("Conversation:\n\n");
+    }
+    for message in messages {
+        let role = &message.role;
+        let text = &message.content;
+        content.push_str(&format!("{role}: {text}\n\n"));
+    }
+    content.push_str(
+        "Please write one concise summary of the whole conversation above, keeping facts, \
+         decisions and open questions that later messages may refer to.",
+    );
+    content
+}
+
+pub fn summary_system_prompt(summary: &str) -> String {
+    format!("Summary of earlier messages in this conversation:\n\n{summary}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::TemplateApp;
+
+    fn message_with(id: &str, role: &str, content: String) -> ChatMessage {
+        ChatMessage {
+            id: id.to_owned(),
+            timestamp_ms: 0,
+            ..ChatMessage::new(role, content)
+        }
+    }
+
+    /// A saved message of 13 estimated tokens: 36 characters plus the overhead.
+    fn message(id: &str, role: &str) -> ChatMessage {
+        message_with(id, role, "x".repeat(36))
+    }
+
+    fn conversation() -> Vec<ChatMessage> {
+        vec![
+            message("1", "user"),
+            message("2", "assistant"),
+            message("3", "user"),
+            message("4", "assistant"),
+            message("5", "user"),
+        ]
+    }
+
+    #[test]
+    fn tokens_are_estimated_per_script() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(
+            estimate_tokens("秘密保持？"),
+            5,
+            "full-width punctuation counts like CJK"
+        );
+        assert_eq!(
+            estimate_tokens("NDA 秘密"),
+            3,
+            "four narrow characters and two wide ones"
+        );
+        assert_eq!(estimate_message_tokens(&message("1", "user")), 13);
+    }
+
+    #[test]
+    fn window_fills_up_to_the_budget_and_starts_with_a_user_turn() {
+        let messages = conversation();
+        assert_eq!(
+            first_in_context(&messages, 65),
+            0,
+            "everything fits exactly"
+        );
+        assert_eq!(
+            first_in_context(&messages, 64),
+            2,
+            "one token short drops the first turn"
+        );
+        assert_eq!(
+            first_in_context(&messages, 39),
+            2,
+            "three messages fit exactly"
+        );
+        assert_eq!(
+            first_in_context(&messages, 38),
+            4,
+            "the window doesn't start with the assistant turn that still fits"
+        );
+        assert_eq!(first_in_context(&[], 100), 0);
+    }
+
+    #[test]
+    fn latest_user_message_is_kept_even_when_it_alone_exceeds_the_budget() {
+        let mut messages = conversation();
+        assert_eq!(first_in_context(&messages, 5), 4);
+
+        // The placeholder for the answer being streamed follows the user message
+        messages.push(message_with("", "assistant", String::new()));
+        assert_eq!(first_in_context(&messages, 0), 4);
+    }
+
+    #[test]
+    fn rolling_summary_takes_in_what_fell_out_since_the_last_summary() {
+        let mut messages = conversation();
+        assert_eq!(
+            messages_to_summarize(&messages, 0, None),
+            None,
+            "nothing fell out"
+        );
+        assert_eq!(messages_to_summarize(&messages, 2, None), Some(0..2));
+        assert_eq!(
+            messages_to_summarize(&messages, 4, Some(1)),
+            Some(2..4),
+            "extends the summary"
+        );
+        assert_eq!(
+            messages_to_summarize(&messages, 2, Some(1)),
+            None,
+            "already summarized"
+        );
+
+        messages[3].id.clear();
+        assert_eq!(
+            messages_to_summarize(&messages, 4, Some(1)),
+            None,
+            "unsaved end can't be referenced"
+        );
+    }
+
+    #[test]
+    fn summary_prompt_takes_the_place_of_the_summarized_turns() {
+        let mut app = TemplateApp {
+            chat_messages: conversation(),
+            context_strategy: ContextStrategy::RollingSummary,
+            context_budgets: vec![ContextBudget {
+                model: "tiny".to_owned(),
+                max_tokens: 100,
+            }],
+            reserved_output_tokens: 35,
+            ..TemplateApp::default()
+        };
+        app.active_profile.config.model = "tiny".to_owned();
+        assert_eq!(app.context_summary_end(), None);
+        assert_eq!(app.context_start(false), 0, "65 tokens fit");
+
+        app.context_summary = Some(ContextSummary {
+            through_message_id: "2".to_owned(),
+            text: "x".repeat(4),
+        });
+        assert_eq!(app.context_summary_end(), Some(1));
+        // The summary's system prompt costs tokens too, so the oldest turn drops out
+        assert_eq!(app.context_start(false), 2);
+
+        app.context_strategy = ContextStrategy::DropOldest;
+        assert_eq!(
+            app.context_summary_end(),
+            None,
+            "summaries only apply when summarizing"
+        );
+        assert_eq!(app.context_start(false), 0);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::app::TemplateApp;
+
+enum ConversationAction {
+    Switch(i64),
+    StartRename(i64, String),
+    Rename(i64, String),
+    CancelRename,
+    SetArchived(i64, bool),
+}
+
+impl TemplateApp {
+    #[expect(clippy::too_many_lines)]
+    pub fn render_conversation_panel(&mut self, ctx: &egui::Context) {
+        let mut action: Option<ConversationAction> = None;
+        let mut create_new = false;
+
+        egui::SidePanel::left("conversations")
+            .default_width(200.0)
+            .min_width(150.0)
+            .max_width(300.0)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("🗂 Conversations");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui
+                            .add_enabled(!self.is_waiting_response, egui::Button::new("➕"))
+                            .on_hover_text("Start a new conversation")
+                            .clicked()
+                        {
+                            create_new = true;
+                        }
+                    });
+                });
+
+                let previous_show_archived = self.show_archived_conversations;
+                ui.checkbox(&mut self.show_archived_conversations, "Show archived");
+                if previous_show_archived != self.show_archived_conversations {
+                    self.load_conversations();
+                }
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        if self.conversations.is_empty() {
+                            ui.colored_label(egui::Color32::GRAY, "No conversations yet.");
+                        }
+
+                        for conversation in &self.conversations {
+                            let is_current = self.current_conversation_id == Some(conversation.id);
+
+                            // Inline rename editor
+                            if let Some((renaming_id, title)) = &mut self.renaming_conversation {
+                                if *renaming_id == conversation.id {
+                                    ui.horizontal(|ui| {
+                                        let response = ui.add(
+                                            egui::TextEdit::singleline(title)
+                                                .desired_width(ui.available_width() - 50.0),
+                                        );
+                                        response.request_focus();
+                                        if ui
+                                            .small_button("✔")
+                                            .on_hover_text("Save title")
+                                            .clicked()
+                                            || (response.lost_focus()
+                                                && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                                        {
+                                            action = Some(ConversationAction::Rename(
+                                                conversation.id,
+                                                title.clone(),
+                                            ));
+                                        }
+                                        if ui.small_button("✖").on_hover_text("Cancel").clicked()
+                                            || ui.input(|i| i.key_pressed(egui::Key::Escape))
+                                        {
+                                            action = Some(ConversationAction::CancelRename);
+                                        }
+                                    });
+                                    continue;
+                                }
+                            }
+
+                            ui.horizontal(|ui| {
+                                let title = if conversation.is_archived {
+                                    egui::RichText::new(format!("🗄 {}", conversation.title))
+                                        .color(egui::Color32::GRAY)
+                                } else {
+                                    egui::RichText::new(&conversation.title)
+                                };
+
+                                let response = ui
+                                    .add_enabled(
+                                        !self.is_waiting_response || is_current,
+                                        egui::Button::selectable(is_current, title).truncate(),
+                                    )
+                                    .on_hover_text(format!(
+                                        "Last updated: {}",
+                                        conversation.updated_at
+                                    ));
+                                if response.clicked() && !is_current {
+                                    action = Some(ConversationAction::Switch(conversation.id));
+                                }
+                                if response.double_clicked() {
+                                    action = Some(ConversationAction::StartRename(
+                                        conversation.id,
+                                        conversation.title.clone(),
+                                    ));
+                                }
+
+                                response.context_menu(|ui| {
+                                    if ui.button("✏ Rename").clicked() {
+                                        action = Some(ConversationAction::StartRename(
+                                            conversation.id,
+                                            conversation.title.clone(),
+                                        ));
+                                        ui.close();
+                                    }
+                                    let archive_label = if conversation.is_archived {
+                                        "📤 Unarchive"
+                                    } else {
+                                        "🗄 Archive"
+                                    };
+                                    if ui
+                                        .add_enabled(
+                                            !(is_current && self.is_waiting_response),
+                                            egui::Button::new(archive_label),
+                                        )
+                                        .clicked()
+                                    {
+                                        action = Some(ConversationAction::SetArchived(
+                                            conversation.id,
+                                            !conversation.is_archived,
+                                        ));
+                                        ui.close();
+                                    }
+                                });
+                            });
+                        }
+                    });
+            });
+
+        if create_new {
+            self.new_conversation();
+        }
+
+        match action {
+            Some(ConversationAction::Switch(conversation_id)) => {
+                self.switch_conversation(conversation_id);
+            }
+            Some(ConversationAction::StartRename(conversation_id, title)) => {
+                self.renaming_conversation = Some((conversation_id, title));
+            }
+            Some(ConversationAction::Rename(conversation_id, title)) => {
+                self.renaming_conversation = None;
+                self.rename_conversation(conversation_id, &title);
+            }
+            Some(ConversationAction::CancelRename) => {
+                self.renaming_conversation = None;
+            }
+            Some(ConversationAction::SetArchived(conversation_id, is_archived)) => {
+                self.set_conversation_archived(conversation_id, is_archived);
+            }
+            None => {}
+        }
+    }
+}
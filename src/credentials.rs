@@ -0,0 +1,649 @@
+//! API keys kept out of the environment: in the OS credential store (Secret
+//! Service, macOS Keychain, Windows Credential Manager), or for headless
+//! machines without one, in a file encrypted with a passphrase.
+
+use crate::app::TemplateApp;
+use crate::profiles::ProviderProfile;
+use chacha20poly1305::aead::rand_core::RngCore as _;
+use chacha20poly1305::aead::{Aead as _, AeadCore as _, KeyInit as _, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::collections::BTreeMap;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Service name the keys are filed under in the OS credential store.
+const KEYRING_SERVICE: &str = "egui-chatbot";
+
+/// Unlocks the encrypted file without asking, for scripts and the command line.
+pub const PASSPHRASE_ENV_VAR: &str = "PBOT_CREDENTIALS_PASSPHRASE";
+
+/// Encrypted to tell a wrong passphrase apart from a file without keys.
+const VERIFIER: &[u8] = b"pbot credentials";
+
+#[derive(Debug)]
+pub enum CredentialError {
+    Keyring(keyring::Error),
+    Io(std::io::Error),
+    WrongPassphrase,
+    Corrupt(String),
+}
+
+impl std::fmt::Display for CredentialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Keyring(e) => write!(f, "keyring: {e}"),
+            Self::Io(e) => write!(f, "credentials file: {e}"),
+            Self::WrongPassphrase => write!(f, "wrong passphrase for the credentials file"),
+            Self::Corrupt(reason) => write!(f, "credentials file is damaged: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for CredentialError {}
+
+impl From<keyring::Error> for CredentialError {
+    fn from(e: keyring::Error) -> Self {
+        Self::Keyring(e)
+    }
+}
+
+impl From<std::io::Error> for CredentialError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Somewhere to keep one secret per provider account, looked up by account name.
+pub trait CredentialStore {
+    fn load(&self, account: &str) -> Result<Option<String>, CredentialError>;
+
+    fn save(&self, account: &str, secret: &str) -> Result<(), CredentialError>;
+
+    /// Remove the secret of `account`; removing one that isn't there is not an error.
+    fn delete(&self, account: &str) -> Result<(), CredentialError>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum CredentialBackend {
+    #[default]
+    Keyring,
+    EncryptedFile,
+}
+
+impl CredentialBackend {
+    pub const ALL: [Self; 2] = [Self::Keyring, Self::EncryptedFile];
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::Keyring => "System keyring",
+            Self::EncryptedFile => "Encrypted file",
+        }
+    }
+}
+
+/// The OS credential store, through the `keyring` crate.
+pub struct KeyringStore;
+
+impl CredentialStore for KeyringStore {
+    fn load(&self, account: &str) -> Result<Option<String>, CredentialError> {
+        match keyring::Entry::new(KEYRING_SERVICE, account)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, account: &str, secret: &str) -> Result<(), CredentialError> {
+        Ok(keyring::Entry::new(KEYRING_SERVICE, account)?.set_password(secret)?)
+    }
+
+    fn delete(&self, account: &str) -> Result<(), CredentialError> {
+        match keyring::Entry::new(KEYRING_SERVICE, account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// On-disk layout of the encrypted file. Only the secrets are encrypted; account names are not.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CredentialFile {
+    version: u32,
+    /// Argon2id salt, hex.
+    salt: String,
+    verifier: Sealed,
+    secrets: BTreeMap<String, Sealed>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write as _;
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, CredentialError> {
+    if hex.len() % 2 != 0 {
+        return Err(CredentialError::Corrupt("odd hex length".to_owned()));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(hex.get(i..i + 2).unwrap_or_default(), 16)
+                .map_err(|e| CredentialError::Corrupt(e.to_string()))
+        })
+        .collect()
+}
+
+/// Secrets encrypted with ChaCha20-Poly1305 under a key derived from a passphrase with Argon2id.
+///
+/// Holds the derived key, never the passphrase, for as long as it stays unlocked.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    salt: Vec<u8>,
+    cipher: ChaCha20Poly1305,
+}
+
+impl EncryptedFileStore {
+    /// Next to the default workspace, e.g. `~/.local/share/egui-chatbot/credentials.json`.
+    pub fn default_path() -> PathBuf {
+        crate::database::Database::default_path().with_file_name("credentials.json")
+    }
+
+    fn derive_cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, CredentialError> {
+        let mut key = [0_u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| CredentialError::Corrupt(e.to_string()))?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    /// Open the file at `path` with `passphrase`, creating it when it doesn't exist yet.
+    pub fn unlock(path: &Path, passphrase: &str) -> Result<Self, CredentialError> {
+        if !path.exists() {
+            let mut salt = vec![0_u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            let store = Self {
+                path: path.to_owned(),
+                cipher: Self::derive_cipher(passphrase, &salt)?,
+                salt,
+            };
+            store.write(BTreeMap::new())?;
+            return Ok(store);
+        }
+
+        let file = Self::read_file(path)?;
+        let salt = from_hex(&file.salt)?;
+        let store = Self {
+            path: path.to_owned(),
+            cipher: Self::derive_cipher(passphrase, &salt)?,
+            salt,
+        };
+        if store.open(&file.verifier, "").ok().as_deref() != Some(VERIFIER) {
+            return Err(CredentialError::WrongPassphrase);
+        }
+        Ok(store)
+    }
+
+    fn read_file(path: &Path) -> Result<CredentialFile, CredentialError> {
+        let text = std::fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| CredentialError::Corrupt(e.to_string()))
+    }
+
+    /// Encrypt `plaintext`, binding it to `account` so entries can't be swapped around.
+    fn seal(&self, plaintext: &[u8], account: &str) -> Result<Sealed, CredentialError> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: account.as_bytes(),
+                },
+            )
+            .map_err(|e| CredentialError::Corrupt(e.to_string()))?;
+        Ok(Sealed {
+            nonce: to_hex(&nonce),
+            ciphertext: to_hex(&ciphertext),
+        })
+    }
+
+    fn open(&self, sealed: &Sealed, account: &str) -> Result<Vec<u8>, CredentialError> {
+        let nonce = from_hex(&sealed.nonce)?;
+        if nonce.len() != 12 {
+            return Err(CredentialError::Corrupt("bad nonce".to_owned()));
+        }
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &from_hex(&sealed.ciphertext)?,
+                    aad: account.as_bytes(),
+                },
+            )
+            .map_err(|_err| CredentialError::WrongPassphrase)
+    }
+
+    fn write(&self, secrets: BTreeMap<String, Sealed>) -> Result<(), CredentialError> {
+        let file = CredentialFile {
+            version: 1,
+            salt: to_hex(&self.salt),
+            verifier: self.seal(VERIFIER, "")?,
+            secrets,
+        };
+        let json = serde_json::to_string_pretty(&file)
+            .map_err(|e| CredentialError::Corrupt(e.to_string()))?;
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Written beside the file and renamed over it, so a crash can't leave half a file
+        let temp_path = self.path.with_extension("json.tmp");
+        let mut temp_file = create_owner_only(&temp_path)?;
+        temp_file.write_all(json.as_bytes())?;
+        temp_file.sync_all()?;
+        drop(temp_file);
+        std::fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+
+    fn secrets(&self) -> Result<BTreeMap<String, Sealed>, CredentialError> {
+        Ok(Self::read_file(&self.path)?.secrets)
+    }
+}
+
+/// Create `path` empty, readable only by the owner on unix from the moment it exists.
+fn create_owner_only(path: &Path) -> std::io::Result<std::fs::File> {
+    // A file left by an earlier crash keeps its old permissions; start over instead
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt as _;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn load(&self, account: &str) -> Result<Option<String>, CredentialError> {
+        let Some(sealed) = self.secrets()?.remove(account) else {
+            return Ok(None);
+        };
+        String::from_utf8(self.open(&sealed, account)?)
+            .map(Some)
+            .map_err(|e| CredentialError::Corrupt(e.to_string()))
+    }
+
+    fn save(&self, account: &str, secret: &str) -> Result<(), CredentialError> {
+        let mut secrets = self.secrets()?;
+        secrets.insert(account.to_owned(), self.seal(secret.as_bytes(), account)?);
+        self.write(secrets)
+    }
+
+    fn delete(&self, account: &str) -> Result<(), CredentialError> {
+        let mut secrets = self.secrets()?;
+        if secrets.remove(account).is_some() {
+            self.write(secrets)?;
+        }
+        Ok(())
+    }
+}
+
+/// Start of the credential ids given to profiles that existed before keys were
+/// filed by id; see migration 15.
+const LEGACY_CREDENTIAL_PREFIX: &str = "legacy-";
+
+/// Key of `profile` in `store`.
+///
+/// Keys used to be filed by profile name, which profiles in other workspaces may
+/// share; for profiles from that time such a key is copied to the profile's own
+/// entry, never moved.
+fn load_profile_key(
+    store: &dyn CredentialStore,
+    profile: &ProviderProfile,
+) -> Result<Option<String>, CredentialError> {
+    if let Some(secret) = store.load(&profile.credential_id)? {
+        return Ok(Some(secret));
+    }
+    if !profile.credential_id.starts_with(LEGACY_CREDENTIAL_PREFIX) {
+        return Ok(None);
+    }
+    let legacy = store.load(&profile.name)?;
+    if let Some(secret) = &legacy {
+        store.save(&profile.credential_id, secret)?;
+    }
+    Ok(legacy)
+}
+
+/// Key for `profile` where nobody can be asked: `LLM_API_KEY`, then the encrypted
+/// file if [`PASSPHRASE_ENV_VAR`] is set, then the OS credential store.
+pub fn api_key_without_ui(profile: &ProviderProfile) -> Result<String, CredentialError> {
+    if let Ok(api_key) = std::env::var("LLM_API_KEY") {
+        return Ok(api_key);
+    }
+    let path = EncryptedFileStore::default_path();
+    let secret = match std::env::var(PASSPHRASE_ENV_VAR) {
+        Ok(passphrase) if path.exists() => {
+            load_profile_key(&EncryptedFileStore::unlock(&path, &passphrase)?, profile)?
+        }
+        Ok(_) => None,
+        Err(_) => load_profile_key(&KeyringStore, profile)?,
+    };
+    Ok(secret.unwrap_or_default())
+}
+
+/// State of the API key field in the Settings window.
+#[derive(Default)]
+pub struct CredentialSettingsState {
+    pub reveal_api_key: bool,
+    pub passphrase_input: String,
+    /// What happened on the last load or save, shown under the key.
+    pub status: Option<Result<String, String>>,
+}
+
+impl TemplateApp {
+    /// The store selected in Settings, `None` while the encrypted file is locked.
+    fn credential_store(&self) -> Option<&dyn CredentialStore> {
+        match self.credential_backend {
+            CredentialBackend::Keyring => Some(&KeyringStore),
+            CredentialBackend::EncryptedFile => self
+                .credential_file
+                .as_ref()
+                .map(|store| store as &dyn CredentialStore),
+        }
+    }
+
+    /// Fill in the API key of the active profile.
+    pub fn load_api_key(&mut self) {
+        self.api_key = self.stored_api_key(&self.active_profile.clone());
+        self.temp_api_key = self.api_key.clone();
+    }
+
+    /// Key of `profile`, or `LLM_API_KEY` which overrides them all; empty when there is none.
+    pub fn stored_api_key(&mut self, profile: &ProviderProfile) -> String {
+        if let Ok(api_key) = std::env::var("LLM_API_KEY") {
+            return api_key;
+        }
+        if self.credential_backend == CredentialBackend::EncryptedFile
+            && self.credential_file.is_none()
+        {
+            if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+                self.unlock_credential_file(&passphrase);
+            }
+        }
+        let Some(store) = self.credential_store() else {
+            return String::new();
+        };
+        match load_profile_key(store, profile) {
+            Ok(secret) => secret.unwrap_or_default(),
+            Err(e) => {
+                log::warn!("Failed to load the API key of {}: {e}", profile.name);
+                self.credential_settings.status = Some(Err(e.to_string()));
+                String::new()
+            }
+        }
+    }
+
+    /// Store `api_key` for `profile`, or remove it when empty.
+    pub fn save_api_key(&mut self, profile: &ProviderProfile, api_key: &str) {
+        let Some(store) = self.credential_store() else {
+            self.credential_settings.status =
+                Some(Err("Unlock the credentials file to save the key".to_owned()));
+            return;
+        };
+        let result = if api_key.is_empty() {
+            store.delete(&profile.credential_id)
+        } else {
+            store.save(&profile.credential_id, api_key)
+        };
+        self.credential_settings.status = Some(match result {
+            Ok(()) if api_key.is_empty() => Ok(format!(
+                "Key removed from {}",
+                self.credential_backend.display_name().to_lowercase()
+            )),
+            Ok(()) => Ok(format!(
+                "Key saved to {}",
+                self.credential_backend.display_name().to_lowercase()
+            )),
+            Err(e) => {
+                log::error!("Failed to save the API key: {e}");
+                Err(e.to_string())
+            }
+        });
+    }
+
+    fn unlock_credential_file(&mut self, passphrase: &str) {
+        match EncryptedFileStore::unlock(&EncryptedFileStore::default_path(), passphrase) {
+            Ok(store) => {
+                self.credential_file = Some(store);
+                self.credential_settings.status = None;
+            }
+            Err(e) => self.credential_settings.status = Some(Err(e.to_string())),
+        }
+    }
+
+    /// Backend choice, masked key field and, for the encrypted file, its passphrase.
+    pub fn render_api_key_settings(&mut self, ui: &mut egui::Ui) {
+        let from_env = std::env::var_os("LLM_API_KEY").is_some();
+
+        ui.horizontal(|ui| {
+            ui.label("API Key:");
+            ui.add_enabled(
+                !from_env,
+                egui::TextEdit::singleline(&mut self.temp_api_key)
+                    .password(!self.credential_settings.reveal_api_key)
+                    .desired_width(220.0),
+            );
+            let reveal = &mut self.credential_settings.reveal_api_key;
+            if ui
+                .selectable_label(*reveal, "👁")
+                .on_hover_text(if *reveal { "Hide key" } else { "Show key" })
+                .clicked()
+            {
+                *reveal = !*reveal;
+            }
+        });
+        if from_env {
+            ui.colored_label(
+                egui::Color32::GRAY,
+                "Set by LLM_API_KEY, which takes precedence over the stored key",
+            );
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Store keys in:");
+            egui::ComboBox::from_id_salt("credential_backend")
+                .selected_text(self.credential_backend.display_name())
+                .show_ui(ui, |ui| {
+                    for backend in CredentialBackend::ALL {
+                        ui.selectable_value(
+                            &mut self.credential_backend,
+                            backend,
+                            backend.display_name(),
+                        );
+                    }
+                });
+        });
+
+        if self.credential_backend == CredentialBackend::EncryptedFile
+            && self.credential_file.is_none()
+        {
+            let path = EncryptedFileStore::default_path();
+            ui.horizontal(|ui| {
+                ui.label("Passphrase:");
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.credential_settings.passphrase_input)
+                        .password(true)
+                        .desired_width(160.0),
+                );
+                let entered =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                let label = if path.exists() {
+                    "🔓 Unlock"
+                } else {
+                    "Create"
+                };
+                if (ui.button(label).clicked() || entered)
+                    && !self.credential_settings.passphrase_input.is_empty()
+                {
+                    let passphrase = std::mem::take(&mut self.credential_settings.passphrase_input);
+                    self.unlock_credential_file(&passphrase);
+                    if self.temp_api_key.is_empty() {
+                        self.temp_api_key = self.stored_api_key(&self.profile_draft.clone());
+                    }
+                }
+            })
+            .response
+            .on_hover_text(path.display().to_string());
+        }
+
+        match &self.credential_settings.status {
+            Some(Ok(message)) => {
+                ui.colored_label(egui::Color32::GRAY, message);
+            }
+            Some(Err(message)) => {
+                ui.colored_label(egui::Color32::RED, message);
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::DEFAULT_PROFILE_NAME;
+
+    fn temp_credentials_path() -> PathBuf {
+        std::env::temp_dir().join(format!("pbot-credentials-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn encrypted_file_keeps_one_secret_per_profile() {
+        let path = temp_credentials_path();
+        let store = EncryptedFileStore::unlock(&path, "correct horse").expect("create file");
+        store.save("default", "sk-first").expect("save");
+        store.save("work", "sk-second").expect("save");
+        store.delete("default").expect("delete");
+        store
+            .delete("never-saved")
+            .expect("deleting a missing secret succeeds");
+
+        let reopened = EncryptedFileStore::unlock(&path, "correct horse").expect("unlock");
+        assert_eq!(
+            reopened.load("default").expect("load"),
+            None,
+            "deleted secret is gone"
+        );
+        assert_eq!(
+            reopened.load("work").expect("load").as_deref(),
+            Some("sk-second")
+        );
+
+        let contents = std::fs::read_to_string(&path).expect("read file");
+        assert!(
+            !contents.contains("sk-second"),
+            "secret is stored in plain text"
+        );
+        std::fs::remove_file(&path).expect("clean up");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn credential_file_is_only_readable_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt as _;
+        let path = temp_credentials_path();
+        // A leftover temp file with loose permissions must not be reused
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, "stale").expect("write");
+        std::fs::set_permissions(&temp_path, std::fs::Permissions::from_mode(0o644))
+            .expect("chmod");
+
+        let store = EncryptedFileStore::unlock(&path, "correct horse").expect("create file");
+        store.save("default", "sk-secret").expect("save");
+        let mode = std::fs::metadata(&path).expect("metadata").permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!temp_path.exists(), "temp file is renamed into place");
+        std::fs::remove_file(&path).expect("clean up");
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected_even_without_secrets() {
+        let path = temp_credentials_path();
+        EncryptedFileStore::unlock(&path, "right").expect("create file");
+        assert!(
+            matches!(
+                EncryptedFileStore::unlock(&path, "wrong"),
+                Err(CredentialError::WrongPassphrase)
+            ),
+            "wrong passphrase unlocked the file"
+        );
+        std::fs::remove_file(&path).expect("clean up");
+    }
+
+    #[test]
+    fn same_named_profiles_keep_their_own_keys() {
+        let path = temp_credentials_path();
+        let store = EncryptedFileStore::unlock(&path, "correct horse").expect("create file");
+        let first = ProviderProfile::default();
+        let second = ProviderProfile::default();
+        assert_eq!(first.name, second.name);
+        store.save(&first.credential_id, "sk-first").expect("save");
+        store
+            .save(&second.credential_id, "sk-second")
+            .expect("save");
+        store.delete(&first.credential_id).expect("delete");
+
+        assert_eq!(load_profile_key(&store, &first).expect("load"), None);
+        assert_eq!(
+            load_profile_key(&store, &second).expect("load").as_deref(),
+            Some("sk-second")
+        );
+        std::fs::remove_file(&path).expect("clean up");
+    }
+
+    #[test]
+    fn keys_filed_by_name_are_copied_to_legacy_profiles_only() {
+        let path = temp_credentials_path();
+        let store = EncryptedFileStore::unlock(&path, "correct horse").expect("create file");
+        store.save(DEFAULT_PROFILE_NAME, "sk-old").expect("save");
+        let legacy = ProviderProfile {
+            credential_id: format!("{LEGACY_CREDENTIAL_PREFIX}0123"),
+            ..ProviderProfile::default()
+        };
+
+        assert_eq!(
+            load_profile_key(&store, &ProviderProfile::default()).expect("load"),
+            None
+        );
+        assert_eq!(
+            load_profile_key(&store, &legacy).expect("load").as_deref(),
+            Some("sk-old")
+        );
+        assert_eq!(
+            store.load(&legacy.credential_id).expect("load").as_deref(),
+            Some("sk-old")
+        );
+        assert_eq!(
+            store.load(DEFAULT_PROFILE_NAME).expect("load").as_deref(),
+            Some("sk-old"),
+            "other workspaces may still need the old entry"
+        );
+        std::fs::remove_file(&path).expect("clean up");
+    }
+}
@@ -1,28 +1,60 @@
-use crate::app::{ChatMessage, DigestItem, LongTermMemoryItem};
+use crate::app::{
+    AssistantRole, ChatMessage, Conversation, DigestItem, LongTermMemoryItem, PromptRevision,
+};
+use crate::context_window::ContextSummary;
+use crate::generation_params::RoleGenerationParams;
+use crate::migrations::{self, DatabaseError};
+use crate::model_discovery::ModelList;
+use crate::network_log::NetworkLogEntry;
+use crate::profiles::ProviderProfile;
+use crate::search::{MIN_INDEXED_TERM_CHARS, SearchFilters, SearchHit, fts_match_expression};
+use crate::usage::TokenUsage;
 use rusqlite::{Connection, OptionalExtension as _, Result as SqliteResult, params};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+/// Hex SHA-256 of `content`, the key digest and memory items are deduplicated by.
+pub fn content_hash(content: &str) -> String {
+    use sha2::{Digest as _, Sha256};
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
 pub struct Database {
     conn: Connection,
+    path: PathBuf,
 }
 
 impl Database {
-    pub fn new() -> SqliteResult<Self> {
-        let db_path = Self::get_db_path();
-
+    /// Open the database at `path`, creating and migrating it as needed.
+    pub fn open(path: &Path) -> Result<Self, DatabaseError> {
         // Ensure the directory exists
-        if let Some(parent) = db_path.parent() {
+        if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent).ok();
         }
 
-        let conn = Connection::open(&db_path)?;
-        let db = Self { conn };
-        db.initialize_tables()?;
+        let conn = Connection::open(path)?;
+        let mut db = Self {
+            conn,
+            path: path.to_path_buf(),
+        };
+        db.initialize()?;
         Ok(db)
     }
 
-    fn get_db_path() -> PathBuf {
+    /// Run `f` in one transaction, rolling everything back if it fails.
+    pub fn transaction<T>(&self, f: impl FnOnce() -> SqliteResult<T>) -> SqliteResult<T> {
+        let tx = self.conn.unchecked_transaction()?;
+        let result = f()?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `chat_data.db` in the platform data directory, used when no workspace is chosen.
+    pub fn default_path() -> PathBuf {
         // Get app data directory
         let mut path = if cfg!(target_os = "windows") {
             std::env::var("APPDATA")
@@ -47,153 +79,216 @@ impl Database {
         path
     }
 
-    fn initialize_tables(&self) -> SqliteResult<()> {
-        // Create content_items table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS content_items (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                content TEXT NOT NULL,
-                role_or_source TEXT NOT NULL,
-                timestamp_unix INTEGER,
-                timestamp_display TEXT,
-                original_id TEXT UNIQUE,
-                is_active BOOLEAN DEFAULT 1,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
-
-        // Create panel_associations table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS panel_associations (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                content_id INTEGER NOT NULL,
-                panel_type TEXT NOT NULL,
-                is_active BOOLEAN DEFAULT 1,
-                added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (content_id) REFERENCES content_items(id),
-                UNIQUE(content_id, panel_type)
-            )",
-            [],
-        )?;
-
-        // Create assistant_roles table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS assistant_roles (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                role_name TEXT UNIQUE NOT NULL,
-                display_name TEXT NOT NULL,
-                description TEXT,
-                is_active BOOLEAN DEFAULT 1,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
-
-        // Create system_prompts table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS system_prompts (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                role_id INTEGER NOT NULL,
-                panel_type TEXT NOT NULL,
-                prompt_text TEXT NOT NULL,
-                is_active BOOLEAN DEFAULT 1,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (role_id) REFERENCES assistant_roles(id),
-                UNIQUE(role_id, panel_type)
-            )",
-            [],
-        )?;
+    fn initialize(&mut self) -> Result<(), DatabaseError> {
+        migrations::run(&mut self.conn)?;
 
-        // Create indexes for better performance
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_content_original_id ON content_items(original_id)",
-            [],
-        )?;
+        // Insert initial roles and prompts if they don't exist
+        self.insert_initial_roles_and_prompts()?;
 
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_panel_associations_content_id ON panel_associations(content_id)",
-            [],
-        )?;
+        // Prompts written before revisions existed become their first revision
+        self.backfill_prompt_revisions()?;
 
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_panel_associations_panel_type ON panel_associations(panel_type)",
-            [],
-        )?;
+        Ok(())
+    }
 
+    fn backfill_prompt_revisions(&self) -> SqliteResult<()> {
         self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_system_prompts_role_id ON system_prompts(role_id)",
+            "INSERT INTO system_prompt_revisions (role_id, panel_type, prompt_text, author, created_at)
+             SELECT role_id, panel_type, prompt_text, 'initial', created_at FROM system_prompts
+             WHERE revision_id IS NULL",
             [],
         )?;
-
         self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_system_prompts_panel_type ON system_prompts(panel_type)",
+            "UPDATE system_prompts SET revision_id = (
+                 SELECT MAX(r.id) FROM system_prompt_revisions r
+                 WHERE r.role_id = system_prompts.role_id AND r.panel_type = system_prompts.panel_type
+             )
+             WHERE revision_id IS NULL",
             [],
         )?;
-
-        // Insert initial roles and prompts if they don't exist
-        self.insert_initial_roles_and_prompts()?;
-
         Ok(())
     }
 
+    /// Save a piece of content and attach it to the given panels, returning its stable id.
+    ///
+    /// Chat messages belong to a conversation; digest and memory items are shared
+    /// across conversations and are saved with `conversation_id` set to `None`.
     pub fn save_content(
         &self,
         content: &str,
         role_or_source: &str,
-        timestamp_unix: i64,
-        timestamp_display: &str,
+        timestamp_ms: i64,
+        conversation_id: Option<i64>,
         panel_types: &[&str],
-    ) -> SqliteResult<()> {
+    ) -> SqliteResult<String> {
+        // Every call is a new item, even when the same text was saved before
         let original_id = Uuid::new_v4().to_string();
+        // Whole seconds and a display string are still written for older readers
+        let timestamp_display = crate::timestamps::to_local(timestamp_ms)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        self.conn.execute(
+            "INSERT INTO content_items (content, role_or_source, timestamp_ms, timestamp_unix, timestamp_display, original_id, conversation_id)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                content,
+                role_or_source,
+                timestamp_ms,
+                timestamp_ms.div_euclid(1000),
+                timestamp_display,
+                original_id,
+                conversation_id
+            ],
+        )?;
+        let content_id = self.conn.last_insert_rowid();
+
+        if let Some(conversation_id) = conversation_id {
+            self.touch_conversation(conversation_id)?;
+        }
+
+        // Add panel associations, reactivating any that were deleted before
+        for panel_type in panel_types {
+            self.conn.execute(
+                "INSERT INTO panel_associations (content_id, panel_type) VALUES (?, ?)
+                 ON CONFLICT(content_id, panel_type) DO UPDATE SET is_active = 1",
+                params![content_id, panel_type],
+            )?;
+        }
+
+        Ok(original_id)
+    }
 
-        // First, check if identical content already exists
-        let existing_content_id: Option<i64> = self
+    /// Add content to the digest or memory panel, reusing the item that already
+    /// holds the same text there (even if it was removed) instead of adding a copy.
+    pub fn save_panel_content(
+        &self,
+        content: &str,
+        source: &str,
+        timestamp_ms: i64,
+        panel_type: &str,
+    ) -> SqliteResult<String> {
+        let hash = content_hash(content);
+        let existing: Option<(i64, String)> = self
             .conn
             .query_row(
-                "SELECT id FROM content_items WHERE content = ? AND role_or_source = ?",
-                params![content, role_or_source],
-                |row| row.get(0),
+                "SELECT ci.id, ci.original_id FROM content_hashes ch
+                 JOIN content_items ci ON ci.id = ch.content_id
+                 WHERE ch.panel_type = ? AND ch.content_hash = ?",
+                params![panel_type, hash],
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )
             .optional()?;
 
-        let content_id = if let Some(id) = existing_content_id {
-            // Content already exists, use existing ID
-            id
-        } else {
-            // Insert new content
-            self.conn.execute(
-                "INSERT INTO content_items (content, role_or_source, timestamp_unix, timestamp_display, original_id)
-                 VALUES (?, ?, ?, ?, ?)",
-                params![content, role_or_source, timestamp_unix, timestamp_display, original_id],
-            )?;
-            self.conn.last_insert_rowid()
-        };
-
-        // Add panel associations (using INSERT OR IGNORE to handle duplicates)
-        for panel_type in panel_types {
+        if let Some((content_id, original_id)) = existing {
             self.conn.execute(
-                "INSERT OR IGNORE INTO panel_associations (content_id, panel_type) VALUES (?, ?)",
+                "INSERT INTO panel_associations (content_id, panel_type) VALUES (?, ?)
+                 ON CONFLICT(content_id, panel_type) DO UPDATE SET is_active = 1",
                 params![content_id, panel_type],
             )?;
+            return Ok(original_id);
         }
 
+        let original_id = self.save_content(content, source, timestamp_ms, None, &[panel_type])?;
+        self.conn.execute(
+            "INSERT INTO content_hashes (panel_type, content_hash, content_id)
+             SELECT ?, ?, id FROM content_items WHERE original_id = ?",
+            params![panel_type, hash, original_id],
+        )?;
+        Ok(original_id)
+    }
+
+    /// Whether the digest or memory panel currently shows an item with exactly this text.
+    pub fn panel_has_content(&self, content: &str, panel_type: &str) -> SqliteResult<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS (
+                 SELECT 1 FROM content_hashes ch
+                 JOIN panel_associations pa
+                   ON pa.content_id = ch.content_id AND pa.panel_type = ch.panel_type
+                 WHERE ch.panel_type = ? AND ch.content_hash = ? AND pa.is_active = 1
+             )",
+            params![panel_type, content_hash(content)],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_content_truncated(&self, original_id: &str, is_truncated: bool) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE content_items SET is_truncated = ? WHERE original_id = ?",
+            params![is_truncated, original_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_content_usage(&self, original_id: &str, usage: &TokenUsage) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE content_items SET model_name = ?, prompt_tokens = ?, completion_tokens = ?
+             WHERE original_id = ?",
+            params![
+                usage.model,
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                original_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_content_prompt_revision(
+        &self,
+        original_id: &str,
+        revision_id: i64,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE content_items SET prompt_revision_id = ? WHERE original_id = ?",
+            params![revision_id, original_id],
+        )?;
+        Ok(())
+    }
+
+    /// Soft-delete one item from a panel; the content itself stays in `content_items`.
+    pub fn remove_panel_association(
+        &self,
+        original_id: &str,
+        panel_type: &str,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE panel_associations SET is_active = 0
+             WHERE panel_type = ?
+             AND content_id IN (SELECT id FROM content_items WHERE original_id = ?)",
+            params![panel_type, original_id],
+        )?;
         Ok(())
     }
 
-    pub fn load_chat_messages(&self) -> SqliteResult<Vec<ChatMessage>> {
+    pub fn load_chat_messages(&self, conversation_id: i64) -> SqliteResult<Vec<ChatMessage>> {
         let mut stmt = self.conn.prepare(
-            "SELECT ci.content, ci.role_or_source
+            "SELECT ci.original_id, ci.content, ci.role_or_source, ci.is_truncated,
+                    ci.model_name, ci.prompt_tokens, ci.completion_tokens, ci.prompt_revision_id,
+                    ci.timestamp_ms
              FROM content_items ci
              JOIN panel_associations pa ON ci.id = pa.content_id
-             WHERE pa.panel_type = 'chat'
-             ORDER BY ci.timestamp_unix ASC",
+             WHERE pa.panel_type = 'chat' AND pa.is_active = 1 AND ci.conversation_id = ?
+             ORDER BY ci.timestamp_ms ASC, ci.id ASC",
         )?;
 
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map([conversation_id], |row| {
             Ok(ChatMessage {
-                role: row.get(1)?,
-                content: row.get(0)?,
+                id: row.get(0)?,
+                role: row.get(2)?,
+                content: row.get(1)?,
+                truncated: row.get::<_, Option<bool>>(3)?.unwrap_or(false),
+                usage: match (row.get(4)?, row.get(5)?, row.get(6)?) {
+                    (Some(model), Some(prompt_tokens), Some(completion_tokens)) => {
+                        Some(TokenUsage {
+                            model,
+                            prompt_tokens,
+                            completion_tokens,
+                        })
+                    }
+                    _ => None,
+                },
+                prompt_revision_id: row.get(7)?,
+                timestamp_ms: row.get::<_, Option<i64>>(8)?.unwrap_or_default(),
             })
         })?;
 
@@ -207,11 +302,11 @@ impl Database {
 
     pub fn load_digest_items(&self) -> SqliteResult<Vec<DigestItem>> {
         let mut stmt = self.conn.prepare(
-            "SELECT ci.original_id, ci.content, ci.role_or_source, ci.timestamp_display
+            "SELECT ci.original_id, ci.content, ci.role_or_source, ci.timestamp_ms
              FROM content_items ci
              JOIN panel_associations pa ON ci.id = pa.content_id
-             WHERE pa.panel_type = 'digest'
-             ORDER BY ci.timestamp_unix ASC",
+             WHERE pa.panel_type = 'digest' AND pa.is_active = 1
+             ORDER BY ci.timestamp_ms ASC, ci.id ASC",
         )?;
 
         let rows = stmt.query_map([], |row| {
@@ -219,7 +314,7 @@ impl Database {
                 id: row.get(0)?,
                 content: row.get(1)?,
                 source: row.get(2)?,
-                timestamp: row.get(3)?,
+                timestamp_ms: row.get::<_, Option<i64>>(3)?.unwrap_or_default(),
                 selected: false, // Default to unselected when loading
             })
         })?;
@@ -232,13 +327,114 @@ impl Database {
         Ok(items)
     }
 
+    /// Items of any panel containing every term, best matches first.
+    ///
+    /// Terms long enough for the full-text index are matched and ranked through
+    /// it; shorter ones are filtered by substring.
+    pub fn search_content(
+        &self,
+        terms: &[String],
+        filters: &SearchFilters,
+        (from_ms, to_ms): (Option<i64>, Option<i64>),
+        limit: usize,
+    ) -> SqliteResult<Vec<SearchHit>> {
+        let mut sql = String::from(
+            "SELECT ci.original_id, pa.panel_type, ci.role_or_source, ci.conversation_id,
+                    c.title, ci.timestamp_ms, ci.content
+             FROM content_items ci
+             JOIN panel_associations pa ON ci.id = pa.content_id
+             LEFT JOIN conversations c ON c.id = ci.conversation_id",
+        );
+        let mut conditions = vec!["pa.is_active = 1".to_owned()];
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+
+        let match_expression = fts_match_expression(terms);
+        if let Some(match_expression) = &match_expression {
+            sql.push_str(" JOIN content_fts ON content_fts.rowid = ci.id");
+            conditions.push("content_fts MATCH ?".to_owned());
+            values.push(match_expression.clone().into());
+        }
+        for term in terms {
+            if term.chars().count() < MIN_INDEXED_TERM_CHARS {
+                conditions.push("ci.content LIKE ? ESCAPE '\\'".to_owned());
+                let escaped = term
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                values.push(format!("%{escaped}%").into());
+            }
+        }
+        if let Some(panel_type) = &filters.panel_type {
+            conditions.push("pa.panel_type = ?".to_owned());
+            values.push(panel_type.clone().into());
+        }
+        if let Some(role_or_source) = &filters.role_or_source {
+            conditions.push("ci.role_or_source = ?".to_owned());
+            values.push(role_or_source.clone().into());
+        }
+        if let Some(from_ms) = from_ms {
+            conditions.push("ci.timestamp_ms >= ?".to_owned());
+            values.push(from_ms.into());
+        }
+        if let Some(to_ms) = to_ms {
+            conditions.push("ci.timestamp_ms < ?".to_owned());
+            values.push(to_ms.into());
+        }
+
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+        if match_expression.is_some() {
+            sql.push_str(" ORDER BY bm25(content_fts), ci.timestamp_ms DESC");
+        } else {
+            sql.push_str(" ORDER BY ci.timestamp_ms DESC, ci.id DESC");
+        }
+        sql.push_str(" LIMIT ?");
+        values.push((limit as i64).into());
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(values), |row| {
+            Ok(SearchHit {
+                original_id: row.get(0)?,
+                panel_type: row.get(1)?,
+                role_or_source: row.get(2)?,
+                conversation_id: row.get(3)?,
+                conversation_title: row.get(4)?,
+                timestamp_ms: row.get::<_, Option<i64>>(5)?.unwrap_or_default(),
+                content: row.get(6)?,
+            })
+        })?;
+
+        let mut hits = Vec::new();
+        for row in rows {
+            hits.push(row?);
+        }
+
+        Ok(hits)
+    }
+
+    /// Distinct `role_or_source` values of stored content.
+    pub fn get_content_sources(&self) -> SqliteResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT role_or_source FROM content_items ORDER BY role_or_source")?;
+
+        let rows = stmt.query_map([], |row| row.get(0))?;
+
+        let mut sources = Vec::new();
+        for row in rows {
+            sources.push(row?);
+        }
+
+        Ok(sources)
+    }
+
     pub fn load_longterm_memory_items(&self) -> SqliteResult<Vec<LongTermMemoryItem>> {
         let mut stmt = self.conn.prepare(
-            "SELECT ci.original_id, ci.content, ci.role_or_source, ci.timestamp_display
+            "SELECT ci.original_id, ci.content, ci.role_or_source, ci.timestamp_ms
              FROM content_items ci
              JOIN panel_associations pa ON ci.id = pa.content_id
-             WHERE pa.panel_type = 'longterm'
-             ORDER BY ci.timestamp_unix ASC",
+             WHERE pa.panel_type = 'longterm' AND pa.is_active = 1
+             ORDER BY ci.timestamp_ms ASC, ci.id ASC",
         )?;
 
         let rows = stmt.query_map([], |row| {
@@ -246,7 +442,7 @@ impl Database {
                 id: row.get(0)?,
                 content: row.get(1)?,
                 source: row.get(2)?,
-                timestamp: row.get(3)?,
+                timestamp_ms: row.get::<_, Option<i64>>(3)?.unwrap_or_default(),
                 selected: false, // Default to unselected when loading
             })
         })?;
@@ -265,19 +461,19 @@ impl Database {
                 .query_row("SELECT COUNT(*) FROM content_items", [], |row| row.get(0))?;
 
         let chat_count: usize = self.conn.query_row(
-            "SELECT COUNT(*) FROM panel_associations WHERE panel_type = 'chat'",
+            "SELECT COUNT(*) FROM panel_associations WHERE panel_type = 'chat' AND is_active = 1",
             [],
             |row| row.get(0),
         )?;
 
         let digest_count: usize = self.conn.query_row(
-            "SELECT COUNT(*) FROM panel_associations WHERE panel_type = 'digest'",
+            "SELECT COUNT(*) FROM panel_associations WHERE panel_type = 'digest' AND is_active = 1",
             [],
             |row| row.get(0),
         )?;
 
         let longterm_count: usize = self.conn.query_row(
-            "SELECT COUNT(*) FROM panel_associations WHERE panel_type = 'longterm'",
+            "SELECT COUNT(*) FROM panel_associations WHERE panel_type = 'longterm' AND is_active = 1",
             [],
             |row| row.get(0),
         )?;
@@ -285,35 +481,202 @@ impl Database {
         Ok((total_content, chat_count, digest_count, longterm_count))
     }
 
-    pub fn get_database_path() -> PathBuf {
-        Self::get_db_path()
+    pub fn clear_chat_panel_associations(&self, conversation_id: i64) -> SqliteResult<()> {
+        // Soft-delete the conversation's chat panel associations, keeping content_items intact
+        // This effectively "clears" the chat panel while preserving data
+        self.conn.execute(
+            "UPDATE panel_associations SET is_active = 0
+             WHERE panel_type = 'chat'
+             AND content_id IN (SELECT id FROM content_items WHERE conversation_id = ?)",
+            [conversation_id],
+        )?;
+        Ok(())
     }
 
-    pub fn clear_chat_panel_associations(&self) -> SqliteResult<()> {
-        // Remove all chat panel associations, keeping content_items intact
-        // This effectively "clears" the chat panel while preserving data
+    pub fn create_conversation(&self, title: &str) -> SqliteResult<i64> {
+        self.conn
+            .execute("INSERT INTO conversations (title) VALUES (?)", [title])?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn get_conversation(&self, conversation_id: i64) -> SqliteResult<Option<Conversation>> {
+        self.conn
+            .query_row(
+                "SELECT id, title, is_archived, updated_at FROM conversations WHERE id = ?",
+                [conversation_id],
+                |row| {
+                    Ok(Conversation {
+                        id: row.get(0)?,
+                        title: row.get(1)?,
+                        is_archived: row.get(2)?,
+                        updated_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn get_conversations(&self, include_archived: bool) -> SqliteResult<Vec<Conversation>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, title, is_archived, updated_at FROM conversations
+             WHERE is_archived = 0 OR ?
+             ORDER BY updated_at DESC, id DESC",
+        )?;
+
+        let rows = stmt.query_map([include_archived], |row| {
+            Ok(Conversation {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                is_archived: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        })?;
+
+        let mut conversations = Vec::new();
+        for row in rows {
+            conversations.push(row?);
+        }
+
+        Ok(conversations)
+    }
+
+    /// Date a conversation by its last message, e.g. after importing it.
+    pub fn set_conversation_updated_at(
+        &self,
+        conversation_id: i64,
+        timestamp_ms: i64,
+    ) -> SqliteResult<()> {
         self.conn.execute(
-            "DELETE FROM panel_associations WHERE panel_type = 'chat'",
-            [],
+            "UPDATE conversations SET updated_at = datetime(? / 1000, 'unixepoch') WHERE id = ?",
+            params![timestamp_ms, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    /// Conversation created by an earlier import of `source_key`, if it still exists.
+    pub fn find_imported_conversation(&self, source_key: &str) -> SqliteResult<Option<i64>> {
+        self.conn
+            .query_row(
+                "SELECT ci.conversation_id FROM conversation_imports ci
+                 JOIN conversations c ON c.id = ci.conversation_id
+                 WHERE ci.source_key = ?",
+                [source_key],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    pub fn record_conversation_import(
+        &self,
+        source_key: &str,
+        conversation_id: i64,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO conversation_imports (source_key, conversation_id) VALUES (?, ?)
+             ON CONFLICT(source_key) DO UPDATE SET
+                conversation_id = excluded.conversation_id,
+                imported_at = CURRENT_TIMESTAMP",
+            params![source_key, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn rename_conversation(&self, conversation_id: i64, title: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE conversations SET title = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![title, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_conversation_archived(
+        &self,
+        conversation_id: i64,
+        is_archived: bool,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE conversations SET is_archived = ? WHERE id = ?",
+            params![is_archived, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_pinned_prompt_revision(&self, conversation_id: i64) -> SqliteResult<Option<i64>> {
+        self.conn.query_row(
+            "SELECT pinned_prompt_revision_id FROM conversations WHERE id = ?",
+            [conversation_id],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_pinned_prompt_revision(
+        &self,
+        conversation_id: i64,
+        revision_id: Option<i64>,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE conversations SET pinned_prompt_revision_id = ? WHERE id = ?",
+            params![revision_id, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_context_summary(
+        &self,
+        conversation_id: i64,
+    ) -> SqliteResult<Option<ContextSummary>> {
+        let (text, through_message_id): (Option<String>, Option<String>) = self.conn.query_row(
+            "SELECT context_summary, context_summary_through FROM conversations WHERE id = ?",
+            [conversation_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(text
+            .zip(through_message_id)
+            .map(|(text, through_message_id)| ContextSummary {
+                through_message_id,
+                text,
+            }))
+    }
+
+    pub fn set_context_summary(
+        &self,
+        conversation_id: i64,
+        summary: Option<&ContextSummary>,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE conversations SET context_summary = ?, context_summary_through = ? WHERE id = ?",
+            params![
+                summary.map(|summary| &summary.text),
+                summary.map(|summary| &summary.through_message_id),
+                conversation_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn touch_conversation(&self, conversation_id: i64) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE conversations SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            [conversation_id],
         )?;
         Ok(())
     }
 
     pub fn clear_digest_panel_associations(&self) -> SqliteResult<()> {
-        // Remove all digest panel associations, keeping content_items intact
+        // Soft-delete all digest panel associations, keeping content_items intact
         // This effectively "clears" the digest panel while preserving data
         self.conn.execute(
-            "DELETE FROM panel_associations WHERE panel_type = 'digest'",
+            "UPDATE panel_associations SET is_active = 0 WHERE panel_type = 'digest'",
             [],
         )?;
         Ok(())
     }
 
     pub fn clear_longterm_panel_associations(&self) -> SqliteResult<()> {
-        // Remove all longterm memory panel associations, keeping content_items intact
+        // Soft-delete all longterm memory panel associations, keeping content_items intact
         // This effectively "clears" the longterm memory panel while preserving data
         self.conn.execute(
-            "DELETE FROM panel_associations WHERE panel_type = 'longterm'",
+            "UPDATE panel_associations SET is_active = 0 WHERE panel_type = 'longterm'",
             [],
         )?;
         Ok(())
@@ -434,6 +797,182 @@ impl Database {
         Ok(roles)
     }
 
+    /// All roles including deactivated ones, for the role editor.
+    pub fn get_all_assistant_roles(&self) -> SqliteResult<Vec<AssistantRole>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, role_name, display_name, description, is_active FROM assistant_roles
+             ORDER BY display_name",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(AssistantRole {
+                id: row.get(0)?,
+                role_name: row.get(1)?,
+                display_name: row.get(2)?,
+                description: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                is_active: row.get(4)?,
+            })
+        })?;
+
+        let mut roles = Vec::new();
+        for row in rows {
+            roles.push(row?);
+        }
+
+        Ok(roles)
+    }
+
+    pub fn create_assistant_role(
+        &self,
+        role_name: &str,
+        display_name: &str,
+        description: &str,
+    ) -> SqliteResult<i64> {
+        self.conn.execute(
+            "INSERT INTO assistant_roles (role_name, display_name, description) VALUES (?, ?, ?)",
+            params![role_name, display_name, description],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn update_assistant_role(
+        &self,
+        role_id: i64,
+        role_name: &str,
+        display_name: &str,
+        description: &str,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE assistant_roles SET role_name = ?, display_name = ?, description = ? WHERE id = ?",
+            params![role_name, display_name, description, role_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_assistant_role_active(&self, role_id: i64, is_active: bool) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE assistant_roles SET is_active = ? WHERE id = ?",
+            params![is_active, role_id],
+        )?;
+        Ok(())
+    }
+
+    /// Create or replace the prompt of one panel; an empty prompt is stored inactive.
+    ///
+    /// A new revision is recorded whenever the text actually changes. Panels that never
+    /// had a prompt get no row, and no revision, until they are given one.
+    pub fn save_system_prompt(
+        &self,
+        role_id: i64,
+        panel_type: &str,
+        prompt_text: &str,
+        author: &str,
+    ) -> SqliteResult<()> {
+        let current: Option<(String, Option<i64>)> = self
+            .conn
+            .query_row(
+                "SELECT prompt_text, revision_id FROM system_prompts
+                 WHERE role_id = ? AND panel_type = ?",
+                params![role_id, panel_type],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let revision_id = match current {
+            None if prompt_text.trim().is_empty() => return Ok(()),
+            Some((current_text, Some(revision_id))) if current_text == prompt_text => revision_id,
+            _ => {
+                self.conn.execute(
+                    "INSERT INTO system_prompt_revisions (role_id, panel_type, prompt_text, author)
+                     VALUES (?, ?, ?, ?)",
+                    params![role_id, panel_type, prompt_text, author],
+                )?;
+                self.conn.last_insert_rowid()
+            }
+        };
+
+        self.conn.execute(
+            "INSERT INTO system_prompts (role_id, panel_type, prompt_text, revision_id, is_active)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(role_id, panel_type)
+             DO UPDATE SET prompt_text = ?3, revision_id = ?4, is_active = ?5",
+            params![
+                role_id,
+                panel_type,
+                prompt_text,
+                revision_id,
+                !prompt_text.trim().is_empty()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Revision ids of the active prompts of a role, by panel type.
+    pub fn get_prompt_revision_ids_for_role(
+        &self,
+        role_id: i64,
+    ) -> SqliteResult<std::collections::HashMap<String, i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT panel_type, revision_id FROM system_prompts
+             WHERE role_id = ? AND is_active = 1 AND revision_id IS NOT NULL",
+        )?;
+
+        let rows = stmt.query_map([role_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut revision_ids = std::collections::HashMap::new();
+        for row in rows {
+            let (panel_type, revision_id) = row?;
+            revision_ids.insert(panel_type, revision_id);
+        }
+
+        Ok(revision_ids)
+    }
+
+    /// Every revision of one prompt, newest first.
+    pub fn get_prompt_revisions(
+        &self,
+        role_id: i64,
+        panel_type: &str,
+    ) -> SqliteResult<Vec<PromptRevision>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, role_id, panel_type, prompt_text, author, created_at
+             FROM system_prompt_revisions
+             WHERE role_id = ? AND panel_type = ?
+             ORDER BY id DESC",
+        )?;
+
+        let rows = stmt.query_map(params![role_id, panel_type], Self::prompt_revision_from_row)?;
+
+        let mut revisions = Vec::new();
+        for row in rows {
+            revisions.push(row?);
+        }
+
+        Ok(revisions)
+    }
+
+    pub fn get_prompt_revision(&self, revision_id: i64) -> SqliteResult<Option<PromptRevision>> {
+        self.conn
+            .query_row(
+                "SELECT id, role_id, panel_type, prompt_text, author, created_at
+                 FROM system_prompt_revisions WHERE id = ?",
+                [revision_id],
+                Self::prompt_revision_from_row,
+            )
+            .optional()
+    }
+
+    fn prompt_revision_from_row(row: &rusqlite::Row<'_>) -> SqliteResult<PromptRevision> {
+        Ok(PromptRevision {
+            id: row.get(0)?,
+            role_id: row.get(1)?,
+            panel_type: row.get(2)?,
+            prompt_text: row.get(3)?,
+            author: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            created_at: row.get(5)?,
+        })
+    }
+
     pub fn get_system_prompts_for_role(
         &self,
         role_id: i64,
@@ -454,4 +993,433 @@ impl Database {
 
         Ok(prompts)
     }
+
+    pub fn get_role_generation_params(&self, role_id: i64) -> SqliteResult<RoleGenerationParams> {
+        let params: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT generation_params FROM assistant_roles WHERE id = ?",
+                [role_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        let Some(params) = params else {
+            return Ok(RoleGenerationParams::default());
+        };
+        serde_json::from_str(&params).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+        })
+    }
+
+    pub fn set_role_generation_params(
+        &self,
+        role_id: i64,
+        params: &RoleGenerationParams,
+    ) -> SqliteResult<()> {
+        let params = if params.is_unset() {
+            None
+        } else {
+            Some(
+                serde_json::to_string(params)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+            )
+        };
+        self.conn.execute(
+            "UPDATE assistant_roles SET generation_params = ? WHERE id = ?",
+            params![params, role_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_provider_profiles(&self) -> SqliteResult<Vec<ProviderProfile>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, config, credential_id FROM provider_profiles ORDER BY name COLLATE NOCASE")?;
+
+        let rows = stmt.query_map([], |row| {
+            let config: String = row.get(2)?;
+            Ok(ProviderProfile {
+                id: Some(row.get(0)?),
+                name: row.get(1)?,
+                credential_id: row.get(3)?,
+                config: serde_json::from_str(&config).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        2,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })?,
+            })
+        })?;
+
+        let mut profiles = Vec::new();
+        for row in rows {
+            profiles.push(row?);
+        }
+
+        Ok(profiles)
+    }
+
+    /// Insert `profile`, or update it when it has an id; returns its id.
+    pub fn save_provider_profile(&self, profile: &ProviderProfile) -> SqliteResult<i64> {
+        let config = serde_json::to_string(&profile.config)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        if let Some(id) = profile.id {
+            self.conn.execute(
+                "UPDATE provider_profiles SET name = ?, config = ?, credential_id = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                params![profile.name, config, profile.credential_id, id],
+            )?;
+            return Ok(id);
+        }
+        self.conn.execute(
+            "INSERT INTO provider_profiles (name, config, credential_id) VALUES (?, ?, ?)",
+            params![profile.name, config, profile.credential_id],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn delete_provider_profile(&self, profile_id: i64) -> SqliteResult<()> {
+        self.conn
+            .execute("DELETE FROM model_lists WHERE profile_id = ?", [profile_id])?;
+        self.conn
+            .execute("DELETE FROM provider_profiles WHERE id = ?", [profile_id])?;
+        Ok(())
+    }
+
+    /// Store `entry` and drop all but the newest stored entries; returns its id.
+    pub fn save_network_log_entry(&self, entry: &NetworkLogEntry) -> SqliteResult<i64> {
+        let request_headers = serde_json::to_string(&entry.request_headers)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.conn.execute(
+            "INSERT INTO network_log (
+                started_at_ms, method, url, request_headers, status, latency_ms, duration_ms,
+                attempts, prompt_tokens, completion_tokens, error, request_body, response_body
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                entry.started_at_ms,
+                entry.method,
+                entry.url,
+                request_headers,
+                entry.status,
+                entry.latency_ms,
+                entry.duration_ms,
+                entry.attempts,
+                entry.prompt_tokens,
+                entry.completion_tokens,
+                entry.error,
+                entry.request_body,
+                entry.response_body,
+            ],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        self.conn.execute(
+            "DELETE FROM network_log WHERE id <= ?",
+            [id - crate::network_log::STORED_ENTRIES],
+        )?;
+        Ok(id)
+    }
+
+    /// The newest `limit` network log entries, oldest first.
+    pub fn get_network_log(&self, limit: usize) -> SqliteResult<Vec<NetworkLogEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, started_at_ms, method, url, request_headers, status, latency_ms, duration_ms,
+                    attempts, prompt_tokens, completion_tokens, error, request_body, response_body
+             FROM (SELECT * FROM network_log ORDER BY id DESC LIMIT ?)
+             ORDER BY id",
+        )?;
+
+        let rows = stmt.query_map([limit as i64], |row| {
+            let request_headers: String = row.get(4)?;
+            Ok(NetworkLogEntry {
+                id: Some(row.get(0)?),
+                started_at_ms: row.get(1)?,
+                method: row.get(2)?,
+                url: row.get(3)?,
+                request_headers: serde_json::from_str(&request_headers).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        4,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })?,
+                status: row.get(5)?,
+                latency_ms: row.get(6)?,
+                duration_ms: row.get(7)?,
+                attempts: row.get(8)?,
+                prompt_tokens: row.get(9)?,
+                completion_tokens: row.get(10)?,
+                error: row.get(11)?,
+                request_body: row.get(12)?,
+                response_body: row.get(13)?,
+            })
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(row?);
+        }
+
+        Ok(entries)
+    }
+
+    pub fn clear_network_log(&self) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM network_log", [])?;
+        Ok(())
+    }
+
+    pub fn get_model_list(&self, profile_id: i64) -> SqliteResult<Option<ModelList>> {
+        self.conn
+            .query_row(
+                "SELECT base_url, models, fetched_at_ms FROM model_lists WHERE profile_id = ?",
+                [profile_id],
+                |row| {
+                    let models: String = row.get(1)?;
+                    Ok(ModelList {
+                        base_url: row.get(0)?,
+                        models: serde_json::from_str(&models).map_err(|e| {
+                            rusqlite::Error::FromSqlConversionFailure(
+                                1,
+                                rusqlite::types::Type::Text,
+                                Box::new(e),
+                            )
+                        })?,
+                        fetched_at_ms: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn save_model_list(&self, profile_id: i64, list: &ModelList) -> SqliteResult<()> {
+        let models = serde_json::to_string(&list.models)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO model_lists (profile_id, base_url, models, fetched_at_ms) VALUES (?, ?, ?, ?)",
+            params![profile_id, list.base_url, models, list.fetched_at_ms],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_in_memory() -> Database {
+        Database::open(Path::new(":memory:")).expect("open in-memory database")
+    }
+
+    #[test]
+    fn identical_chat_messages_stay_separate() {
+        let db = open_in_memory();
+        let conversation_id = db.create_conversation("Test").expect("conversation");
+        for timestamp_ms in [1_000, 2_000, 2_000] {
+            db.save_content(
+                "yes",
+                "user",
+                timestamp_ms,
+                Some(conversation_id),
+                &["chat"],
+            )
+            .expect("save");
+        }
+
+        let messages = db.load_chat_messages(conversation_id).expect("load");
+        let timestamps: Vec<i64> = messages.iter().map(|m| m.timestamp_ms).collect();
+        assert_eq!(
+            timestamps,
+            vec![1_000, 2_000, 2_000],
+            "every message keeps its own row"
+        );
+        assert_ne!(messages[1].id, messages[2].id, "ids are distinct");
+    }
+
+    #[test]
+    fn digest_reuses_the_item_with_the_same_content() {
+        let db = open_in_memory();
+        let first = db
+            .save_panel_content("Key point", "user", 1_000, "digest")
+            .expect("save");
+        db.remove_panel_association(&first, "digest")
+            .expect("remove");
+        let again = db
+            .save_panel_content("Key point", "assistant", 2_000, "digest")
+            .expect("save");
+        assert_eq!(first, again, "re-adding restores the existing item");
+
+        let memory = db
+            .save_panel_content("Key point", "user", 3_000, "longterm")
+            .expect("save");
+        assert_ne!(first, memory, "each panel deduplicates on its own");
+
+        let digest_items = db.load_digest_items().expect("load");
+        assert_eq!(
+            digest_items.len(),
+            1,
+            "one digest item for the repeated text"
+        );
+    }
+
+    fn panel_association_rows(db: &Database) -> Vec<(String, bool)> {
+        let mut stmt = db
+            .conn
+            .prepare("SELECT panel_type, is_active FROM panel_associations ORDER BY id")
+            .expect("prepare");
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("query")
+            .collect::<SqliteResult<_>>()
+            .expect("rows")
+    }
+
+    #[test]
+    fn deletes_and_clears_only_deactivate_panel_items() {
+        let db = open_in_memory();
+        let conversation_id = db.create_conversation("Test").expect("conversation");
+        let question = db
+            .save_content(
+                "Is the NDA mutual?",
+                "user",
+                1_000,
+                Some(conversation_id),
+                &["chat"],
+            )
+            .expect("save");
+        db.save_content("Yes.", "assistant", 2_000, Some(conversation_id), &["chat"])
+            .expect("save");
+        let digest = db
+            .save_panel_content("NDA is mutual", "user", 3_000, "digest")
+            .expect("save");
+        db.save_panel_content("Pilot runs 3 months", "user", 4_000, "digest")
+            .expect("save");
+        let memory = db
+            .save_panel_content("Client is Acme", "user", 5_000, "longterm")
+            .expect("save");
+        db.save_panel_content("Prefers email", "user", 6_000, "longterm")
+            .expect("save");
+
+        db.remove_panel_association(&question, "chat")
+            .expect("delete chat message");
+        db.remove_panel_association(&digest, "digest")
+            .expect("delete digest item");
+        db.remove_panel_association(&memory, "longterm")
+            .expect("delete memory item");
+        assert_eq!(
+            db.load_chat_messages(conversation_id).expect("load").len(),
+            1
+        );
+        assert_eq!(db.load_digest_items().expect("load").len(), 1);
+        assert_eq!(db.load_longterm_memory_items().expect("load").len(), 1);
+
+        db.clear_chat_panel_associations(conversation_id)
+            .expect("clear chat");
+        db.clear_digest_panel_associations().expect("clear digest");
+        db.clear_longterm_panel_associations()
+            .expect("clear memory");
+        assert!(
+            db.load_chat_messages(conversation_id)
+                .expect("load")
+                .is_empty()
+        );
+        assert!(db.load_digest_items().expect("load").is_empty());
+        assert!(db.load_longterm_memory_items().expect("load").is_empty());
+
+        let rows = panel_association_rows(&db);
+        assert_eq!(rows.len(), 6, "no association row is deleted");
+        assert!(rows.iter().all(|(_, is_active)| !is_active), "{rows:?}");
+        let (total_content, ..) = db.get_database_stats().expect("stats");
+        assert_eq!(total_content, 6, "content stays");
+
+        // A cleared item comes back when its text is added again
+        db.save_panel_content("NDA is mutual", "user", 7_000, "digest")
+            .expect("save");
+        assert_eq!(db.load_digest_items().expect("load")[0].id, digest);
+    }
+
+    #[test]
+    fn prompt_revisions_are_saved_pinned_and_rolled_back() {
+        let db = open_in_memory();
+        let role_id = db
+            .create_assistant_role("tester", "Tester", "")
+            .expect("role");
+        let texts = |db: &Database| -> Vec<String> {
+            db.get_prompt_revisions(role_id, "chat")
+                .expect("revisions")
+                .into_iter()
+                .map(|revision| revision.prompt_text)
+                .collect()
+        };
+
+        db.save_system_prompt(role_id, "chat", "", "me")
+            .expect("save");
+        db.save_system_prompt(role_id, "digest", "  ", "me")
+            .expect("save");
+        assert!(texts(&db).is_empty(), "an empty new prompt has no revision");
+        assert!(
+            db.get_prompt_revisions(role_id, "digest")
+                .expect("revisions")
+                .is_empty()
+        );
+
+        db.save_system_prompt(role_id, "chat", "Be brief.", "me")
+            .expect("save");
+        db.save_system_prompt(role_id, "chat", "Be brief.", "me")
+            .expect("save");
+        db.save_system_prompt(role_id, "chat", "Be thorough.", "me")
+            .expect("save");
+        assert_eq!(
+            texts(&db),
+            ["Be thorough.", "Be brief."],
+            "unchanged saves add nothing"
+        );
+        let revisions = db.get_prompt_revisions(role_id, "chat").expect("revisions");
+        let (second, first) = (revisions[0].id, revisions[1].id);
+        assert_eq!(
+            db.get_prompt_revision_ids_for_role(role_id).expect("ids")["chat"],
+            second
+        );
+
+        // Pinning keeps a conversation on an older revision
+        let conversation_id = db.create_conversation("Pinned").expect("conversation");
+        db.set_pinned_prompt_revision(conversation_id, Some(first))
+            .expect("pin");
+        assert_eq!(
+            db.get_pinned_prompt_revision(conversation_id)
+                .expect("pinned"),
+            Some(first)
+        );
+        let pinned = db
+            .get_prompt_revision(first)
+            .expect("revision")
+            .expect("exists");
+        assert_eq!(pinned.prompt_text, "Be brief.");
+        db.set_pinned_prompt_revision(conversation_id, None)
+            .expect("unpin");
+        assert_eq!(
+            db.get_pinned_prompt_revision(conversation_id)
+                .expect("pinned"),
+            None
+        );
+
+        // Rolling back saves the old text as a new revision
+        db.save_system_prompt(role_id, "chat", &pinned.prompt_text, "me")
+            .expect("save");
+        assert_eq!(texts(&db), ["Be brief.", "Be thorough.", "Be brief."]);
+        let current = db.get_prompt_revision_ids_for_role(role_id).expect("ids")["chat"];
+        assert!(current > second, "the rollback is the newest revision");
+        assert_eq!(
+            db.get_system_prompts_for_role(role_id).expect("prompts")["chat"],
+            "Be brief."
+        );
+
+        // Clearing an existing prompt is a revision too, and turns it off
+        db.save_system_prompt(role_id, "chat", "", "me")
+            .expect("save");
+        assert_eq!(texts(&db).len(), 4);
+        assert!(
+            db.get_system_prompts_for_role(role_id)
+                .expect("prompts")
+                .is_empty()
+        );
+    }
 }
@@ -71,6 +71,9 @@ impl TemplateApp {
                             ui.colored_label(egui::Color32::GRAY, "No items match your search.");
                         } else {
                             for i in filtered_indices {
+                                let item_id = self.digest_items[i].id.clone();
+                                self.scroll_to_search_hit(ui, &item_id);
+
                                 // Check selection state first
                                 let is_selected = self.digest_items[i].selected;
 
@@ -89,7 +92,16 @@ impl TemplateApp {
                                                     if self.digest_items[i].source == "user" { egui::Color32::DARK_RED } else { egui::Color32::DARK_GREEN },
                                                     format!("{source_label}:")
                                                 );
-                                                ui.label(&self.digest_items[i].timestamp);
+                                                ui.colored_label(
+                                                    egui::Color32::GRAY,
+                                                    crate::timestamps::format_relative(
+                                                        self.digest_items[i].timestamp_ms,
+                                                        chrono::Local::now(),
+                                                    ),
+                                                )
+                                                .on_hover_text(crate::timestamps::format_full(
+                                                    self.digest_items[i].timestamp_ms,
+                                                ));
                                             });
 
                                             // Content
@@ -130,7 +142,16 @@ impl TemplateApp {
                                             if self.digest_items[i].source == "user" { egui::Color32::DARK_RED } else { egui::Color32::DARK_GREEN },
                                             format!("{source_label}:")
                                         );
-                                        ui.label(&self.digest_items[i].timestamp);
+                                        ui.colored_label(
+                                            egui::Color32::GRAY,
+                                            crate::timestamps::format_relative(
+                                                self.digest_items[i].timestamp_ms,
+                                                chrono::Local::now(),
+                                            ),
+                                        )
+                                        .on_hover_text(crate::timestamps::format_full(
+                                            self.digest_items[i].timestamp_ms,
+                                        ));
                                     });
 
                                     // Content
@@ -170,7 +191,7 @@ impl TemplateApp {
                 });
 
             if let Some(index) = item_to_delete {
-                self.digest_items.remove(index);
+                self.delete_digest_item(index);
             }
 
             ui.add_space(-25.0);
@@ -0,0 +1,811 @@
+use crate::app::TemplateApp;
+use crate::database::Database;
+use crate::pdf::{PdfWriter, TextStyle};
+use crate::timestamps;
+use crate::usage::TokenUsage;
+use rusqlite::Result as SqliteResult;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Version of the JSON layout, raised whenever a field changes meaning.
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Json,
+    Html,
+    Pdf,
+}
+
+impl ExportFormat {
+    pub const ALL: [Self; 4] = [Self::Markdown, Self::Json, Self::Html, Self::Pdf];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Markdown => "Markdown",
+            Self::Json => "JSON",
+            Self::Html => "HTML",
+            Self::Pdf => "PDF",
+        }
+    }
+
+    /// Format named on the command line or by a file extension, e.g. `md` or `pdf`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            "html" | "htm" => Some(Self::Html),
+            "pdf" => Some(Self::Pdf),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Html => "html",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+/// What gets exported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportSource {
+    Conversation(i64),
+    Digest,
+    Memory,
+}
+
+impl ExportSource {
+    fn kind(self) -> &'static str {
+        match self {
+            Self::Conversation(_) => "conversation",
+            Self::Digest => "digest",
+            Self::Memory => "memory",
+        }
+    }
+}
+
+/// Everything an export contains, written as-is for JSON so nothing is lost.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExportDocument {
+    pub format_version: u32,
+    /// `conversation`, `digest` or `memory`.
+    pub kind: String,
+    pub title: String,
+    pub exported_at_ms: i64,
+    pub items: Vec<ExportItem>,
+    /// System prompt revisions referenced by `items`.
+    #[serde(default)]
+    pub prompt_revisions: Vec<ExportPromptRevision>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExportItem {
+    pub id: String,
+    /// `user` or `assistant`.
+    pub role: String,
+    pub content: String,
+    /// Unix milliseconds, 0 if unknown.
+    pub timestamp_ms: i64,
+    /// The same instant in RFC 3339 UTC, for readers of the file; ignored on import.
+    #[serde(default)]
+    pub timestamp: String,
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    #[serde(default)]
+    pub prompt_revision_id: Option<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExportPromptRevision {
+    pub id: i64,
+    /// `role_name` of the assistant role the prompt belongs to.
+    pub role: String,
+    pub panel_type: String,
+    pub prompt_text: String,
+    pub author: String,
+    pub created_at: String,
+}
+
+fn rfc3339(timestamp_ms: i64) -> String {
+    if timestamp_ms <= 0 {
+        return String::new();
+    }
+    chrono::DateTime::from_timestamp_millis(timestamp_ms)
+        .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+fn item(id: String, role: String, content: String, timestamp_ms: i64) -> ExportItem {
+    ExportItem {
+        id,
+        role,
+        content,
+        timestamp_ms,
+        timestamp: rfc3339(timestamp_ms),
+        truncated: false,
+        usage: None,
+        prompt_revision_id: None,
+    }
+}
+
+/// Read a conversation, the digest or long term memory from the database.
+pub fn build_document(
+    db: &Database,
+    source: ExportSource,
+    now_ms: i64,
+) -> SqliteResult<ExportDocument> {
+    let (title, items): (String, Vec<ExportItem>) = match source {
+        ExportSource::Conversation(conversation_id) => {
+            let title = db.get_conversation(conversation_id)?.map_or_else(
+                || "Conversation".to_owned(),
+                |conversation| conversation.title,
+            );
+            let items = db
+                .load_chat_messages(conversation_id)?
+                .into_iter()
+                .map(|message| ExportItem {
+                    truncated: message.truncated,
+                    usage: message.usage,
+                    prompt_revision_id: message.prompt_revision_id,
+                    ..item(
+                        message.id,
+                        message.role,
+                        message.content,
+                        message.timestamp_ms,
+                    )
+                })
+                .collect();
+            (title, items)
+        }
+        ExportSource::Digest => {
+            let items = db
+                .load_digest_items()?
+                .into_iter()
+                .map(|digest| {
+                    item(
+                        digest.id,
+                        digest.source,
+                        digest.content,
+                        digest.timestamp_ms,
+                    )
+                })
+                .collect();
+            ("Digest".to_owned(), items)
+        }
+        ExportSource::Memory => {
+            let items = db
+                .load_longterm_memory_items()?
+                .into_iter()
+                .map(|memory| {
+                    item(
+                        memory.id,
+                        memory.source,
+                        memory.content,
+                        memory.timestamp_ms,
+                    )
+                })
+                .collect();
+            ("Long Term Memory".to_owned(), items)
+        }
+    };
+
+    let revision_ids: BTreeSet<i64> = items
+        .iter()
+        .filter_map(|item| item.prompt_revision_id)
+        .collect();
+    let mut prompt_revisions = Vec::new();
+    if !revision_ids.is_empty() {
+        let roles = db.get_all_assistant_roles()?;
+        for revision_id in revision_ids {
+            let Some(revision) = db.get_prompt_revision(revision_id)? else {
+                continue;
+            };
+            let role = roles
+                .iter()
+                .find(|role| role.id == revision.role_id)
+                .map(|role| role.role_name.clone())
+                .unwrap_or_default();
+            prompt_revisions.push(ExportPromptRevision {
+                id: revision.id,
+                role,
+                panel_type: revision.panel_type,
+                prompt_text: revision.prompt_text,
+                author: revision.author,
+                created_at: revision.created_at,
+            });
+        }
+    }
+
+    Ok(ExportDocument {
+        format_version: EXPORT_FORMAT_VERSION,
+        kind: source.kind().to_owned(),
+        title,
+        exported_at_ms: now_ms,
+        items,
+        prompt_revisions,
+    })
+}
+
+fn role_label(role: &str) -> &str {
+    match role {
+        "user" => "You",
+        "assistant" => "Assistant",
+        other => other,
+    }
+}
+
+/// Model, token counts, prompt revision and truncation of an item, if any.
+fn item_details(item: &ExportItem) -> String {
+    let mut details = Vec::new();
+    if let Some(usage) = &item.usage {
+        let tokens = format!(
+            "{} prompt + {} completion tokens",
+            usage.prompt_tokens, usage.completion_tokens
+        );
+        if usage.model.is_empty() {
+            details.push(tokens);
+        } else {
+            details.push(format!("{} · {tokens}", usage.model));
+        }
+    }
+    if let Some(revision_id) = item.prompt_revision_id {
+        details.push(format!("prompt r{revision_id}"));
+    }
+    if item.truncated {
+        details.push("response truncated".to_owned());
+    }
+    details.join(" · ")
+}
+
+fn summary_line(document: &ExportDocument) -> String {
+    format!(
+        "{} items · exported {}",
+        document.items.len(),
+        timestamps::format_local(document.exported_at_ms)
+    )
+}
+
+fn revision_heading(revision: &ExportPromptRevision) -> String {
+    let mut heading = format!(
+        "r{} · {} · {} panel",
+        revision.id, revision.role, revision.panel_type
+    );
+    if !revision.author.is_empty() {
+        heading.push_str(&format!(" · {}", revision.author));
+    }
+    heading.push_str(&format!(" · {}", revision.created_at));
+    heading
+}
+
+/// Code fence longer than any run of backticks in `text`.
+fn fence_for(text: &str) -> String {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest_run.max(2) + 1)
+}
+
+pub fn to_markdown(document: &ExportDocument) -> String {
+    let mut markdown = format!("# {}\n\n*{}*\n\n", document.title, summary_line(document));
+    for item in &document.items {
+        markdown.push_str(&format!(
+            "## {} · {}\n\n{}\n\n",
+            role_label(&item.role),
+            timestamps::format_local(item.timestamp_ms),
+            item.content.trim_end()
+        ));
+        let details = item_details(item);
+        if !details.is_empty() {
+            markdown.push_str(&format!("*{details}*\n\n"));
+        }
+        markdown.push_str("---\n\n");
+    }
+    if !document.prompt_revisions.is_empty() {
+        markdown.push_str("## System prompts used\n\n");
+        for revision in &document.prompt_revisions {
+            let fence = fence_for(&revision.prompt_text);
+            markdown.push_str(&format!(
+                "### {}\n\n{fence}text\n{}\n{fence}\n\n",
+                revision_heading(revision),
+                revision.prompt_text.trim_end()
+            ));
+        }
+    }
+    markdown
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render message Markdown, showing any raw HTML in it as text.
+fn markdown_to_html(markdown: &str) -> String {
+    use pulldown_cmark::{Event, Options, Parser};
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    )
+    .map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        other => other,
+    });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+    html
+}
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,'Segoe UI',Helvetica,Arial,sans-serif;max-width:820px;margin:40px auto;padding:0 20px;color:#222;line-height:1.5}\
+header.summary{color:#777;margin-bottom:24px}\
+section.item{border-top:1px solid #ddd;padding:12px 0}\
+section.item h2{font-size:1em;margin:0 0 6px}\
+section.user .content{background:#eeebe2;border-radius:4px;padding:8px}\
+time,.details{color:#888;font-weight:normal;font-size:.85em}\
+pre{background:#f5f5f5;padding:8px;overflow-x:auto;white-space:pre-wrap}\
+code{font-family:Menlo,Consolas,monospace}\
+table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:4px 8px}";
+
+pub fn to_html(document: &ExportDocument) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<header class=\"summary\">{}</header>\n",
+        escape_html(&summary_line(document)),
+        title = escape_html(&document.title),
+    );
+    for item in &document.items {
+        html.push_str(&format!(
+            "<section class=\"item {}\">\n<h2>{} <time datetime=\"{}\">{}</time></h2>\n<div class=\"content\">{}</div>\n",
+            escape_html(&item.role),
+            escape_html(role_label(&item.role)),
+            escape_html(&item.timestamp),
+            escape_html(&timestamps::format_local(item.timestamp_ms)),
+            markdown_to_html(&item.content),
+        ));
+        let details = item_details(item);
+        if !details.is_empty() {
+            html.push_str(&format!(
+                "<p class=\"details\">{}</p>\n",
+                escape_html(&details)
+            ));
+        }
+        html.push_str("</section>\n");
+    }
+    if !document.prompt_revisions.is_empty() {
+        html.push_str("<h2>System prompts used</h2>\n");
+        for revision in &document.prompt_revisions {
+            html.push_str(&format!(
+                "<h3>{}</h3>\n<pre>{}</pre>\n",
+                escape_html(&revision_heading(revision)),
+                escape_html(&revision.prompt_text)
+            ));
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+pub fn to_pdf(document: &ExportDocument) -> std::io::Result<Vec<u8>> {
+    let mut pdf = PdfWriter::new(&document.title);
+    pdf.text(&document.title, TextStyle::Title);
+    pdf.text(&summary_line(document), TextStyle::Note);
+    pdf.space(8.0);
+    for item in &document.items {
+        pdf.rule();
+        pdf.text(
+            &format!(
+                "{} · {}",
+                role_label(&item.role),
+                timestamps::format_local(item.timestamp_ms)
+            ),
+            TextStyle::Heading,
+        );
+        pdf.space(2.0);
+        pdf.text(item.content.trim_end(), TextStyle::Body);
+        let details = item_details(item);
+        if !details.is_empty() {
+            pdf.space(2.0);
+            pdf.text(&details, TextStyle::Note);
+        }
+    }
+    if !document.prompt_revisions.is_empty() {
+        pdf.rule();
+        pdf.text("System prompts used", TextStyle::Heading);
+        for revision in &document.prompt_revisions {
+            pdf.space(4.0);
+            pdf.text(&revision_heading(revision), TextStyle::Note);
+            pdf.text(revision.prompt_text.trim_end(), TextStyle::Body);
+        }
+    }
+    pdf.finish()
+}
+
+/// File contents of `document` in `format`.
+pub fn render(document: &ExportDocument, format: ExportFormat) -> std::io::Result<Vec<u8>> {
+    Ok(match format {
+        ExportFormat::Markdown => to_markdown(document).into_bytes(),
+        ExportFormat::Json => serde_json::to_vec_pretty(document)?,
+        ExportFormat::Html => to_html(document).into_bytes(),
+        ExportFormat::Pdf => to_pdf(document)?,
+    })
+}
+
+/// File name for an export such as `client-call.md`.
+fn file_name(title: &str, format: ExportFormat) -> String {
+    let mut stem = String::new();
+    for c in title.trim().chars() {
+        if c.is_alphanumeric() {
+            stem.extend(c.to_lowercase());
+        } else if !stem.is_empty() && !stem.ends_with('-') {
+            stem.push('-');
+        }
+    }
+    let stem = stem.trim_end_matches('-');
+    let stem = if stem.is_empty() { "export" } else { stem };
+    format!("{stem}.{}", format.extension())
+}
+
+fn default_export_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default()
+}
+
+pub struct ExportDialogState {
+    pub source: ExportSource,
+    pub format: ExportFormat,
+    /// Leave out digest or memory items that aren't ticked in their panel.
+    pub only_selected: bool,
+    pub path_input: String,
+    pub error: Option<String>,
+    pub saved_to: Option<PathBuf>,
+}
+
+impl Default for ExportDialogState {
+    fn default() -> Self {
+        Self {
+            source: ExportSource::Digest,
+            format: ExportFormat::default(),
+            only_selected: false,
+            path_input: String::new(),
+            error: None,
+            saved_to: None,
+        }
+    }
+}
+
+impl TemplateApp {
+    pub fn open_export_dialog(&mut self) {
+        if let Some(conversation_id) = self.current_conversation_id {
+            self.export_dialog.source = ExportSource::Conversation(conversation_id);
+        }
+        self.export_dialog.error = None;
+        self.export_dialog.saved_to = None;
+        self.update_export_path();
+        self.show_export_dialog = true;
+    }
+
+    fn export_title(&self, source: ExportSource) -> String {
+        match source {
+            ExportSource::Conversation(conversation_id) => self
+                .conversations
+                .iter()
+                .find(|conversation| conversation.id == conversation_id)
+                .map(|conversation| conversation.title.clone())
+                .unwrap_or_else(|| "Conversation".to_owned()),
+            ExportSource::Digest => "Digest".to_owned(),
+            ExportSource::Memory => "Long Term Memory".to_owned(),
+        }
+    }
+
+    /// Suggest a file name for the chosen source and format, keeping the folder.
+    fn update_export_path(&mut self) {
+        let dialog = &self.export_dialog;
+        let dir = Path::new(dialog.path_input.trim())
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(default_export_dir);
+        let name = file_name(&self.export_title(dialog.source), dialog.format);
+        self.export_dialog.path_input = dir.join(name).display().to_string();
+    }
+
+    /// Ids of the items ticked in the digest or memory panel.
+    fn selected_item_ids(&self, source: ExportSource) -> Option<HashSet<String>> {
+        match source {
+            ExportSource::Conversation(_) => None,
+            ExportSource::Digest => Some(
+                self.digest_items
+                    .iter()
+                    .filter(|item| item.selected)
+                    .map(|item| item.id.clone())
+                    .collect(),
+            ),
+            ExportSource::Memory => Some(
+                self.long_term_memory_items
+                    .iter()
+                    .filter(|item| item.selected)
+                    .map(|item| item.id.clone())
+                    .collect(),
+            ),
+        }
+    }
+
+    fn write_export(&self, path: &Path) -> Result<(), String> {
+        let dialog = &self.export_dialog;
+        let db = self.database.as_ref().ok_or("No database is open")?;
+        let mut document = build_document(db, dialog.source, timestamps::now_ms())
+            .map_err(|e| format!("Database error: {e}"))?;
+        if dialog.only_selected {
+            if let Some(selected) = self.selected_item_ids(dialog.source) {
+                document.items.retain(|item| selected.contains(&item.id));
+            }
+        }
+        let bytes = render(&document, dialog.format).map_err(|e| e.to_string())?;
+        std::fs::write(path, bytes).map_err(|e| format!("Could not write {}: {e}", path.display()))
+    }
+
+    #[expect(clippy::too_many_lines)]
+    pub fn render_export_dialog(&mut self, ctx: &egui::Context) {
+        if !self.show_export_dialog {
+            return;
+        }
+
+        let mut show_export_dialog = self.show_export_dialog;
+        let mut path_changed = false;
+        let mut export_clicked = false;
+        let previous_source = self.export_dialog.source;
+
+        egui::Window::new("📤 Export")
+            .open(&mut show_export_dialog)
+            .default_width(520.0)
+            .resizable(true)
+            .show(ctx, |ui| {
+                egui::Grid::new("export_options")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Export:");
+                        let selected_text = self.export_title(self.export_dialog.source);
+                        egui::ComboBox::from_id_salt("export_source")
+                            .selected_text(selected_text)
+                            .width(300.0)
+                            .show_ui(ui, |ui| {
+                                for conversation in &self.conversations {
+                                    ui.selectable_value(
+                                        &mut self.export_dialog.source,
+                                        ExportSource::Conversation(conversation.id),
+                                        format!("💬 {}", conversation.title),
+                                    );
+                                }
+                                ui.separator();
+                                ui.selectable_value(
+                                    &mut self.export_dialog.source,
+                                    ExportSource::Digest,
+                                    "📌 Digest",
+                                );
+                                ui.selectable_value(
+                                    &mut self.export_dialog.source,
+                                    ExportSource::Memory,
+                                    "🗄 Long Term Memory",
+                                );
+                            });
+                        ui.end_row();
+
+                        ui.label("Format:");
+                        ui.horizontal(|ui| {
+                            for format in ExportFormat::ALL {
+                                if ui
+                                    .selectable_value(
+                                        &mut self.export_dialog.format,
+                                        format,
+                                        format.label(),
+                                    )
+                                    .changed()
+                                {
+                                    path_changed = true;
+                                }
+                            }
+                        });
+                        ui.end_row();
+                    });
+
+                if !matches!(self.export_dialog.source, ExportSource::Conversation(_)) {
+                    ui.checkbox(
+                        &mut self.export_dialog.only_selected,
+                        "Only items ticked in the panel",
+                    );
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.export_dialog.path_input)
+                            .desired_width(ui.available_width() - 70.0),
+                    );
+                    let path_input = self.export_dialog.path_input.trim();
+                    if ui
+                        .add_enabled(!path_input.is_empty(), egui::Button::new("Export"))
+                        .clicked()
+                    {
+                        export_clicked = true;
+                    }
+                });
+
+                if let Some(error) = &self.export_dialog.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                if let Some(saved_to) = &self.export_dialog.saved_to {
+                    ui.colored_label(
+                        egui::Color32::DARK_GREEN,
+                        format!("✔ Saved to {}", saved_to.display()),
+                    );
+                }
+            });
+
+        self.show_export_dialog = show_export_dialog;
+        if path_changed || self.export_dialog.source != previous_source {
+            self.update_export_path();
+        }
+        if export_clicked {
+            let path = PathBuf::from(self.export_dialog.path_input.trim());
+            match self.write_export(&path) {
+                Ok(()) => {
+                    log::info!("Exported to {}", path.display());
+                    self.export_dialog.error = None;
+                    self.export_dialog.saved_to = Some(path);
+                }
+                Err(e) => {
+                    log::error!("Export failed: {e}");
+                    self.export_dialog.error = Some(e);
+                    self.export_dialog.saved_to = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_document() -> ExportDocument {
+        ExportDocument {
+            format_version: EXPORT_FORMAT_VERSION,
+            kind: "conversation".to_owned(),
+            title: "Client call (draft)".to_owned(),
+            exported_at_ms: 1_717_164_180_000,
+            items: vec![
+                item(
+                    "a".to_owned(),
+                    "user".to_owned(),
+                    "Is the NDA <b>mutual</b>?".to_owned(),
+                    1_717_164_000_000,
+                ),
+                ExportItem {
+                    usage: Some(TokenUsage {
+                        model: "gpt-4o".to_owned(),
+                        prompt_tokens: 120,
+                        completion_tokens: 340,
+                    }),
+                    prompt_revision_id: Some(3),
+                    truncated: true,
+                    ..item(
+                        "b".to_owned(),
+                        "assistant".to_owned(),
+                        "Yes – both parties.\n\n".repeat(200),
+                        1_717_164_060_123,
+                    )
+                },
+            ],
+            prompt_revisions: vec![ExportPromptRevision {
+                id: 3,
+                role: "lawyer".to_owned(),
+                panel_type: "chat".to_owned(),
+                prompt_text: "Answer with ``` fences".to_owned(),
+                author: String::new(),
+                created_at: "2024-05-31 12:00:00".to_owned(),
+            }],
+        }
+    }
+
+    #[test]
+    fn json_export_round_trips() {
+        let document = sample_document();
+        let bytes = render(&document, ExportFormat::Json).expect("render");
+        let parsed: ExportDocument = serde_json::from_slice(&bytes).expect("parse");
+        assert_eq!(parsed, document);
+        assert_eq!(parsed.items[1].timestamp, "2024-05-31T14:01:00.123Z");
+    }
+
+    #[test]
+    fn html_export_escapes_raw_html() {
+        let html = to_html(&sample_document());
+        assert!(
+            html.contains("&lt;b&gt;mutual&lt;/b&gt;"),
+            "raw HTML is shown as text"
+        );
+        assert!(
+            html.contains("gpt-4o · 120 prompt + 340 completion tokens"),
+            "usage is listed"
+        );
+    }
+
+    #[test]
+    fn pdf_cross_reference_points_at_objects() {
+        let pdf = to_pdf(&sample_document()).expect("Latin text");
+        assert!(pdf.starts_with(b"%PDF-1.4\n"), "header");
+        assert!(pdf.ends_with(b"%%EOF\n"), "trailer");
+
+        // Everything after the binary marker line is ASCII
+        let text = std::str::from_utf8(&pdf[15..]).expect("ASCII body");
+        let xref_start = text.rfind("startxref\n").expect("startxref") + "startxref\n".len();
+        let xref_offset: usize = text[xref_start..]
+            .lines()
+            .next()
+            .expect("offset")
+            .parse()
+            .expect("number");
+        assert!(
+            pdf[xref_offset..].starts_with(b"xref\n"),
+            "startxref points at the table"
+        );
+
+        let entries = text[xref_offset - 15..]
+            .lines()
+            .skip(3)
+            .take_while(|line| !line.starts_with("trailer"));
+        for (i, entry) in entries.enumerate() {
+            let offset: usize = entry[..10].parse().expect("entry offset");
+            let expected = format!("{} 0 obj", i + 1);
+            assert!(
+                pdf[offset..].starts_with(expected.as_bytes()),
+                "object {} offset",
+                i + 1
+            );
+        }
+        assert!(
+            text.matches("/Type /Page ").count() > 1,
+            "long content spans pages"
+        );
+    }
+
+    #[test]
+    fn pdf_refuses_text_the_standard_fonts_cannot_show() {
+        let mut document = sample_document();
+        document.items[0].content = "这份保密协议是双向的吗？".to_owned();
+
+        let error = render(&document, ExportFormat::Pdf)
+            .expect_err("CJK text must not turn into question marks");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("“这”"), "{error}");
+
+        let html =
+            String::from_utf8(render(&document, ExportFormat::Html).expect("HTML")).expect("UTF-8");
+        assert!(
+            html.contains("这份保密协议是双向的吗？"),
+            "other formats keep the text"
+        );
+
+        document.items[0].content = "Vertragsentwurf für Müller – “final”".to_owned();
+        assert!(
+            to_pdf(&document).is_ok(),
+            "Latin-1 and typographic quotes are supported"
+        );
+    }
+}
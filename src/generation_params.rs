@@ -0,0 +1,263 @@
+use crate::app::TemplateApp;
+use crate::llm_provider::{GenerationParams, ResponseFormat};
+use std::collections::BTreeMap;
+
+/// Generation parameters of an assistant role, stored as JSON in `assistant_roles`.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RoleGenerationParams {
+    /// Used by every panel.
+    pub all_panels: GenerationParams,
+    /// Overrides for one panel, keyed by panel type (`chat`, `digest`, `memory`).
+    pub panels: BTreeMap<String, GenerationParams>,
+}
+
+impl RoleGenerationParams {
+    /// Whether the role sets nothing and inherits everything from the profile.
+    pub fn is_unset(&self) -> bool {
+        self.all_panels.is_unset() && self.panels.values().all(GenerationParams::is_unset)
+    }
+
+    /// Parameters for a request from `panel_type`: the panel's own, then the
+    /// role's, then the profile's, then the historical defaults.
+    pub fn resolve(&self, panel_type: &str, profile: &GenerationParams) -> GenerationParams {
+        self.panels
+            .get(panel_type)
+            .cloned()
+            .unwrap_or_default()
+            .or(self.all_panels.clone())
+            .or(profile.clone())
+            .or(GenerationParams::FALLBACK)
+    }
+}
+
+/// Editor for a set of parameters; unchecked ones are inherited.
+pub fn edit_generation_params(ui: &mut egui::Ui, params: &mut GenerationParams, id_salt: &str) {
+    egui::Grid::new(("generation_params", id_salt))
+        .num_columns(2)
+        .show(ui, |ui| {
+            optional_row(ui, "Temperature:", &mut params.temperature, 0.3, |drag| {
+                drag.speed(0.01).range(0.0..=2.0)
+            });
+            optional_row(ui, "Max tokens:", &mut params.max_tokens, 4096, |drag| {
+                drag.speed(64).range(1..=1_000_000)
+            });
+            optional_row(ui, "Top p:", &mut params.top_p, 1.0, |drag| {
+                drag.speed(0.01).range(0.0..=1.0)
+            });
+            optional_row(
+                ui,
+                "Presence penalty:",
+                &mut params.presence_penalty,
+                0.0,
+                |drag| drag.speed(0.01).range(-2.0..=2.0),
+            );
+            optional_row(
+                ui,
+                "Frequency penalty:",
+                &mut params.frequency_penalty,
+                0.0,
+                |drag| drag.speed(0.01).range(-2.0..=2.0),
+            );
+            optional_row(ui, "Seed:", &mut params.seed, 0, |drag| drag.speed(1));
+
+            ui.label("Response format:");
+            egui::ComboBox::from_id_salt(("response_format", id_salt))
+                .selected_text(
+                    params
+                        .response_format
+                        .map_or("Inherit", ResponseFormat::display_name),
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut params.response_format, None, "Inherit");
+                    for format in ResponseFormat::ALL {
+                        ui.selectable_value(
+                            &mut params.response_format,
+                            Some(format),
+                            format.display_name(),
+                        );
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Stop sequences:");
+            let mut stop = params.stop.join("\n");
+            let response = ui.add(
+                egui::TextEdit::multiline(&mut stop)
+                    .desired_rows(2)
+                    .desired_width(200.0)
+                    .hint_text("One per line"),
+            );
+            if response.changed() {
+                params.stop = if stop.is_empty() {
+                    Vec::new()
+                } else {
+                    stop.split('\n').map(str::to_owned).collect()
+                };
+            }
+            ui.end_row();
+        });
+}
+
+/// A grid row with a checkbox that sets the value and a drag value to edit it.
+fn optional_row<T: egui::emath::Numeric>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<T>,
+    default: T,
+    configure: impl FnOnce(egui::DragValue<'_>) -> egui::DragValue<'_>,
+) {
+    ui.label(label);
+    ui.horizontal(|ui| {
+        let mut set = value.is_some();
+        ui.checkbox(&mut set, "");
+        let mut current = value.unwrap_or(default);
+        ui.add_enabled(set, configure(egui::DragValue::new(&mut current)));
+        *value = set.then_some(current);
+    });
+    ui.end_row();
+}
+
+impl TemplateApp {
+    /// Parameters for the next request from `panel_type`; the one-off override
+    /// from the input area only applies to chat.
+    pub fn generation_params(&self, panel_type: &str) -> GenerationParams {
+        let params = self
+            .current_role_params
+            .resolve(panel_type, &self.active_profile.config.params);
+        if panel_type == "chat" {
+            self.request_params.clone().or(params)
+        } else {
+            params
+        }
+    }
+
+    /// Toggle for the advanced drawer, shown next to the chat input.
+    pub fn render_request_params_toggle(&mut self, ui: &mut egui::Ui) {
+        let overridden = !self.request_params.is_unset();
+        let label = if overridden { "🎛•" } else { "🎛" };
+        if ui
+            .selectable_label(self.show_request_params, label)
+            .on_hover_text("Generation parameters for the next message")
+            .clicked()
+        {
+            self.show_request_params = !self.show_request_params;
+        }
+    }
+
+    /// Drawer to override the role's parameters for the next message only.
+    pub fn render_request_params_drawer(&mut self, ui: &mut egui::Ui) {
+        if !self.show_request_params {
+            return;
+        }
+
+        egui::Frame::new()
+            .fill(ui.visuals().extreme_bg_color)
+            .inner_margin(6.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("Next message only").strong());
+                    if ui
+                        .add_enabled(
+                            !self.request_params.is_unset(),
+                            egui::Button::new("↺ Reset"),
+                        )
+                        .clicked()
+                    {
+                        self.request_params = GenerationParams::default();
+                    }
+                });
+                ui.weak("Checked values override the role and profile; the rest is inherited.");
+                edit_generation_params(ui, &mut self.request_params, "request");
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use std::path::Path;
+
+    #[test]
+    fn panel_overrides_win_over_role_and_profile() {
+        let role = RoleGenerationParams {
+            all_panels: GenerationParams {
+                temperature: Some(0.9),
+                top_p: Some(0.95),
+                ..GenerationParams::default()
+            },
+            panels: BTreeMap::from([(
+                "digest".to_owned(),
+                GenerationParams {
+                    temperature: Some(0.0),
+                    stop: vec!["END".to_owned(), String::new()],
+                    ..GenerationParams::default()
+                },
+            )]),
+        };
+        let profile = GenerationParams {
+            max_tokens: Some(512),
+            top_p: Some(0.5),
+            ..GenerationParams::default()
+        };
+
+        let digest = role.resolve("digest", &profile);
+        assert_eq!(digest.temperature, Some(0.0), "panel override");
+        assert_eq!(digest.top_p, Some(0.95), "role setting");
+        assert_eq!(digest.max_tokens, Some(512), "profile setting");
+        assert_eq!(digest.stop_sequences().collect::<Vec<_>>(), vec!["END"]);
+
+        let chat = role.resolve("chat", &profile);
+        assert_eq!(
+            chat.temperature,
+            Some(0.9),
+            "role setting without a panel override"
+        );
+
+        let unset = RoleGenerationParams::default().resolve("memory", &GenerationParams::default());
+        assert_eq!(unset, GenerationParams::FALLBACK);
+    }
+
+    #[test]
+    fn role_params_round_trip_through_the_database() {
+        let db = Database::open(Path::new(":memory:")).expect("open database");
+        let role_id = db
+            .create_assistant_role("drafting", "Drafting", "")
+            .expect("role");
+        assert!(
+            db.get_role_generation_params(role_id)
+                .expect("load")
+                .is_unset()
+        );
+
+        let params = RoleGenerationParams {
+            all_panels: GenerationParams {
+                seed: Some(7),
+                response_format: Some(ResponseFormat::JsonObject),
+                ..GenerationParams::default()
+            },
+            panels: BTreeMap::from([(
+                "chat".to_owned(),
+                GenerationParams {
+                    temperature: Some(0.05),
+                    ..GenerationParams::default()
+                },
+            )]),
+        };
+        db.set_role_generation_params(role_id, &params)
+            .expect("save");
+        assert_eq!(
+            db.get_role_generation_params(role_id).expect("load"),
+            params
+        );
+
+        db.set_role_generation_params(role_id, &RoleGenerationParams::default())
+            .expect("clear");
+        assert!(
+            db.get_role_generation_params(role_id)
+                .expect("load")
+                .is_unset()
+        );
+    }
+}
@@ -0,0 +1,1086 @@
+use crate::app::TemplateApp;
+use crate::database::{Database, content_hash};
+use crate::export::ExportDocument;
+use crate::usage::TokenUsage;
+use rusqlite::Result as SqliteResult;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// How many conversations are imported between two frames, so the window
+/// keeps repainting and showing progress during long imports.
+const CONVERSATIONS_PER_FRAME: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// `conversations.json` from a `ChatGPT` data export.
+    ChatGpt,
+    /// JSON written by File → Export.
+    Pbot,
+    /// One `{"role", "content", "timestamp"}` object per line.
+    Jsonl,
+}
+
+impl ImportFormat {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ChatGpt => "ChatGPT export",
+            Self::Pbot => "JSON export from this app",
+            Self::Jsonl => "JSONL messages",
+        }
+    }
+
+    fn key_prefix(self) -> &'static str {
+        match self {
+            Self::ChatGpt => "chatgpt",
+            Self::Pbot => "pbot",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportedMessage {
+    /// `user` or `assistant`.
+    pub role: String,
+    pub content: String,
+    /// Unix milliseconds, 0 if unknown.
+    pub timestamp_ms: i64,
+    pub truncated: bool,
+    pub usage: Option<TokenUsage>,
+}
+
+impl ImportedMessage {
+    fn new(role: &str, content: String, timestamp_ms: i64) -> Self {
+        Self {
+            role: role.to_owned(),
+            content,
+            timestamp_ms,
+            truncated: false,
+            usage: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedConversation {
+    pub title: String,
+    /// Identifies the conversation across imports, e.g. `chatgpt:<id>`.
+    pub source_key: String,
+    pub messages: Vec<ImportedMessage>,
+}
+
+impl ImportedConversation {
+    fn first_timestamp_ms(&self) -> i64 {
+        self.messages
+            .iter()
+            .map(|m| m.timestamp_ms)
+            .find(|&ms| ms > 0)
+            .unwrap_or(0)
+    }
+
+    fn last_timestamp_ms(&self) -> i64 {
+        self.messages
+            .iter()
+            .map(|m| m.timestamp_ms)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Everything read from one file.
+#[derive(Debug)]
+pub struct ImportBatch {
+    pub format: ImportFormat,
+    pub conversations: Vec<ImportedConversation>,
+    pub digest_items: Vec<ImportedMessage>,
+    pub memory_items: Vec<ImportedMessage>,
+}
+
+impl ImportBatch {
+    fn new(format: ImportFormat) -> Self {
+        Self {
+            format,
+            conversations: Vec::new(),
+            digest_items: Vec::new(),
+            memory_items: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    Json {
+        /// 1-based line of a JSONL file.
+        line: Option<usize>,
+        error: serde_json::Error,
+    },
+    Unrecognized(String),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Json {
+                line: Some(line),
+                error,
+            } => write!(f, "invalid JSON on line {line}: {error}"),
+            Self::Json { line: None, error } => write!(f, "invalid JSON: {error}"),
+            Self::Unrecognized(reason) => write!(f, "unrecognized file: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Role as stored by the app, `None` for system and tool messages, which are skipped.
+fn normalize_role(role: &str) -> Option<&'static str> {
+    match role.to_ascii_lowercase().as_str() {
+        "user" | "human" => Some("user"),
+        "assistant" | "ai" | "bot" | "model" | "gpt" => Some("assistant"),
+        _ => None,
+    }
+}
+
+/// Unix seconds (as `ChatGPT` writes them) to milliseconds.
+fn seconds_to_ms(value: Option<&Value>) -> Option<i64> {
+    value
+        .and_then(Value::as_f64)
+        .filter(|seconds| *seconds > 0.0)
+        .map(|seconds| (seconds * 1000.0).round() as i64)
+}
+
+/// A JSONL timestamp: Unix seconds or milliseconds, or an RFC 3339 string.
+fn parse_timestamp(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(number) => {
+            let number = number.as_f64()?;
+            // Anything past 1e11 can only be milliseconds (1e11 seconds is year 5138)
+            Some(if number > 1e11 {
+                number.round() as i64
+            } else {
+                (number * 1000.0).round() as i64
+            })
+        }
+        Value::String(text) => chrono::DateTime::parse_from_rfc3339(text.trim())
+            .ok()
+            .map(|time| time.timestamp_millis())
+            .or_else(|| {
+                text.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|n| parse_timestamp(&Value::from(n)))
+            }),
+        _ => None,
+    }
+}
+
+/// Key for sources without ids of their own: the same messages give the same key.
+fn content_key(format: ImportFormat, messages: &[ImportedMessage]) -> String {
+    let mut text = String::new();
+    for message in messages {
+        text.push_str(&format!(
+            "{}\0{}\0{}\n",
+            message.role, message.timestamp_ms, message.content
+        ));
+    }
+    format!("{}:{}", format.key_prefix(), content_hash(&text))
+}
+
+/// Read `path`, detecting its format from the contents.
+pub fn parse_file(path: &Path) -> Result<ImportBatch, ImportError> {
+    let text = std::fs::read_to_string(path)?;
+    let fallback_title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Imported conversation".to_owned());
+    parse(&text, &fallback_title)
+}
+
+/// Parse a `ChatGPT` export, an export from this app or JSONL; JSONL
+/// messages become one conversation called `fallback_title`.
+pub fn parse(text: &str, fallback_title: &str) -> Result<ImportBatch, ImportError> {
+    let trimmed = text.trim_start_matches('\u{feff}').trim_start();
+    if trimmed.starts_with('[') {
+        let value: Value = serde_json::from_str(trimmed)
+            .map_err(|error| ImportError::Json { line: None, error })?;
+        return parse_chatgpt(&value);
+    }
+    if !trimmed.starts_with('{') {
+        return Err(ImportError::Unrecognized(
+            "expected JSON or JSONL".to_owned(),
+        ));
+    }
+
+    // A whole-file object is an export; otherwise every line is its own object
+    match serde_json::from_str::<Value>(trimmed) {
+        Ok(value) if value.get("mapping").is_some() => parse_chatgpt(&Value::Array(vec![value])),
+        Ok(value) if value.get("format_version").is_some() && value.get("items").is_some() => {
+            let document: ExportDocument = serde_json::from_value(value)
+                .map_err(|error| ImportError::Json { line: None, error })?;
+            parse_pbot(document)
+        }
+        _ => parse_jsonl(trimmed, fallback_title),
+    }
+}
+
+fn parse_chatgpt(value: &Value) -> Result<ImportBatch, ImportError> {
+    let conversations = value
+        .as_array()
+        .ok_or_else(|| ImportError::Unrecognized("expected a list of conversations".to_owned()))?;
+    if conversations
+        .iter()
+        .any(|conversation| conversation.get("mapping").is_none())
+    {
+        return Err(ImportError::Unrecognized(
+            "not a ChatGPT conversations.json: conversations have no message mapping".to_owned(),
+        ));
+    }
+    let mut batch = ImportBatch::new(ImportFormat::ChatGpt);
+    batch.conversations = conversations
+        .iter()
+        .filter_map(chatgpt_conversation)
+        .filter(|conversation| !conversation.messages.is_empty())
+        .collect();
+    Ok(batch)
+}
+
+fn parent_of(node: &Value) -> Option<&str> {
+    node.get("parent").and_then(Value::as_str)
+}
+
+fn last_child_of(node: &Value) -> Option<&str> {
+    node.get("children")
+        .and_then(Value::as_array)
+        .and_then(|children| children.last())
+        .and_then(Value::as_str)
+}
+
+/// Nodes of the branch that was showing last, root first.
+///
+/// `ChatGPT` keeps every regenerated answer as a sibling in `mapping`; walking up
+/// from `current_node` picks the one the user ended up with.
+fn chatgpt_branch<'a>(
+    conversation: &'a Value,
+    mapping: &'a serde_json::Map<String, Value>,
+) -> Vec<&'a Value> {
+    let current_node = conversation
+        .get("current_node")
+        .and_then(Value::as_str)
+        .filter(|id| mapping.contains_key(*id));
+    let mut branch = Vec::new();
+    if let Some(mut id) = current_node {
+        while let Some(node) = mapping.get(id) {
+            branch.push(node);
+            // Guard against cycles in damaged files
+            if branch.len() > mapping.len() {
+                break;
+            }
+            let Some(parent) = parent_of(node) else { break };
+            id = parent;
+        }
+        branch.reverse();
+    } else if let Some(mut node) = mapping.values().find(|node| parent_of(node).is_none()) {
+        // No current node: follow the latest child from the root
+        loop {
+            branch.push(node);
+            let Some(child) = last_child_of(node).and_then(|id| mapping.get(id)) else {
+                break;
+            };
+            if branch.len() > mapping.len() {
+                break;
+            }
+            node = child;
+        }
+    }
+    branch
+}
+
+fn chatgpt_message(node: &Value, fallback_ms: i64) -> Option<ImportedMessage> {
+    let message = node.get("message").filter(|message| message.is_object())?;
+    let role = normalize_role(message.pointer("/author/role")?.as_str()?)?;
+    if message
+        .pointer("/metadata/is_visually_hidden_from_conversation")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    let content = message.get("content")?;
+    let text = if let Some(parts) = content.get("parts").and_then(Value::as_array) {
+        // Images and other attachments are objects; only the text is kept
+        parts
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        content
+            .get("text")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned()
+    };
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let timestamp_ms = seconds_to_ms(message.get("create_time")).unwrap_or(fallback_ms);
+    Some(ImportedMessage::new(role, text, timestamp_ms))
+}
+
+fn chatgpt_conversation(conversation: &Value) -> Option<ImportedConversation> {
+    let mapping = conversation.get("mapping")?.as_object()?;
+    let created_ms = seconds_to_ms(conversation.get("create_time")).unwrap_or(0);
+
+    let mut messages = Vec::new();
+    let mut previous_ms = created_ms;
+    for node in chatgpt_branch(conversation, mapping) {
+        if let Some(message) = chatgpt_message(node, previous_ms) {
+            previous_ms = message.timestamp_ms;
+            messages.push(message);
+        }
+    }
+
+    let title = conversation
+        .get("title")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .unwrap_or("Untitled")
+        .to_owned();
+    let source_key = conversation
+        .get("conversation_id")
+        .or_else(|| conversation.get("id"))
+        .and_then(Value::as_str)
+        .map(|id| format!("chatgpt:{id}"))
+        .unwrap_or_else(|| content_key(ImportFormat::ChatGpt, &messages));
+    Some(ImportedConversation {
+        title,
+        source_key,
+        messages,
+    })
+}
+
+fn parse_pbot(document: ExportDocument) -> Result<ImportBatch, ImportError> {
+    let messages: Vec<ImportedMessage> = document
+        .items
+        .into_iter()
+        .filter_map(|item| {
+            let role = normalize_role(&item.role)?;
+            Some(ImportedMessage {
+                truncated: item.truncated,
+                usage: item.usage,
+                ..ImportedMessage::new(role, item.content, item.timestamp_ms)
+            })
+        })
+        .collect();
+
+    let mut batch = ImportBatch::new(ImportFormat::Pbot);
+    match document.kind.as_str() {
+        "conversation" => {
+            let source_key = content_key(ImportFormat::Pbot, &messages);
+            batch.conversations.push(ImportedConversation {
+                title: document.title,
+                source_key,
+                messages,
+            });
+        }
+        "digest" => batch.digest_items = messages,
+        "memory" => batch.memory_items = messages,
+        other => {
+            return Err(ImportError::Unrecognized(format!(
+                "unknown export kind `{other}`"
+            )));
+        }
+    }
+    Ok(batch)
+}
+
+fn parse_jsonl(text: &str, title: &str) -> Result<ImportBatch, ImportError> {
+    let mut messages = Vec::new();
+    let mut previous_ms = 0;
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line).map_err(|error| ImportError::Json {
+            line: Some(i + 1),
+            error,
+        })?;
+        let (Some(role), Some(content)) = (
+            value.get("role").and_then(Value::as_str),
+            value.get("content").and_then(Value::as_str),
+        ) else {
+            return Err(ImportError::Unrecognized(format!(
+                "line {} has no `role` and `content`",
+                i + 1
+            )));
+        };
+        let Some(role) = normalize_role(role) else {
+            continue;
+        };
+        // Lines without a time keep the previous one so the order survives
+        let timestamp_ms = value
+            .get("timestamp")
+            .and_then(parse_timestamp)
+            .unwrap_or(previous_ms);
+        previous_ms = timestamp_ms;
+        messages.push(ImportedMessage::new(role, content.to_owned(), timestamp_ms));
+    }
+
+    let mut batch = ImportBatch::new(ImportFormat::Jsonl);
+    if !messages.is_empty() {
+        batch.conversations.push(ImportedConversation {
+            title: title.to_owned(),
+            source_key: content_key(ImportFormat::Jsonl, &messages),
+            messages,
+        });
+    }
+    Ok(batch)
+}
+
+/// What to do with a conversation that an earlier import already created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    #[default]
+    Skip,
+    /// Replace the earlier import's messages with the new ones.
+    Replace,
+    /// Import a second copy.
+    KeepBoth,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [Self; 3] = [Self::Skip, Self::Replace, Self::KeepBoth];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Skip => "Skip it",
+            Self::Replace => "Replace it",
+            Self::KeepBoth => "Import a copy",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Imported(i64),
+    Replaced(i64),
+    Skipped,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportCounts {
+    pub imported: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+    pub messages: usize,
+    pub panel_items: usize,
+}
+
+impl ImportCounts {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Imported {} conversations with {} messages",
+            self.imported + self.replaced,
+            self.messages
+        );
+        if self.replaced > 0 {
+            summary.push_str(&format!(", {} replaced", self.replaced));
+        }
+        if self.skipped > 0 {
+            summary.push_str(&format!(", {} skipped as already imported", self.skipped));
+        }
+        if self.panel_items > 0 {
+            summary.push_str(&format!(", {} digest and memory items", self.panel_items));
+        }
+        summary
+    }
+}
+
+/// Save one conversation through `save_content`, all or nothing.
+pub fn import_conversation(
+    db: &Database,
+    conversation: &ImportedConversation,
+    policy: ConflictPolicy,
+) -> SqliteResult<ImportOutcome> {
+    db.transaction(|| {
+        let existing = db.find_imported_conversation(&conversation.source_key)?;
+        let (conversation_id, outcome) = match (existing, policy) {
+            (Some(_), ConflictPolicy::Skip) => return Ok(ImportOutcome::Skipped),
+            (Some(conversation_id), ConflictPolicy::Replace) => {
+                // The summary and pinned prompt belonged to the replaced messages
+                db.clear_chat_panel_associations(conversation_id)?;
+                db.set_context_summary(conversation_id, None)?;
+                db.set_pinned_prompt_revision(conversation_id, None)?;
+                db.rename_conversation(conversation_id, &conversation.title)?;
+                (conversation_id, ImportOutcome::Replaced(conversation_id))
+            }
+            (Some(_), ConflictPolicy::KeepBoth) | (None, _) => {
+                let conversation_id = db.create_conversation(&conversation.title)?;
+                (conversation_id, ImportOutcome::Imported(conversation_id))
+            }
+        };
+
+        for message in &conversation.messages {
+            let id = db.save_content(
+                &message.content,
+                &message.role,
+                message.timestamp_ms,
+                Some(conversation_id),
+                &["chat"],
+            )?;
+            if message.truncated {
+                db.set_content_truncated(&id, true)?;
+            }
+            if let Some(usage) = &message.usage {
+                db.set_content_usage(&id, usage)?;
+            }
+        }
+        let last_ms = conversation.last_timestamp_ms();
+        if last_ms > 0 {
+            db.set_conversation_updated_at(conversation_id, last_ms)?;
+        }
+        db.record_conversation_import(&conversation.source_key, conversation_id)?;
+        Ok(outcome)
+    })
+}
+
+/// Add digest or memory items, returning how many were added; text already in the
+/// panel is not added twice.
+pub fn import_panel_items(
+    db: &Database,
+    items: &[ImportedMessage],
+    panel_type: &str,
+) -> SqliteResult<usize> {
+    db.transaction(|| {
+        let mut added = 0;
+        for item in items {
+            if db.panel_has_content(&item.content, panel_type)? {
+                continue;
+            }
+            db.save_panel_content(&item.content, &item.role, item.timestamp_ms, panel_type)?;
+            added += 1;
+        }
+        Ok(added)
+    })
+}
+
+/// A parsed file waiting for the user to confirm.
+pub struct ImportPreview {
+    pub path: PathBuf,
+    pub batch: ImportBatch,
+    pub include: Vec<bool>,
+    /// Conversation from an earlier import of each conversation, if any.
+    pub conflicts: Vec<Option<i64>>,
+}
+
+/// An import in progress, worked through a few conversations per frame.
+pub struct ImportJob {
+    pending: VecDeque<ImportedConversation>,
+    pending_panel_items: Vec<(&'static str, Vec<ImportedMessage>)>,
+    policy: ConflictPolicy,
+    total: usize,
+    counts: ImportCounts,
+}
+
+impl ImportJob {
+    fn new(preview: ImportPreview, policy: ConflictPolicy) -> Self {
+        let ImportPreview { batch, include, .. } = preview;
+        let pending: VecDeque<ImportedConversation> = batch
+            .conversations
+            .into_iter()
+            .zip(include)
+            .filter_map(|(conversation, include)| include.then_some(conversation))
+            .collect();
+        let pending_panel_items = vec![
+            ("digest", batch.digest_items),
+            ("longterm", batch.memory_items),
+        ];
+        Self {
+            total: pending.len(),
+            pending,
+            pending_panel_items,
+            policy,
+            counts: ImportCounts::default(),
+        }
+    }
+
+    fn done(&self) -> usize {
+        self.total - self.pending.len()
+    }
+
+    /// Import up to `limit` conversations; true once nothing is left.
+    fn step(&mut self, db: &Database, limit: usize) -> SqliteResult<bool> {
+        for (panel_type, items) in std::mem::take(&mut self.pending_panel_items) {
+            self.counts.panel_items += import_panel_items(db, &items, panel_type)?;
+        }
+        for _ in 0..limit {
+            let Some(conversation) = self.pending.pop_front() else {
+                break;
+            };
+            match import_conversation(db, &conversation, self.policy)? {
+                ImportOutcome::Imported(_) => self.counts.imported += 1,
+                ImportOutcome::Replaced(_) => self.counts.replaced += 1,
+                ImportOutcome::Skipped => {
+                    self.counts.skipped += 1;
+                    continue;
+                }
+            }
+            self.counts.messages += conversation.messages.len();
+        }
+        Ok(self.pending.is_empty())
+    }
+}
+
+#[derive(Default)]
+pub struct ImportDialogState {
+    pub path_input: String,
+    pub policy: ConflictPolicy,
+    pub preview: Option<ImportPreview>,
+    pub job: Option<ImportJob>,
+    pub error: Option<String>,
+    pub summary: Option<String>,
+}
+
+fn date_range(conversations: &[ImportedConversation]) -> Option<String> {
+    let first = conversations
+        .iter()
+        .map(ImportedConversation::first_timestamp_ms)
+        .filter(|&ms| ms > 0)
+        .min()?;
+    let last = conversations
+        .iter()
+        .map(ImportedConversation::last_timestamp_ms)
+        .max()?;
+    let date = |ms| {
+        crate::timestamps::local_date(ms)
+            .map(|date| date.to_string())
+            .unwrap_or_default()
+    };
+    Some(format!("{} – {}", date(first), date(last)))
+}
+
+impl TemplateApp {
+    pub fn open_import_dialog(&mut self) {
+        self.import_dialog.error = None;
+        self.show_import_dialog = true;
+    }
+
+    fn preview_import(&mut self) {
+        let path = PathBuf::from(self.import_dialog.path_input.trim());
+        self.import_dialog.preview = None;
+        self.import_dialog.summary = None;
+        let Some(db) = &self.database else {
+            self.import_dialog.error = Some("No database is open".to_owned());
+            return;
+        };
+        let batch = match parse_file(&path) {
+            Ok(batch) => batch,
+            Err(e) => {
+                log::error!("Failed to read import file {}: {e}", path.display());
+                self.import_dialog.error = Some(format!("Could not read {}: {e}", path.display()));
+                return;
+            }
+        };
+        let conflicts = match batch
+            .conversations
+            .iter()
+            .map(|conversation| db.find_imported_conversation(&conversation.source_key))
+            .collect::<SqliteResult<Vec<_>>>()
+        {
+            Ok(conflicts) => conflicts,
+            Err(e) => {
+                log::error!("Failed to check for earlier imports: {e}");
+                self.import_dialog.error = Some(format!("Database error: {e}"));
+                return;
+            }
+        };
+        self.import_dialog.error = None;
+        self.import_dialog.preview = Some(ImportPreview {
+            path,
+            include: vec![true; batch.conversations.len()],
+            batch,
+            conflicts,
+        });
+    }
+
+    /// Advance a running import and report when it is finished.
+    fn run_import_job(&mut self, ctx: &egui::Context) {
+        let (Some(job), Some(db)) = (&mut self.import_dialog.job, &self.database) else {
+            return;
+        };
+        match job.step(db, CONVERSATIONS_PER_FRAME) {
+            Ok(false) => ctx.request_repaint(),
+            Ok(true) => {
+                let summary = job.counts.summary();
+                log::info!("{summary}");
+                self.import_dialog.summary = Some(summary);
+                self.import_dialog.job = None;
+                self.load_data_from_database();
+            }
+            Err(e) => {
+                log::error!("Import failed: {e}");
+                let counts = job.counts;
+                self.import_dialog.error = Some(format!(
+                    "Database error: {e}. {} before the error.",
+                    counts.summary()
+                ));
+                self.import_dialog.job = None;
+                self.load_data_from_database();
+            }
+        }
+    }
+
+    #[expect(clippy::too_many_lines)]
+    pub fn render_import_dialog(&mut self, ctx: &egui::Context) {
+        self.run_import_job(ctx);
+        if !self.show_import_dialog {
+            return;
+        }
+
+        let mut show_import_dialog = self.show_import_dialog;
+        let mut preview_clicked = false;
+        let mut import_clicked = false;
+        let is_running = self.import_dialog.job.is_some();
+
+        egui::Window::new("📥 Import")
+            .open(&mut show_import_dialog)
+            .default_width(560.0)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label("A ChatGPT conversations.json, a JSON export from this app, or JSONL with one {\"role\", \"content\", \"timestamp\"} object per line.");
+                ui.add_space(6.0);
+
+                ui.add_enabled_ui(!is_running, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("File:");
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut self.import_dialog.path_input)
+                                .desired_width(ui.available_width() - 70.0)
+                                .hint_text("/path/to/conversations.json"),
+                        );
+                        let submitted =
+                            response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        let has_path = !self.import_dialog.path_input.trim().is_empty();
+                        if (ui.add_enabled(has_path, egui::Button::new("Preview")).clicked() || submitted)
+                            && has_path
+                        {
+                            preview_clicked = true;
+                        }
+                    });
+                });
+
+                if let Some(error) = &self.import_dialog.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+
+                if let Some(job) = &self.import_dialog.job {
+                    ui.separator();
+                    let done = job.done();
+                    let progress = if job.total == 0 { 1.0 } else { done as f32 / job.total as f32 };
+                    ui.add(
+                        egui::ProgressBar::new(progress)
+                            .show_percentage()
+                            .text(format!("Importing {done} / {} conversations", job.total)),
+                    );
+                    return;
+                }
+
+                if let Some(summary) = &self.import_dialog.summary {
+                    ui.colored_label(egui::Color32::DARK_GREEN, format!("✔ {summary}"));
+                }
+
+                let Some(preview) = &mut self.import_dialog.preview else {
+                    return;
+                };
+                ui.separator();
+                let batch = &preview.batch;
+                let message_count: usize = batch.conversations.iter().map(|c| c.messages.len()).sum();
+                let mut description = format!(
+                    "{}: {} conversations, {message_count} messages",
+                    batch.format.label(),
+                    batch.conversations.len()
+                );
+                if let Some(range) = date_range(&batch.conversations) {
+                    description.push_str(&format!(", {range}"));
+                }
+                if !batch.digest_items.is_empty() {
+                    description.push_str(&format!(", {} digest items", batch.digest_items.len()));
+                }
+                if !batch.memory_items.is_empty() {
+                    description.push_str(&format!(", {} memory items", batch.memory_items.len()));
+                }
+                ui.label(description);
+
+                let conflict_count = preview.conflicts.iter().filter(|conflict| conflict.is_some()).count();
+                if conflict_count > 0 {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{conflict_count} were imported before:"));
+                        for policy in ConflictPolicy::ALL {
+                            ui.radio_value(&mut self.import_dialog.policy, policy, policy.label());
+                        }
+                    });
+                }
+
+                if !batch.conversations.is_empty() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("Select all").clicked() {
+                            preview.include.fill(true);
+                        }
+                        if ui.small_button("Select none").clicked() {
+                            preview.include.fill(false);
+                        }
+                    });
+                    egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                        for (i, conversation) in batch.conversations.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut preview.include[i], &conversation.title);
+                                ui.colored_label(
+                                    egui::Color32::GRAY,
+                                    format!(
+                                        "{} messages · {}",
+                                        conversation.messages.len(),
+                                        crate::timestamps::format_local(conversation.first_timestamp_ms())
+                                    ),
+                                );
+                                if preview.conflicts[i].is_some() {
+                                    ui.colored_label(egui::Color32::BROWN, "⚠ imported before");
+                                }
+                            });
+                        }
+                    });
+                }
+
+                ui.separator();
+                let selected = preview.include.iter().filter(|include| **include).count();
+                let has_panel_items = !batch.digest_items.is_empty() || !batch.memory_items.is_empty();
+                if ui
+                    .add_enabled(selected > 0 || has_panel_items, egui::Button::new("Import"))
+                    .clicked()
+                {
+                    import_clicked = true;
+                }
+            });
+
+        self.show_import_dialog = show_import_dialog;
+        if preview_clicked {
+            self.preview_import();
+        }
+        if import_clicked {
+            if let Some(preview) = self.import_dialog.preview.take() {
+                log::info!("Importing {}", preview.path.display());
+                self.import_dialog.summary = None;
+                self.import_dialog.job = Some(ImportJob::new(preview, self.import_dialog.policy));
+                ctx.request_repaint();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHATGPT: &str = include_str!("../tests/fixtures/import/chatgpt_conversations.json");
+    const PBOT_CONVERSATION: &str = include_str!("../tests/fixtures/import/pbot_conversation.json");
+    const PBOT_DIGEST: &str = include_str!("../tests/fixtures/import/pbot_digest.json");
+    const JSONL: &str = include_str!("../tests/fixtures/import/generic.jsonl");
+
+    fn open_in_memory() -> Database {
+        Database::open(Path::new(":memory:")).expect("open in-memory database")
+    }
+
+    fn contents(conversation: &ImportedConversation) -> Vec<(&str, &str)> {
+        conversation
+            .messages
+            .iter()
+            .map(|message| (message.role.as_str(), message.content.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn chatgpt_export_follows_the_current_branch() {
+        let batch = parse(CHATGPT, "conversations").expect("parse");
+        assert_eq!(batch.format, ImportFormat::ChatGpt);
+        assert_eq!(batch.conversations.len(), 2);
+
+        let nda = &batch.conversations[0];
+        assert_eq!(nda.title, "NDA review");
+        assert_eq!(
+            nda.source_key,
+            "chatgpt:6659a0e8-0001-4c1e-9a7e-5a1b2c3d4e5f"
+        );
+        assert_eq!(
+            contents(nda),
+            vec![
+                ("user", "Is this NDA mutual?"),
+                ("assistant", "Yes, both parties are bound."),
+                ("user", "What about clause 4 in this scan?"),
+                ("assistant", "Clause 4 limits the term to two years."),
+            ],
+            "regenerated answers, hidden system and tool messages and images are dropped"
+        );
+        assert_eq!(nda.messages[0].timestamp_ms, 1_717_164_010_250);
+
+        let regex = &batch.conversations[1];
+        assert_eq!(
+            contents(regex)[1],
+            ("assistant", "\\d{4}-\\d{2}-\\d{2}"),
+            "code content"
+        );
+        assert_eq!(
+            regex.messages[0].timestamp_ms, 1_700_000_000_000,
+            "falls back to the conversation time"
+        );
+    }
+
+    #[test]
+    fn pbot_export_keeps_usage_and_truncation() {
+        let batch = parse(PBOT_CONVERSATION, "export").expect("parse");
+        assert_eq!(batch.format, ImportFormat::Pbot);
+        let db = open_in_memory();
+        let ImportOutcome::Imported(conversation_id) =
+            import_conversation(&db, &batch.conversations[0], ConflictPolicy::Skip)
+                .expect("import")
+        else {
+            panic!("conversation should be imported");
+        };
+
+        let messages = db.load_chat_messages(conversation_id).expect("load");
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].timestamp_ms, 1_717_164_005_123);
+        assert!(messages[1].truncated, "truncation is kept");
+        assert_eq!(
+            messages[1]
+                .usage
+                .as_ref()
+                .map(|usage| usage.completion_tokens),
+            Some(34),
+            "token usage is kept"
+        );
+    }
+
+    #[test]
+    fn pbot_digest_export_fills_the_digest_once() {
+        let batch = parse(PBOT_DIGEST, "digest").expect("parse");
+        assert!(batch.conversations.is_empty());
+        let db = open_in_memory();
+        let added = import_panel_items(&db, &batch.digest_items, "digest").expect("import");
+        assert_eq!(added, 2);
+        let added = import_panel_items(&db, &batch.digest_items, "digest").expect("import");
+        assert_eq!(added, 0, "nothing new is counted");
+        assert_eq!(
+            db.load_digest_items().expect("load").len(),
+            2,
+            "re-importing adds no copies"
+        );
+    }
+
+    #[test]
+    fn jsonl_messages_become_one_conversation() {
+        let batch = parse(JSONL, "generic").expect("parse");
+        assert_eq!(batch.format, ImportFormat::Jsonl);
+        let conversation = &batch.conversations[0];
+        assert_eq!(conversation.title, "generic");
+        assert_eq!(
+            contents(conversation),
+            vec![
+                ("user", "Hi there"),
+                ("assistant", "Hello!"),
+                ("user", "What is 2+2?"),
+                ("assistant", "4"),
+                ("user", "No timestamp on this one"),
+            ]
+        );
+        let timestamps: Vec<i64> = conversation
+            .messages
+            .iter()
+            .map(|m| m.timestamp_ms)
+            .collect();
+        assert_eq!(
+            timestamps,
+            vec![
+                1_717_164_001_000,
+                1_717_164_002_500,
+                1_717_157_100_000,
+                1_717_157_101_250,
+                1_717_157_101_250
+            ],
+            "seconds, milliseconds and RFC 3339 are accepted"
+        );
+    }
+
+    #[test]
+    fn invalid_jsonl_reports_the_line() {
+        let error = parse(
+            "{\"role\": \"user\", \"content\": \"ok\"}\n{oops\n",
+            "broken",
+        )
+        .expect_err("invalid");
+        assert!(
+            matches!(error, ImportError::Json { line: Some(2), .. }),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn conflicts_follow_the_chosen_policy() {
+        let batch = parse(CHATGPT, "conversations").expect("parse");
+        let conversation = &batch.conversations[0];
+        let db = open_in_memory();
+        let conversation_count =
+            |db: &Database| db.get_conversations(true).expect("conversations").len();
+        let initial = conversation_count(&db);
+
+        let ImportOutcome::Imported(first_id) =
+            import_conversation(&db, conversation, ConflictPolicy::Skip).expect("import")
+        else {
+            panic!("first import creates a conversation");
+        };
+        assert_eq!(
+            import_conversation(&db, conversation, ConflictPolicy::Skip).expect("import"),
+            ImportOutcome::Skipped
+        );
+        let summary = crate::context_window::ContextSummary {
+            through_message_id: "old".to_owned(),
+            text: "Summary of the old messages".to_owned(),
+        };
+        db.set_context_summary(first_id, Some(&summary)).expect("summary");
+        let role_id = db.create_assistant_role("tester", "Tester", "").expect("role");
+        db.save_system_prompt(role_id, "chat", "Be brief.", "me").expect("prompt");
+        let revision_id = db.get_prompt_revision_ids_for_role(role_id).expect("ids")["chat"];
+        db.set_pinned_prompt_revision(first_id, Some(revision_id)).expect("pin");
+        assert_eq!(
+            import_conversation(&db, conversation, ConflictPolicy::Replace).expect("import"),
+            ImportOutcome::Replaced(first_id)
+        );
+        assert_eq!(db.get_context_summary(first_id).expect("summary"), None);
+        assert_eq!(db.get_pinned_prompt_revision(first_id).expect("pinned"), None);
+        assert_eq!(
+            db.load_chat_messages(first_id).expect("load").len(),
+            4,
+            "replaced, not appended"
+        );
+        assert_eq!(conversation_count(&db), initial + 1);
+
+        let ImportOutcome::Imported(copy_id) =
+            import_conversation(&db, conversation, ConflictPolicy::KeepBoth).expect("import")
+        else {
+            panic!("keeping both creates a conversation");
+        };
+        assert_ne!(copy_id, first_id);
+        assert_eq!(conversation_count(&db), initial + 2);
+    }
+}
@@ -2,8 +2,31 @@
 
 mod app;
 mod chat_panel;
+pub mod cli;
 mod color_test;
+mod context_window;
+mod conversation_panel;
+mod credentials;
 mod database;
 mod digest_panel;
+mod export;
+mod generation_params;
+mod import;
+mod llm_provider;
 mod long_mem_panel;
+mod memory_context;
+mod migrations;
+mod model_discovery;
+mod network_log;
+mod pdf;
+mod profiles;
+mod role_editor;
+mod search;
+mod streaming;
+mod timestamps;
+mod usage;
+mod workspace;
 pub use app::TemplateApp;
+
+/// Title of the window; eframe also keeps the app's saved state under this name.
+pub const APP_NAME: &str = "Pbot";
@@ -0,0 +1,798 @@
+use crate::streaming::{StreamErrorKind, StreamEvent};
+
+/// API used when `LLM_BASE_URL` is not set.
+pub const DEFAULT_BASE_URL: &str = "https://api.deepseek.com";
+
+/// Model used when `LLM_MODEL` is not set.
+pub const DEFAULT_MODEL: &str = "deepseek-chat";
+
+/// Sampling settings; unset ones are left to the server's defaults.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct GenerationParams {
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f64>,
+    /// Empty entries are ignored, so an editor can keep a trailing blank line.
+    pub stop: Vec<String>,
+    pub presence_penalty: Option<f64>,
+    pub frequency_penalty: Option<f64>,
+    pub seed: Option<i64>,
+    pub response_format: Option<ResponseFormat>,
+}
+
+impl GenerationParams {
+    /// What every request used before parameters could be configured.
+    pub const FALLBACK: Self = Self {
+        temperature: Some(0.3),
+        max_tokens: None,
+        top_p: None,
+        stop: Vec::new(),
+        presence_penalty: None,
+        frequency_penalty: None,
+        seed: None,
+        response_format: None,
+    };
+
+    /// These parameters, with the unset ones taken from `fallback`.
+    #[must_use]
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            temperature: self.temperature.or(fallback.temperature),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            top_p: self.top_p.or(fallback.top_p),
+            stop: if self.stop_sequences().next().is_some() {
+                self.stop
+            } else {
+                fallback.stop
+            },
+            presence_penalty: self.presence_penalty.or(fallback.presence_penalty),
+            frequency_penalty: self.frequency_penalty.or(fallback.frequency_penalty),
+            seed: self.seed.or(fallback.seed),
+            response_format: self.response_format.or(fallback.response_format),
+        }
+    }
+
+    /// Whether nothing is set, i.e. everything is inherited.
+    pub fn is_unset(&self) -> bool {
+        self.stop_sequences().next().is_none()
+            && Self {
+                stop: Vec::new(),
+                ..self.clone()
+            } == Self::default()
+    }
+
+    /// The non-empty stop sequences.
+    pub fn stop_sequences(&self) -> impl Iterator<Item = &str> {
+        self.stop
+            .iter()
+            .map(String::as_str)
+            .filter(|stop| !stop.is_empty())
+    }
+}
+
+/// Output format the model is asked for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+}
+
+impl ResponseFormat {
+    pub const ALL: [Self; 2] = [Self::Text, Self::JsonObject];
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::Text => "Text",
+            Self::JsonObject => "JSON object",
+        }
+    }
+}
+
+/// A chat completion request, independent of any vendor's wire format.
+pub struct ChatRequest {
+    pub model: String,
+    pub system_prompts: Vec<String>,
+    pub messages: Vec<(String, String)>, // (role, content), roles are "user" or "assistant"
+    pub params: GenerationParams,
+}
+
+/// How the API key is sent.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum AuthStyle {
+    /// Whatever the API style expects: `Bearer` or `x-api-key`.
+    #[default]
+    ProviderDefault,
+    Bearer,
+    /// `api-key: <key>`, as Azure `OpenAI` expects.
+    ApiKeyHeader,
+    XApiKey,
+    /// No key at all, e.g. for a local server.
+    None,
+}
+
+impl AuthStyle {
+    pub const ALL: [Self; 5] = [
+        Self::ProviderDefault,
+        Self::Bearer,
+        Self::ApiKeyHeader,
+        Self::XApiKey,
+        Self::None,
+    ];
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::ProviderDefault => "API style default",
+            Self::Bearer => "Authorization: Bearer",
+            Self::ApiKeyHeader => "api-key header",
+            Self::XApiKey => "x-api-key header",
+            Self::None => "None",
+        }
+    }
+
+    fn apply(self, builder: reqwest::RequestBuilder, api_key: &str) -> reqwest::RequestBuilder {
+        match self {
+            Self::ProviderDefault | Self::None => builder,
+            Self::Bearer => builder.header("Authorization", format!("Bearer {api_key}")),
+            Self::ApiKeyHeader => builder.header("api-key", api_key),
+            Self::XApiKey => builder.header("x-api-key", api_key),
+        }
+    }
+}
+
+/// Everything needed to reach an API, as configured by a provider profile.
+#[derive(Clone)]
+pub struct Connection {
+    pub provider_kind: ProviderKind,
+    pub base_url: String,
+    pub api_key: String,
+    pub auth_style: AuthStyle,
+    /// Sent with every request, after the auth header so they can replace it.
+    pub headers: Vec<(String, String)>,
+    pub connect_timeout_secs: u64,
+    /// Longest wait for the next bytes of a response, `0` for no limit.
+    pub read_timeout_secs: u64,
+    /// Whether `OpenAI`-style requests ask for token usage with `stream_options`,
+    /// which some compatible servers reject.
+    pub stream_usage: bool,
+}
+
+impl Connection {
+    pub fn provider(&self) -> Box<dyn LlmProvider> {
+        match self.provider_kind {
+            ProviderKind::OpenAiCompatible => Box::new(OpenAiCompatibleProvider {
+                stream_usage: self.stream_usage,
+            }),
+            ProviderKind::AnthropicMessages => Box::new(AnthropicMessagesProvider),
+        }
+    }
+
+    /// The HTTP request for `request`, with auth and extra headers applied.
+    pub fn build_request(
+        &self,
+        client: &reqwest::Client,
+        provider: &dyn LlmProvider,
+        request: &ChatRequest,
+    ) -> reqwest::RequestBuilder {
+        self.authorize(
+            provider,
+            provider.build_request(client, &self.base_url, request),
+        )
+    }
+
+    /// The HTTP request listing the models the server offers.
+    pub fn build_models_request(
+        &self,
+        client: &reqwest::Client,
+        provider: &dyn LlmProvider,
+    ) -> reqwest::RequestBuilder {
+        self.authorize(
+            provider,
+            provider.build_models_request(client, &self.base_url),
+        )
+    }
+
+    fn authorize(
+        &self,
+        provider: &dyn LlmProvider,
+        builder: reqwest::RequestBuilder,
+    ) -> reqwest::RequestBuilder {
+        let auth_style = match self.auth_style {
+            AuthStyle::ProviderDefault => provider.default_auth(),
+            auth_style => auth_style,
+        };
+        let mut builder = auth_style.apply(builder, &self.api_key);
+        for (name, value) in &self.headers {
+            if !name.trim().is_empty() {
+                builder = builder.header(name.trim(), value);
+            }
+        }
+        builder
+    }
+
+    pub fn client(&self) -> reqwest::Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder().connect_timeout(
+            std::time::Duration::from_secs(self.connect_timeout_secs.max(1)),
+        );
+        if self.read_timeout_secs > 0 {
+            builder = builder.read_timeout(std::time::Duration::from_secs(self.read_timeout_secs));
+        }
+        builder.build()
+    }
+}
+
+/// Knows how to talk to one family of chat completion APIs.
+pub trait LlmProvider: Send + Sync {
+    /// Build the streaming HTTP request with URL, headers and body; auth is added by [`Connection`].
+    fn build_request(
+        &self,
+        client: &reqwest::Client,
+        base_url: &str,
+        request: &ChatRequest,
+    ) -> reqwest::RequestBuilder;
+
+    /// How this API expects the key when the profile doesn't say otherwise.
+    fn default_auth(&self) -> AuthStyle;
+
+    /// Build the request listing the available models; auth is added by [`Connection`].
+    fn build_models_request(
+        &self,
+        client: &reqwest::Client,
+        base_url: &str,
+    ) -> reqwest::RequestBuilder {
+        client.get(format!("{base_url}/models"))
+    }
+
+    /// Interpret one server-sent event given its `event:` name (if any) and `data:` payload.
+    ///
+    /// Keep-alives and events we don't care about produce no stream events.
+    fn parse_stream_chunk(&self, event: Option<&str>, data: &str) -> Vec<StreamEvent>;
+
+    /// Extract the readable error message from a failed HTTP response body.
+    fn parse_error(&self, body: &str) -> String {
+        serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|json| json["error"]["message"].as_str().map(str::to_owned))
+            .unwrap_or_else(|| body.to_owned())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub enum ProviderKind {
+    OpenAiCompatible,
+    AnthropicMessages,
+}
+
+impl ProviderKind {
+    pub const ALL: [Self; 2] = [Self::OpenAiCompatible, Self::AnthropicMessages];
+
+    /// Read the provider from `LLM_PROVIDER` ("openai" or "anthropic"), defaulting to `OpenAiCompatible`.
+    pub fn from_env() -> Self {
+        match std::env::var("LLM_PROVIDER").as_deref() {
+            Ok("anthropic") => Self::AnthropicMessages,
+            _ => Self::OpenAiCompatible,
+        }
+    }
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::OpenAiCompatible => "OpenAI-compatible (/chat/completions)",
+            Self::AnthropicMessages => "Messages API (/messages)",
+        }
+    }
+}
+
+/// `/chat/completions` with `Bearer` auth, the de facto standard of most vendors.
+pub struct OpenAiCompatibleProvider {
+    /// Ask for a final chunk with token usage.
+    pub stream_usage: bool,
+}
+
+impl LlmProvider for OpenAiCompatibleProvider {
+    fn build_request(
+        &self,
+        client: &reqwest::Client,
+        base_url: &str,
+        request: &ChatRequest,
+    ) -> reqwest::RequestBuilder {
+        let mut api_messages = Vec::new();
+
+        for system_prompt in &request.system_prompts {
+            api_messages.push(serde_json::json!({
+                "role": "system",
+                "content": system_prompt
+            }));
+        }
+
+        for (role, content) in &request.messages {
+            api_messages.push(serde_json::json!({
+                "role": role,
+                "content": content
+            }));
+        }
+
+        let mut payload = serde_json::json!({
+            "model": request.model,
+            "messages": api_messages,
+            "stream": true
+        });
+        if self.stream_usage {
+            payload["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+        if let Some(temperature) = request.params.temperature {
+            payload["temperature"] = temperature.into();
+        }
+        if let Some(max_tokens) = request.params.max_tokens {
+            payload["max_tokens"] = max_tokens.into();
+        }
+        if let Some(top_p) = request.params.top_p {
+            payload["top_p"] = top_p.into();
+        }
+        let stop: Vec<&str> = request.params.stop_sequences().collect();
+        if !stop.is_empty() {
+            payload["stop"] = stop.into();
+        }
+        if let Some(presence_penalty) = request.params.presence_penalty {
+            payload["presence_penalty"] = presence_penalty.into();
+        }
+        if let Some(frequency_penalty) = request.params.frequency_penalty {
+            payload["frequency_penalty"] = frequency_penalty.into();
+        }
+        if let Some(seed) = request.params.seed {
+            payload["seed"] = seed.into();
+        }
+        if let Some(format) = request.params.response_format {
+            let format = match format {
+                ResponseFormat::Text => "text",
+                ResponseFormat::JsonObject => "json_object",
+            };
+            payload["response_format"] = serde_json::json!({ "type": format });
+        }
+
+        client
+            .post(format!("{base_url}/chat/completions"))
+            .header("Content-Type", "application/json")
+            .json(&payload)
+    }
+
+    fn default_auth(&self) -> AuthStyle {
+        AuthStyle::Bearer
+    }
+
+    fn parse_stream_chunk(&self, _event: Option<&str>, data: &str) -> Vec<StreamEvent> {
+        if data == "[DONE]" {
+            return vec![StreamEvent::Done];
+        }
+
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(data) else {
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        if let Some(error) = json.get("error") {
+            events.push(StreamEvent::Error {
+                kind: StreamErrorKind::Stream,
+                status: None,
+                body: error["message"].as_str().unwrap_or(data).to_owned(),
+            });
+            return events;
+        }
+
+        let choice = &json["choices"][0];
+        if let Some(content) = choice["delta"]["content"].as_str() {
+            if !content.is_empty() {
+                events.push(StreamEvent::Delta(content.to_owned()));
+            }
+        }
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            events.push(StreamEvent::FinishReason(reason.to_owned()));
+        }
+        if let (Some(prompt_tokens), Some(completion_tokens)) = (
+            json["usage"]["prompt_tokens"].as_u64(),
+            json["usage"]["completion_tokens"].as_u64(),
+        ) {
+            events.push(StreamEvent::Usage {
+                prompt_tokens,
+                completion_tokens,
+            });
+        }
+        events
+    }
+}
+
+/// Messages API with `x-api-key` auth and the system prompt as a top-level field.
+pub struct AnthropicMessagesProvider;
+
+impl AnthropicMessagesProvider {
+    const API_VERSION: &'static str = "2023-06-01";
+
+    /// The Messages API requires an explicit output limit.
+    const DEFAULT_MAX_TOKENS: u32 = 4096;
+}
+
+impl LlmProvider for AnthropicMessagesProvider {
+    fn build_request(
+        &self,
+        client: &reqwest::Client,
+        base_url: &str,
+        request: &ChatRequest,
+    ) -> reqwest::RequestBuilder {
+        let api_messages: Vec<serde_json::Value> = request
+            .messages
+            .iter()
+            .map(|(role, content)| {
+                serde_json::json!({
+                    "role": role,
+                    "content": content
+                })
+            })
+            .collect();
+
+        let mut payload = serde_json::json!({
+            "model": request.model,
+            "messages": api_messages,
+            "max_tokens": request.params.max_tokens.unwrap_or(Self::DEFAULT_MAX_TOKENS),
+            "stream": true
+        });
+        if let Some(temperature) = request.params.temperature {
+            payload["temperature"] = temperature.into();
+        }
+        // The Messages API has no penalties, seed or response format; those are dropped.
+        if let Some(top_p) = request.params.top_p {
+            payload["top_p"] = top_p.into();
+        }
+        let stop: Vec<&str> = request.params.stop_sequences().collect();
+        if !stop.is_empty() {
+            payload["stop_sequences"] = stop.into();
+        }
+        if !request.system_prompts.is_empty() {
+            payload["system"] = serde_json::Value::String(request.system_prompts.join("\n\n"));
+        }
+
+        client
+            .post(format!("{base_url}/messages"))
+            .header("anthropic-version", Self::API_VERSION)
+            .header("Content-Type", "application/json")
+            .json(&payload)
+    }
+
+    fn default_auth(&self) -> AuthStyle {
+        AuthStyle::XApiKey
+    }
+
+    fn build_models_request(
+        &self,
+        client: &reqwest::Client,
+        base_url: &str,
+    ) -> reqwest::RequestBuilder {
+        client
+            .get(format!("{base_url}/models"))
+            .header("anthropic-version", Self::API_VERSION)
+    }
+
+    fn parse_stream_chunk(&self, event: Option<&str>, data: &str) -> Vec<StreamEvent> {
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(data) else {
+            return Vec::new();
+        };
+
+        // The event name is repeated as `type` inside the payload
+        let event = event.or_else(|| json["type"].as_str());
+        match event {
+            Some("content_block_delta") => json["delta"]["text"]
+                .as_str()
+                .map(|text| vec![StreamEvent::Delta(text.to_owned())])
+                .unwrap_or_default(),
+            Some("message_start") => {
+                // Input tokens arrive up front; output tokens follow in `message_delta`
+                let usage = &json["message"]["usage"];
+                match usage["input_tokens"].as_u64() {
+                    Some(prompt_tokens) => vec![StreamEvent::Usage {
+                        prompt_tokens,
+                        completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+                    }],
+                    None => Vec::new(),
+                }
+            }
+            Some("message_delta") => {
+                let mut events = Vec::new();
+                if let Some(reason) = json["delta"]["stop_reason"].as_str() {
+                    events.push(StreamEvent::FinishReason(reason.to_owned()));
+                }
+                if let Some(completion_tokens) = json["usage"]["output_tokens"].as_u64() {
+                    events.push(StreamEvent::Usage {
+                        prompt_tokens: json["usage"]["input_tokens"].as_u64().unwrap_or(0),
+                        completion_tokens,
+                    });
+                }
+                events
+            }
+            Some("message_stop") => vec![StreamEvent::Done],
+            Some("error") => vec![StreamEvent::Error {
+                kind: StreamErrorKind::Stream,
+                status: None,
+                body: json["error"]["message"].as_str().unwrap_or(data).to_owned(),
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::SseParser;
+
+    /// A recorded `/chat/completions` stream: two deltas, the finish reason, and
+    /// the usage chunk `stream_options` asks for, which has no choices.
+    const OPENAI_STREAM: &str = "\
+data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\n\
+data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"The NDA \"}}]}\n\n\
+data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"is mutual.\"}}]}\n\n\
+data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"length\"}]}\n\n\
+data: {\"id\":\"c1\",\"choices\":[],\"usage\":{\"prompt_tokens\":42,\"completion_tokens\":7,\"total_tokens\":49}}\n\n\
+data: [DONE]\n\n";
+
+    /// A recorded Messages API stream, with input tokens up front and output
+    /// tokens at the end.
+    const MESSAGES_STREAM: &str = "\
+event: message_start\n\
+data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"role\":\"assistant\",\"content\":[],\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n\
+event: content_block_start\n\
+data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n\
+event: ping\n\
+data: {\"type\":\"ping\"}\n\n\
+event: content_block_delta\n\
+data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"The NDA \"}}\n\n\
+event: content_block_delta\n\
+data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"is mutual.\"}}\n\n\
+event: content_block_stop\n\
+data: {\"type\":\"content_block_stop\",\"index\":0}\n\n\
+event: message_delta\n\
+data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"max_tokens\"},\"usage\":{\"output_tokens\":15}}\n\n\
+event: message_stop\n\
+data: {\"type\":\"message_stop\"}\n\n";
+
+    fn chat_request(params: GenerationParams) -> ChatRequest {
+        ChatRequest {
+            model: "test-model".to_owned(),
+            system_prompts: vec!["You are a lawyer.".to_owned(), "Relevant memory".to_owned()],
+            messages: vec![
+                ("user".to_owned(), "Is the NDA mutual?".to_owned()),
+                ("assistant".to_owned(), "Which one?".to_owned()),
+                ("user".to_owned(), "The pilot NDA.".to_owned()),
+            ],
+            params,
+        }
+    }
+
+    fn connection(provider_kind: ProviderKind) -> Connection {
+        Connection {
+            provider_kind,
+            base_url: "https://api.example.com/v1".to_owned(),
+            api_key: "sk-test".to_owned(),
+            auth_style: AuthStyle::ProviderDefault,
+            headers: Vec::new(),
+            connect_timeout_secs: 5,
+            read_timeout_secs: 5,
+            stream_usage: true,
+        }
+    }
+
+    fn build(connection: &Connection, request: &ChatRequest) -> reqwest::Request {
+        connection
+            .build_request(
+                &reqwest::Client::new(),
+                connection.provider().as_ref(),
+                request,
+            )
+            .build()
+            .expect("request")
+    }
+
+    fn json_body(request: &reqwest::Request) -> serde_json::Value {
+        let body = request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .expect("body");
+        serde_json::from_slice(body).expect("JSON body")
+    }
+
+    fn header<'a>(request: &'a reqwest::Request, name: &str) -> Option<&'a str> {
+        request
+            .headers()
+            .get(name)
+            .map(|value| value.to_str().expect("ASCII header"))
+    }
+
+    fn parse_recorded(provider: &dyn LlmProvider, stream: &str) -> Vec<StreamEvent> {
+        let mut parser = SseParser::default();
+        let mut events = parser.feed(stream.as_bytes());
+        events.extend(parser.finish());
+        events
+            .iter()
+            .flat_map(|event| provider.parse_stream_chunk(event.event.as_deref(), &event.data))
+            .collect()
+    }
+
+    #[test]
+    fn openai_request_sends_system_prompts_as_messages() {
+        let params = GenerationParams {
+            temperature: Some(0.2),
+            max_tokens: Some(256),
+            stop: vec!["###".to_owned()],
+            seed: Some(7),
+            frequency_penalty: Some(0.5),
+            response_format: Some(ResponseFormat::JsonObject),
+            ..GenerationParams::default()
+        };
+        let request = build(
+            &connection(ProviderKind::OpenAiCompatible),
+            &chat_request(params),
+        );
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.example.com/v1/chat/completions"
+        );
+        assert_eq!(header(&request, "authorization"), Some("Bearer sk-test"));
+        assert_eq!(header(&request, "x-api-key"), None);
+
+        let body = json_body(&request);
+        let roles: Vec<&str> = body["messages"]
+            .as_array()
+            .expect("messages")
+            .iter()
+            .map(|message| message["role"].as_str().expect("role"))
+            .collect();
+        assert_eq!(roles, ["system", "system", "user", "assistant", "user"]);
+        assert_eq!(body["messages"][0]["content"], "You are a lawyer.");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["temperature"], 0.2);
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["stop"], serde_json::json!(["###"]));
+        assert_eq!(body["seed"], 7);
+        assert_eq!(body["frequency_penalty"], 0.5);
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert!(body.get("top_p").is_none(), "unset parameters are left out");
+    }
+
+    #[test]
+    fn messages_request_puts_system_prompts_top_level_and_always_sets_max_tokens() {
+        let params = GenerationParams {
+            stop: vec!["###".to_owned()],
+            seed: Some(7),
+            presence_penalty: Some(0.5),
+            ..GenerationParams::default()
+        };
+        let request = build(
+            &connection(ProviderKind::AnthropicMessages),
+            &chat_request(params),
+        );
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.example.com/v1/messages"
+        );
+        assert_eq!(header(&request, "x-api-key"), Some("sk-test"));
+        assert_eq!(header(&request, "anthropic-version"), Some("2023-06-01"));
+        assert_eq!(header(&request, "authorization"), None);
+
+        let body = json_body(&request);
+        assert_eq!(body["system"], "You are a lawyer.\n\nRelevant memory");
+        let roles: Vec<&str> = body["messages"]
+            .as_array()
+            .expect("messages")
+            .iter()
+            .map(|message| message["role"].as_str().expect("role"))
+            .collect();
+        assert_eq!(roles, ["user", "assistant", "user"], "no system messages");
+        assert_eq!(body["max_tokens"], 4096, "required even when unset");
+        assert_eq!(body["stop_sequences"], serde_json::json!(["###"]));
+        assert!(body.get("seed").is_none() && body.get("presence_penalty").is_none());
+        assert!(body.get("stream_options").is_none());
+
+        let mut without_system = chat_request(GenerationParams {
+            max_tokens: Some(100),
+            ..GenerationParams::default()
+        });
+        without_system.system_prompts.clear();
+        let body = json_body(&build(
+            &connection(ProviderKind::AnthropicMessages),
+            &without_system,
+        ));
+        assert_eq!(body["max_tokens"], 100);
+        assert!(body.get("system").is_none());
+    }
+
+    #[test]
+    fn stream_usage_is_only_requested_when_the_profile_allows_it() {
+        let mut connection = connection(ProviderKind::OpenAiCompatible);
+        let with_usage = json_body(&build(
+            &connection,
+            &chat_request(GenerationParams::default()),
+        ));
+        assert_eq!(with_usage["stream_options"]["include_usage"], true);
+
+        connection.stream_usage = false;
+        let without_usage = json_body(&build(
+            &connection,
+            &chat_request(GenerationParams::default()),
+        ));
+        assert!(
+            without_usage.get("stream_options").is_none(),
+            "{without_usage}"
+        );
+        assert_eq!(without_usage["stream"], true);
+    }
+
+    #[test]
+    fn openai_recorded_stream_is_parsed() {
+        let provider = OpenAiCompatibleProvider { stream_usage: true };
+        assert_eq!(
+            parse_recorded(&provider, OPENAI_STREAM),
+            vec![
+                StreamEvent::Delta("The NDA ".to_owned()),
+                StreamEvent::Delta("is mutual.".to_owned()),
+                StreamEvent::FinishReason("length".to_owned()),
+                StreamEvent::Usage {
+                    prompt_tokens: 42,
+                    completion_tokens: 7,
+                },
+                StreamEvent::Done,
+            ]
+        );
+
+        let error =
+            "data: {\"error\":{\"message\":\"Rate limit reached\",\"type\":\"requests\"}}\n\n";
+        assert_eq!(
+            parse_recorded(&provider, error),
+            vec![StreamEvent::Error {
+                kind: StreamErrorKind::Stream,
+                status: None,
+                body: "Rate limit reached".to_owned(),
+            }]
+        );
+        assert_eq!(
+            provider.parse_error(r#"{"error":{"message":"Invalid model"}}"#),
+            "Invalid model"
+        );
+        assert_eq!(provider.parse_error("Bad Gateway"), "Bad Gateway");
+    }
+
+    #[test]
+    fn messages_recorded_stream_is_parsed() {
+        assert_eq!(
+            parse_recorded(&AnthropicMessagesProvider, MESSAGES_STREAM),
+            vec![
+                StreamEvent::Usage {
+                    prompt_tokens: 25,
+                    completion_tokens: 1,
+                },
+                StreamEvent::Delta("The NDA ".to_owned()),
+                StreamEvent::Delta("is mutual.".to_owned()),
+                StreamEvent::FinishReason("max_tokens".to_owned()),
+                StreamEvent::Usage {
+                    prompt_tokens: 0,
+                    completion_tokens: 15,
+                },
+                StreamEvent::Done,
+            ]
+        );
+
+        let error = "event: error\n\
+data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n";
+        assert_eq!(
+            parse_recorded(&AnthropicMessagesProvider, error),
+            vec![StreamEvent::Error {
+                kind: StreamErrorKind::Stream,
+                status: None,
+                body: "Overloaded".to_owned(),
+            }]
+        );
+        // Without `event:` lines the type inside the payload is used
+        assert_eq!(
+            AnthropicMessagesProvider.parse_stream_chunk(None, r#"{"type":"message_stop"}"#),
+            vec![StreamEvent::Done]
+        );
+    }
+}
@@ -73,6 +73,9 @@ impl TemplateApp {
                                 ui.colored_label(egui::Color32::GRAY, "No items match your search.");
                             } else {
                                 for i in filtered_indices {
+                                    let item_id = self.long_term_memory_items[i].id.clone();
+                                    self.scroll_to_search_hit(ui, &item_id);
+
                                     // Check selection state first
                                     let is_selected = self.long_term_memory_items[i].selected;
 
@@ -91,7 +94,16 @@ impl TemplateApp {
                                                         if self.long_term_memory_items[i].source == "user" { egui::Color32::DARK_RED } else { egui::Color32::DARK_GREEN },
                                                         format!("{source_label}:")
                                                     );
-                                                    ui.label(&self.long_term_memory_items[i].timestamp);
+                                                    ui.colored_label(
+                                                        egui::Color32::GRAY,
+                                                        crate::timestamps::format_relative(
+                                                            self.long_term_memory_items[i].timestamp_ms,
+                                                            chrono::Local::now(),
+                                                        ),
+                                                    )
+                                                    .on_hover_text(crate::timestamps::format_full(
+                                                        self.long_term_memory_items[i].timestamp_ms,
+                                                    ));
                                                 });
 
                                                 // Content
@@ -129,7 +141,16 @@ impl TemplateApp {
                                                 if self.long_term_memory_items[i].source == "user" { egui::Color32::DARK_RED } else { egui::Color32::DARK_GREEN },
                                                 format!("{source_label}:")
                                             );
-                                            ui.label(&self.long_term_memory_items[i].timestamp);
+                                            ui.colored_label(
+                                                egui::Color32::GRAY,
+                                                crate::timestamps::format_relative(
+                                                    self.long_term_memory_items[i].timestamp_ms,
+                                                    chrono::Local::now(),
+                                                ),
+                                            )
+                                            .on_hover_text(crate::timestamps::format_full(
+                                                self.long_term_memory_items[i].timestamp_ms,
+                                            ));
                                         });
 
                                         // Content
@@ -166,7 +187,7 @@ impl TemplateApp {
                     });
 
                 if let Some(index) = item_to_delete {
-                    self.long_term_memory_items.remove(index);
+                    self.delete_long_term_memory_item(index);
                 }
 
                 ui.add_space(-30.0);
@@ -4,9 +4,15 @@
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 #[tokio::main]
-async fn main() -> eframe::Result {
+async fn main() -> std::process::ExitCode {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    // `pbot ask ...` and friends run without a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if eframe_template::cli::is_cli_invocation(&args) {
+        return eframe_template::cli::run(&args).await;
+    }
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1200.0, 800.0])
@@ -18,11 +24,17 @@ async fn main() -> eframe::Result {
             ),
         ..Default::default()
     };
-    eframe::run_native(
-        "Pbot",
+    match eframe::run_native(
+        eframe_template::APP_NAME,
         native_options,
         Box::new(|cc| Ok(Box::new(eframe_template::TemplateApp::new(cc)))),
-    )
+    ) {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            log::error!("Failed to start the window: {e}");
+            std::process::ExitCode::FAILURE
+        }
+    }
 }
 
 // When compiling to web using trunk:
@@ -0,0 +1,187 @@
+use crate::app::LongTermMemoryItem;
+
+/// How many matching memory items accompany a chat request unless configured otherwise.
+pub const DEFAULT_MEMORY_CONTEXT_LIMIT: usize = 5;
+
+/// Pick the long-term memory items that should accompany a chat request.
+///
+/// Items the user has selected in the memory panel always win. When nothing is
+/// selected, the `limit` items sharing the most terms with `query` are used.
+pub fn select_memory_items<'a>(
+    items: &'a [LongTermMemoryItem],
+    query: &str,
+    limit: usize,
+) -> Vec<&'a LongTermMemoryItem> {
+    let selected: Vec<&LongTermMemoryItem> = items.iter().filter(|item| item.selected).collect();
+    if !selected.is_empty() {
+        return selected;
+    }
+
+    let query_terms = extract_terms(query);
+    if query_terms.is_empty() {
+        return Vec::new();
+    }
+
+    let mut scored: Vec<(usize, usize, &LongTermMemoryItem)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let item_terms = extract_terms(&item.content);
+            let score = query_terms
+                .iter()
+                .filter(|term| item_terms.binary_search(term).is_ok())
+                .count();
+            (score > 0).then_some((score, index, item))
+        })
+        .collect();
+
+    // Highest score first; newer items win ties
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, _, item)| item)
+        .collect()
+}
+
+/// Build the system message that carries memory into the conversation.
+pub fn build_memory_prompt(
+    memory_prompt: Option<&str>,
+    items: &[&LongTermMemoryItem],
+) -> Option<String> {
+    if items.is_empty() {
+        return None;
+    }
+
+    let mut prompt = String::new();
+    if let Some(memory_prompt) = memory_prompt {
+        prompt.push_str(memory_prompt);
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Relevant long-term memory:\n\n");
+    for (i, item) in items.iter().enumerate() {
+        let num = i + 1;
+        let content = &item.content;
+        prompt.push_str(&format!("{num}. {content}\n\n"));
+    }
+    Some(prompt)
+}
+
+/// Lowercased words for alphabetic scripts, character bigrams for CJK text
+/// (which has no spaces between words).
+fn extract_terms(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    let flush_cjk = |run: &mut Vec<char>, terms: &mut Vec<String>| {
+        if run.len() == 1 {
+            terms.push(run[0].to_string());
+        }
+        for pair in run.windows(2) {
+            terms.push(pair.iter().collect());
+        }
+        run.clear();
+    };
+
+    for c in text.chars() {
+        // Full-width punctuation separates terms like any other punctuation
+        if is_cjk(c) && c.is_alphanumeric() {
+            if word.chars().count() >= 2 {
+                terms.push(std::mem::take(&mut word));
+            }
+            word.clear();
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk_run, &mut terms);
+            word.extend(c.to_lowercase());
+        } else {
+            flush_cjk(&mut cjk_run, &mut terms);
+            if word.chars().count() >= 2 {
+                terms.push(std::mem::take(&mut word));
+            }
+            word.clear();
+        }
+    }
+    flush_cjk(&mut cjk_run, &mut terms);
+    if word.chars().count() >= 2 {
+        terms.push(word);
+    }
+
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// Characters of scripts written without spaces between words, and their
+/// full-width punctuation and letters.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF // Hiragana and Katakana
+        | 0x3400..=0x4DBF // CJK Extension A
+        | 0x4E00..=0x9FFF // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF // Hangul syllables
+        | 0xF900..=0xFAFF // CJK Compatibility Ideographs
+        | 0xFF00..=0xFFEF) // Full-width forms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, content: &str, selected: bool) -> LongTermMemoryItem {
+        LongTermMemoryItem {
+            id: id.to_owned(),
+            content: content.to_owned(),
+            source: "user".to_owned(),
+            timestamp_ms: 0,
+            selected,
+        }
+    }
+
+    fn ids(items: &[&LongTermMemoryItem]) -> Vec<String> {
+        items.iter().map(|item| item.id.clone()).collect()
+    }
+
+    #[test]
+    fn pinned_items_are_sent_and_unpinned_ones_left_out() {
+        let items = [
+            item("nda", "Client prefers the mutual NDA template", false),
+            item("billing", "Invoices go to the finance team", true),
+            item("venue", "Disputes are heard in Zurich", true),
+        ];
+
+        let picked =
+            select_memory_items(&items, "Which NDA template?", DEFAULT_MEMORY_CONTEXT_LIMIT);
+        assert_eq!(
+            ids(&picked),
+            ["billing", "venue"],
+            "the relevant but unpinned item must not be sent"
+        );
+
+        let prompt = build_memory_prompt(Some("Remember:"), &picked).expect("prompt");
+        assert!(prompt.starts_with("Remember:\n\n"));
+        assert!(prompt.contains("1. Invoices go to the finance team"));
+        assert!(prompt.contains("2. Disputes are heard in Zurich"));
+        assert!(!prompt.contains("NDA"));
+    }
+
+    #[test]
+    fn without_pinned_items_the_most_relevant_are_picked() {
+        let items = [
+            item("old", "NDA template for pilots", false),
+            item("billing", "Invoices go to the finance team", false),
+            item("new", "Mutual NDA template for pilots", false),
+            item("cjk", "秘密保持契約のテンプレート", false),
+        ];
+
+        let picked = select_memory_items(&items, "NDA template for a mutual pilot", 2);
+        assert_eq!(ids(&picked), ["new", "old"], "most shared terms first");
+        assert_eq!(
+            ids(&select_memory_items(&items, "秘密保持契約？", 5)),
+            ["cjk"]
+        );
+        assert!(select_memory_items(&items, "?!", 5).is_empty());
+        assert_eq!(build_memory_prompt(None, &[]), None);
+    }
+}
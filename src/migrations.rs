@@ -0,0 +1,657 @@
+use rusqlite::{Connection, Result as SqliteResult, Transaction};
+
+/// One step of the database schema history.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    apply: fn(&Transaction<'_>) -> SqliteResult<()>,
+}
+
+/// Every schema change, oldest first. Versions are stored in `PRAGMA user_version`.
+///
+/// Databases written before versioning existed all report version 0 whatever
+/// their actual shape, so migrations up to version 6 must tolerate tables and
+/// columns that are already there. Later migrations only ever see the schema
+/// left behind by their predecessor.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "content, panel, role and prompt tables",
+        apply: create_base_tables,
+    },
+    Migration {
+        version: 2,
+        description: "conversations",
+        apply: add_conversations,
+    },
+    Migration {
+        version: 3,
+        description: "truncated responses",
+        apply: add_truncated_flag,
+    },
+    Migration {
+        version: 4,
+        description: "token usage",
+        apply: add_token_usage,
+    },
+    Migration {
+        version: 5,
+        description: "rolling context summaries",
+        apply: add_context_summary,
+    },
+    Migration {
+        version: 6,
+        description: "system prompt revisions",
+        apply: add_prompt_revisions,
+    },
+    Migration {
+        version: 7,
+        description: "full-text search index",
+        apply: add_content_search_index,
+    },
+    Migration {
+        version: 8,
+        description: "millisecond timestamps",
+        apply: add_millisecond_timestamps,
+    },
+    Migration {
+        version: 9,
+        description: "digest and memory content hashes",
+        apply: add_content_hashes,
+    },
+    Migration {
+        version: 10,
+        description: "imported conversations",
+        apply: add_conversation_imports,
+    },
+    Migration {
+        version: 11,
+        description: "provider profiles",
+        apply: add_provider_profiles,
+    },
+    Migration {
+        version: 12,
+        description: "per-role generation parameters",
+        apply: add_role_generation_params,
+    },
+    Migration {
+        version: 13,
+        description: "cached model lists",
+        apply: add_model_lists,
+    },
+    Migration {
+        version: 14,
+        description: "network log",
+        apply: add_network_log,
+    },
+    Migration {
+        version: 15,
+        description: "profile credential ids",
+        apply: add_profile_credential_ids,
+    },
+];
+
+/// Schema version this binary writes.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// Why a database could not be opened.
+#[derive(Debug)]
+pub enum DatabaseError {
+    Sqlite(rusqlite::Error),
+    /// The file was written by a newer version of the app.
+    NewerSchema {
+        found: i64,
+        supported: i64,
+    },
+}
+
+impl std::fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sqlite(e) => write!(f, "{e}"),
+            Self::NewerSchema { found, supported } => write!(
+                f,
+                "database schema version {found} is newer than the supported version {supported}; please update the app"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+impl From<rusqlite::Error> for DatabaseError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Sqlite(e)
+    }
+}
+
+pub fn schema_version(conn: &Connection) -> SqliteResult<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Bring the schema up to `SCHEMA_VERSION`, each migration in its own transaction.
+pub fn run(conn: &mut Connection) -> Result<(), DatabaseError> {
+    let current = schema_version(conn)?;
+    if current > SCHEMA_VERSION {
+        return Err(DatabaseError::NewerSchema {
+            found: current,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        log::info!(
+            "Migrating database to version {}: {}",
+            migration.version,
+            migration.description
+        );
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> SqliteResult<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(1))?;
+
+    for existing in columns {
+        if existing? == column {
+            return Ok(());
+        }
+    }
+
+    conn.execute(
+        &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_base_tables(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS content_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            content TEXT NOT NULL,
+            role_or_source TEXT NOT NULL,
+            timestamp_unix INTEGER,
+            timestamp_display TEXT,
+            original_id TEXT UNIQUE,
+            is_active BOOLEAN DEFAULT 1,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS panel_associations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            content_id INTEGER NOT NULL,
+            panel_type TEXT NOT NULL,
+            is_active BOOLEAN DEFAULT 1,
+            added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (content_id) REFERENCES content_items(id),
+            UNIQUE(content_id, panel_type)
+        );
+
+        CREATE TABLE IF NOT EXISTS assistant_roles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            role_name TEXT UNIQUE NOT NULL,
+            display_name TEXT NOT NULL,
+            description TEXT,
+            is_active BOOLEAN DEFAULT 1,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS system_prompts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            role_id INTEGER NOT NULL,
+            panel_type TEXT NOT NULL,
+            prompt_text TEXT NOT NULL,
+            is_active BOOLEAN DEFAULT 1,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (role_id) REFERENCES assistant_roles(id),
+            UNIQUE(role_id, panel_type)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_content_original_id ON content_items(original_id);
+        CREATE INDEX IF NOT EXISTS idx_panel_associations_content_id ON panel_associations(content_id);
+        CREATE INDEX IF NOT EXISTS idx_panel_associations_panel_type ON panel_associations(panel_type);
+        CREATE INDEX IF NOT EXISTS idx_system_prompts_role_id ON system_prompts(role_id);
+        CREATE INDEX IF NOT EXISTS idx_system_prompts_panel_type ON system_prompts(panel_type);",
+    )
+}
+
+fn add_conversations(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS conversations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            is_archived BOOLEAN DEFAULT 0,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    add_column_if_missing(tx, "content_items", "conversation_id", "INTEGER")?;
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_content_conversation_id ON content_items(conversation_id)",
+        [],
+    )?;
+
+    // Move chat history saved before conversations existed into its own conversation
+    let orphan_count: usize = tx.query_row(
+        "SELECT COUNT(*) FROM content_items ci
+         JOIN panel_associations pa ON ci.id = pa.content_id
+         WHERE pa.panel_type = 'chat' AND ci.conversation_id IS NULL",
+        [],
+        |row| row.get(0),
+    )?;
+    if orphan_count == 0 {
+        return Ok(());
+    }
+
+    tx.execute("INSERT INTO conversations (title) VALUES ('General')", [])?;
+    tx.execute(
+        "UPDATE content_items SET conversation_id = ?
+         WHERE conversation_id IS NULL
+         AND id IN (SELECT content_id FROM panel_associations WHERE panel_type = 'chat')",
+        [tx.last_insert_rowid()],
+    )?;
+    Ok(())
+}
+
+fn add_truncated_flag(tx: &Transaction<'_>) -> SqliteResult<()> {
+    add_column_if_missing(tx, "content_items", "is_truncated", "BOOLEAN DEFAULT 0")
+}
+
+fn add_token_usage(tx: &Transaction<'_>) -> SqliteResult<()> {
+    add_column_if_missing(tx, "content_items", "model_name", "TEXT")?;
+    add_column_if_missing(tx, "content_items", "prompt_tokens", "INTEGER")?;
+    add_column_if_missing(tx, "content_items", "completion_tokens", "INTEGER")
+}
+
+fn add_context_summary(tx: &Transaction<'_>) -> SqliteResult<()> {
+    add_column_if_missing(tx, "conversations", "context_summary", "TEXT")?;
+    add_column_if_missing(tx, "conversations", "context_summary_through", "TEXT")
+}
+
+fn add_prompt_revisions(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS system_prompt_revisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            role_id INTEGER NOT NULL,
+            panel_type TEXT NOT NULL,
+            prompt_text TEXT NOT NULL,
+            author TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (role_id) REFERENCES assistant_roles(id)
+        )",
+        [],
+    )?;
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_prompt_revisions_role_panel
+         ON system_prompt_revisions(role_id, panel_type)",
+        [],
+    )?;
+    add_column_if_missing(tx, "system_prompts", "revision_id", "INTEGER")?;
+    add_column_if_missing(tx, "content_items", "prompt_revision_id", "INTEGER")?;
+    add_column_if_missing(tx, "conversations", "pinned_prompt_revision_id", "INTEGER")
+}
+
+/// FTS5 index over `content_items`, kept in sync by triggers. The trigram
+/// tokenizer matches substrings, so CJK text without spaces is searchable too.
+fn add_content_search_index(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE content_fts USING fts5(
+            content,
+            content = 'content_items',
+            content_rowid = 'id',
+            tokenize = 'trigram'
+        );
+
+        CREATE TRIGGER content_fts_insert AFTER INSERT ON content_items BEGIN
+            INSERT INTO content_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+
+        CREATE TRIGGER content_fts_delete AFTER DELETE ON content_items BEGIN
+            INSERT INTO content_fts (content_fts, rowid, content) VALUES ('delete', old.id, old.content);
+        END;
+
+        CREATE TRIGGER content_fts_update AFTER UPDATE OF content ON content_items BEGIN
+            INSERT INTO content_fts (content_fts, rowid, content) VALUES ('delete', old.id, old.content);
+            INSERT INTO content_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+
+        INSERT INTO content_fts (content_fts) VALUES ('rebuild');",
+    )
+}
+
+/// `timestamp_unix` only has whole seconds; items saved within the same second
+/// keep their insertion order through the row id.
+fn add_millisecond_timestamps(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "ALTER TABLE content_items ADD COLUMN timestamp_ms INTEGER;
+
+        UPDATE content_items SET timestamp_ms = COALESCE(
+            timestamp_unix * 1000,
+            CAST(strftime('%s', created_at) AS INTEGER) * 1000
+        );
+
+        CREATE INDEX idx_content_timestamp_ms ON content_items(timestamp_ms);",
+    )
+}
+
+/// Digest and memory items are deduplicated by content hash; chat messages
+/// never are. Existing items are hashed so re-adding them still reuses them.
+fn add_content_hashes(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE content_hashes (
+            panel_type TEXT NOT NULL,
+            content_hash TEXT NOT NULL,
+            content_id INTEGER NOT NULL REFERENCES content_items(id),
+            PRIMARY KEY (panel_type, content_hash)
+        );",
+    )?;
+
+    let mut stmt = tx.prepare(
+        "SELECT pa.panel_type, ci.content, ci.id
+         FROM panel_associations pa
+         JOIN content_items ci ON ci.id = pa.content_id
+         WHERE pa.panel_type IN ('digest', 'longterm')
+         ORDER BY pa.is_active DESC, ci.id ASC",
+    )?;
+    let items = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    for (panel_type, content, content_id) in items {
+        tx.execute(
+            "INSERT OR IGNORE INTO content_hashes (panel_type, content_hash, content_id) VALUES (?, ?, ?)",
+            rusqlite::params![panel_type, crate::database::content_hash(&content), content_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Where each imported conversation came from, so importing the same file
+/// twice can be detected.
+fn add_conversation_imports(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE conversation_imports (
+            source_key TEXT PRIMARY KEY,
+            conversation_id INTEGER NOT NULL REFERENCES conversations(id),
+            imported_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );",
+    )
+}
+
+fn add_provider_profiles(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE provider_profiles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            config TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );",
+    )
+}
+
+/// JSON-encoded [`crate::generation_params::RoleGenerationParams`]; NULL inherits everything.
+fn add_role_generation_params(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch("ALTER TABLE assistant_roles ADD COLUMN generation_params TEXT;")
+}
+
+/// What each provider profile's `/models` endpoint returned last; `models` is JSON.
+fn add_model_lists(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE model_lists (
+            profile_id INTEGER PRIMARY KEY REFERENCES provider_profiles(id),
+            base_url TEXT NOT NULL,
+            models TEXT NOT NULL,
+            fetched_at_ms INTEGER NOT NULL
+        );",
+    )
+}
+
+/// Requests to LLM APIs for the Network log window; `request_headers` is JSON
+/// with credentials already redacted.
+fn add_network_log(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE network_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_at_ms INTEGER NOT NULL,
+            method TEXT NOT NULL,
+            url TEXT NOT NULL,
+            request_headers TEXT NOT NULL,
+            status INTEGER,
+            latency_ms INTEGER,
+            duration_ms INTEGER NOT NULL,
+            attempts INTEGER NOT NULL,
+            prompt_tokens INTEGER,
+            completion_tokens INTEGER,
+            error TEXT,
+            request_body TEXT,
+            response_body TEXT
+        );",
+    )
+}
+
+/// What each profile's API key is stored under. Existing profiles get a random
+/// id; their name-keyed keys are copied over the first time they are read.
+fn add_profile_credential_ids(tx: &Transaction<'_>) -> SqliteResult<()> {
+    tx.execute_batch(
+        "ALTER TABLE provider_profiles ADD COLUMN credential_id TEXT;
+         UPDATE provider_profiles SET credential_id = 'legacy-' || lower(hex(randomblob(16)));",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Schemas of `chat_data.db` as written by earlier releases, all unversioned.
+    const LEGACY_FIXTURES: &[(&str, &str)] = &[
+        (
+            "baseline",
+            include_str!("../tests/fixtures/schema_baseline.sql"),
+        ),
+        (
+            "conversations",
+            include_str!("../tests/fixtures/schema_conversations.sql"),
+        ),
+        (
+            "truncated",
+            include_str!("../tests/fixtures/schema_truncated.sql"),
+        ),
+        (
+            "token_usage",
+            include_str!("../tests/fixtures/schema_token_usage.sql"),
+        ),
+        (
+            "context_summary",
+            include_str!("../tests/fixtures/schema_context_summary.sql"),
+        ),
+        (
+            "prompt_revisions",
+            include_str!("../tests/fixtures/schema_prompt_revisions.sql"),
+        ),
+    ];
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!("PRAGMA table_info({table})"))
+            .expect("table_info");
+        stmt.query_map([], |row| row.get::<_, String>(1))
+            .expect("query table_info")
+            .collect::<SqliteResult<_>>()
+            .expect("column names")
+    }
+
+    fn table_schema(conn: &Connection) -> Vec<(String, Vec<String>)> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .expect("sqlite_master");
+        let tables: Vec<String> = stmt
+            .query_map([], |row| row.get(0))
+            .expect("query sqlite_master")
+            .collect::<SqliteResult<_>>()
+            .expect("table names");
+        tables
+            .into_iter()
+            .map(|table| {
+                let mut table_columns = columns(conn, &table);
+                table_columns.sort();
+                (table, table_columns)
+            })
+            .collect()
+    }
+
+    fn fresh_schema() -> Vec<(String, Vec<String>)> {
+        let mut conn = Connection::open_in_memory().expect("open");
+        run(&mut conn).expect("migrate fresh database");
+        table_schema(&conn)
+    }
+
+    #[test]
+    fn fresh_database_reaches_current_version() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        run(&mut conn).expect("migrate");
+        assert_eq!(schema_version(&conn).expect("version"), SCHEMA_VERSION);
+
+        // Running again is a no-op
+        run(&mut conn).expect("migrate again");
+        assert_eq!(schema_version(&conn).expect("version"), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn migrations_are_ordered() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(
+                migration.version,
+                index as i64 + 1,
+                "migration {:?} out of order",
+                migration.description
+            );
+        }
+    }
+
+    #[test]
+    fn legacy_databases_upgrade_to_the_fresh_schema() {
+        let expected = fresh_schema();
+        for (name, fixture) in LEGACY_FIXTURES {
+            let mut conn = Connection::open_in_memory().expect("open");
+            conn.execute_batch(fixture).expect(name);
+            let messages_before: usize = conn
+                .query_row("SELECT COUNT(*) FROM content_items", [], |row| row.get(0))
+                .expect(name);
+
+            run(&mut conn).unwrap_or_else(|e| panic!("migrating {name}: {e}"));
+
+            assert_eq!(
+                schema_version(&conn).expect("version"),
+                SCHEMA_VERSION,
+                "{name}"
+            );
+            assert_eq!(table_schema(&conn), expected, "schema of upgraded {name}");
+            let messages_after: usize = conn
+                .query_row("SELECT COUNT(*) FROM content_items", [], |row| row.get(0))
+                .expect(name);
+            assert_eq!(messages_before, messages_after, "{name} lost content");
+
+            let orphans: usize = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM content_items ci
+                     JOIN panel_associations pa ON ci.id = pa.content_id
+                     WHERE pa.panel_type = 'chat' AND ci.conversation_id IS NULL",
+                    [],
+                    |row| row.get(0),
+                )
+                .expect(name);
+            assert_eq!(
+                orphans, 0,
+                "{name} left chat messages without a conversation"
+            );
+        }
+    }
+
+    #[test]
+    fn baseline_chat_history_moves_into_a_conversation() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        conn.execute_batch(LEGACY_FIXTURES[0].1).expect("fixture");
+        run(&mut conn).expect("migrate");
+
+        let titles: Vec<String> = conn
+            .prepare("SELECT title FROM conversations")
+            .expect("prepare")
+            .query_map([], |row| row.get(0))
+            .expect("query")
+            .collect::<SqliteResult<_>>()
+            .expect("titles");
+        assert_eq!(titles, vec!["General".to_owned()]);
+    }
+
+    #[test]
+    fn existing_and_new_content_is_searchable() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        conn.execute_batch(LEGACY_FIXTURES[0].1).expect("fixture");
+        run(&mut conn).expect("migrate");
+
+        let count_matches = |conn: &Connection, query: &str| -> usize {
+            conn.query_row(
+                "SELECT COUNT(*) FROM content_fts WHERE content_fts MATCH ?",
+                [query],
+                |row| row.get(0),
+            )
+            .expect("search")
+        };
+        assert_eq!(count_matches(&conn, "nda"), 3, "fixture content is indexed");
+
+        conn.execute(
+            "INSERT INTO content_items (content, role_or_source) VALUES ('Another NDA question', 'user')",
+            [],
+        )
+        .expect("insert");
+        assert_eq!(count_matches(&conn, "nda"), 4, "new content is indexed");
+
+        conn.execute_batch(
+            "DELETE FROM panel_associations WHERE content_id = 1;
+             DELETE FROM content_items WHERE id = 1;",
+        )
+        .expect("delete");
+        assert_eq!(
+            count_matches(&conn, "nda"),
+            3,
+            "deleted content leaves the index"
+        );
+    }
+
+    #[test]
+    fn newer_database_is_refused() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .expect("set version");
+
+        match run(&mut conn) {
+            Err(DatabaseError::NewerSchema { found, supported }) => {
+                assert_eq!(found, SCHEMA_VERSION + 1);
+                assert_eq!(supported, SCHEMA_VERSION);
+            }
+            other => panic!("expected NewerSchema, got {other:?}"),
+        }
+        assert!(
+            columns(&conn, "content_items").is_empty(),
+            "a refused database must not be touched"
+        );
+    }
+}
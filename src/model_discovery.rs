@@ -0,0 +1,462 @@
+use crate::app::TemplateApp;
+use crate::llm_provider::{AuthStyle, Connection};
+use crate::network_log::{Exchange, NetworkRecorder};
+use crate::profiles::ProviderProfile;
+use crate::streaming::{StreamErrorKind, StreamEvent};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::mpsc;
+
+/// One model offered by a provider's `/models` endpoint.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Context window in tokens, for the servers that report it.
+    pub context_length: Option<u64>,
+}
+
+impl ModelInfo {
+    /// The id with the context window, e.g. `llama3.1 · 128k context`.
+    pub fn label(&self) -> String {
+        match self.context_length {
+            Some(tokens) if tokens >= 1024 => format!("{} · {}k context", self.id, tokens / 1024),
+            Some(tokens) => format!("{} · {tokens} context", self.id),
+            None => self.id.clone(),
+        }
+    }
+}
+
+/// The models a profile's server offered when it was last asked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelList {
+    /// The list is stale once the profile points somewhere else.
+    pub base_url: String,
+    pub models: Vec<ModelInfo>,
+    pub fetched_at_ms: i64,
+}
+
+impl ModelList {
+    pub fn contains(&self, model: &str) -> bool {
+        self.models.iter().any(|info| info.id == model)
+    }
+}
+
+/// Why the model list could not be fetched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelListError {
+    pub kind: StreamErrorKind,
+    pub status: Option<u16>,
+    pub body: String,
+}
+
+impl std::fmt::Display for ModelListError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&StreamEvent::error_message(
+            self.kind,
+            self.status,
+            &self.body,
+        ))
+    }
+}
+
+/// Fields servers use for the context window: `OpenRouter` and Together,
+/// Anthropic, LM Studio, vLLM.
+const CONTEXT_LENGTH_FIELDS: [&str; 5] = [
+    "context_length",
+    "context_window",
+    "max_input_tokens",
+    "max_context_length",
+    "max_model_len",
+];
+
+/// The models in a `{"data": [{"id": ...}, ...]}` response, sorted by id.
+pub fn parse_model_list(body: &str) -> Option<Vec<ModelInfo>> {
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    let mut models: Vec<ModelInfo> = json["data"]
+        .as_array()?
+        .iter()
+        .filter_map(|entry| {
+            Some(ModelInfo {
+                id: entry["id"].as_str()?.to_owned(),
+                context_length: CONTEXT_LENGTH_FIELDS
+                    .iter()
+                    .find_map(|field| entry[field].as_u64()),
+            })
+        })
+        .collect();
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    Some(models)
+}
+
+/// Ask the server behind `connection` which models it offers.
+pub async fn fetch_models(
+    connection: &Connection,
+    recorder: Option<&NetworkRecorder>,
+) -> Result<Vec<ModelInfo>, ModelListError> {
+    let mut exchange = None;
+    let result = request_models(connection, recorder, &mut exchange).await;
+    if let Some(exchange) = &mut exchange {
+        match &result {
+            Ok(_) => exchange.finish(),
+            Err(e) => exchange.fail(e.to_string()),
+        }
+    }
+    result
+}
+
+async fn request_models(
+    connection: &Connection,
+    recorder: Option<&NetworkRecorder>,
+    exchange: &mut Option<Exchange>,
+) -> Result<Vec<ModelInfo>, ModelListError> {
+    let error = |kind, status, body: String| ModelListError { kind, status, body };
+
+    let provider = connection.provider();
+    let client = connection
+        .client()
+        .map_err(|e| error(StreamErrorKind::InvalidRequest, None, e.to_string()))?;
+    let request = connection
+        .build_models_request(&client, provider.as_ref())
+        .build()
+        .map_err(|e| error(StreamErrorKind::InvalidRequest, None, e.to_string()))?;
+    *exchange = recorder.map(|recorder| recorder.start(&request));
+    let response = client
+        .execute(request)
+        .await
+        .map_err(|e| error(StreamErrorKind::Connection, None, e.to_string()))?;
+
+    let status = response.status();
+    if let Some(exchange) = exchange.as_mut() {
+        exchange.response(status);
+    }
+    let body = response
+        .text()
+        .await
+        .map_err(|e| error(StreamErrorKind::Connection, None, e.to_string()))?;
+    if let Some(exchange) = exchange.as_mut() {
+        exchange.response_body(body.as_bytes());
+    }
+    if !status.is_success() {
+        return Err(error(
+            StreamErrorKind::Http,
+            Some(status.as_u16()),
+            provider.parse_error(&body),
+        ));
+    }
+    parse_model_list(&body).ok_or_else(|| {
+        error(
+            StreamErrorKind::Stream,
+            None,
+            "the response is not a model list".to_owned(),
+        )
+    })
+}
+
+/// A model list request on its way.
+struct PendingFetch {
+    profile_id: Option<i64>,
+    base_url: String,
+    /// Report the configured model when the server doesn't offer it.
+    validate_model: Option<String>,
+    receiver: mpsc::Receiver<Result<Vec<ModelInfo>, ModelListError>>,
+}
+
+#[derive(Default)]
+pub struct ModelDiscoveryState {
+    /// By profile id (`None` for a profile that hasn't been saved yet); the
+    /// value is `None` when the workspace has no list for the profile.
+    lists: HashMap<Option<i64>, Option<ModelList>>,
+    pending: Option<PendingFetch>,
+    pub error: Option<String>,
+    pub search: String,
+    /// Check the active profile's model at the next frame.
+    pub validate_active_model: bool,
+}
+
+impl TemplateApp {
+    /// The cached model list of a profile, read from the workspace the first time.
+    fn model_list(&mut self, profile: &ProviderProfile) -> Option<&ModelList> {
+        let base_url = profile.config.base_url.trim_end_matches('/');
+        if let (Some(db), Some(profile_id)) = (&self.database, profile.id) {
+            if let Entry::Vacant(entry) = self.model_discovery.lists.entry(profile.id) {
+                entry.insert(db.get_model_list(profile_id).unwrap_or_else(|e| {
+                    log::error!("Failed to load the model list of profile {profile_id}: {e}");
+                    None
+                }));
+            }
+        }
+        self.model_discovery
+            .lists
+            .get(&profile.id)
+            .and_then(Option::as_ref)
+            .filter(|list| list.base_url == base_url)
+    }
+
+    /// Fetch the model list of `profile` in the background.
+    fn refresh_models(
+        &mut self,
+        ctx: &egui::Context,
+        profile: &ProviderProfile,
+        api_key: String,
+        validate: bool,
+    ) {
+        let connection = profile.config.connection(api_key);
+        let (tx, rx) = mpsc::channel();
+        self.model_discovery.pending = Some(PendingFetch {
+            profile_id: profile.id,
+            base_url: connection.base_url.clone(),
+            validate_model: validate.then(|| profile.config.model.clone()),
+            receiver: rx,
+        });
+        self.model_discovery.error = None;
+
+        let ctx = ctx.clone();
+        let recorder = self.network_recorder();
+        tokio::spawn(async move {
+            _ = tx.send(fetch_models(&connection, recorder.as_ref()).await);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Start the startup check of the active model and pick up finished fetches.
+    pub fn poll_model_discovery(&mut self, ctx: &egui::Context) {
+        if self.model_discovery.validate_active_model && self.model_discovery.pending.is_none() {
+            self.model_discovery.validate_active_model = false;
+            let needs_key = self.active_profile.config.auth_style != AuthStyle::None;
+            if !needs_key || !self.api_key.is_empty() {
+                let profile = self.active_profile.clone();
+                self.refresh_models(ctx, &profile, self.api_key.clone(), true);
+            }
+        }
+
+        let Some(pending) = &self.model_discovery.pending else {
+            return;
+        };
+        let result = match pending.receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err(ModelListError {
+                kind: StreamErrorKind::Connection,
+                status: None,
+                body: "the request was cancelled".to_owned(),
+            }),
+        };
+        let Some(pending) = self.model_discovery.pending.take() else {
+            return;
+        };
+
+        let models = match result {
+            Ok(models) => models,
+            Err(e) => {
+                if pending.validate_model.is_some() {
+                    // Many local servers have no model list; that's not worth an error
+                    log::warn!("Could not check the configured model: {e}");
+                } else {
+                    self.model_discovery.error = Some(e.to_string());
+                }
+                return;
+            }
+        };
+
+        let list = ModelList {
+            base_url: pending.base_url,
+            models,
+            fetched_at_ms: crate::timestamps::now_ms(),
+        };
+        if let Some(model) = pending.validate_model {
+            if !list.models.is_empty() && !list.contains(&model) {
+                self.last_error = Some(format!(
+                    "Model `{model}` is not offered by {}; pick another one in Settings",
+                    list.base_url
+                ));
+            }
+        }
+        if let (Some(db), Some(profile_id)) = (&self.database, pending.profile_id) {
+            if let Err(e) = db.save_model_list(profile_id, &list) {
+                log::error!("Failed to cache the model list of profile {profile_id}: {e}");
+            }
+        }
+        self.model_discovery
+            .lists
+            .insert(pending.profile_id, Some(list));
+    }
+
+    /// Model field of the profile being edited, with a searchable list of the server's models.
+    pub fn render_model_picker(&mut self, ui: &mut egui::Ui) {
+        let draft = self.profile_draft.clone();
+        let list = self.model_list(&draft).cloned();
+        let is_fetching = self.model_discovery.pending.is_some();
+        let mut refresh = false;
+
+        ui.horizontal(|ui| {
+            let model = &mut self.profile_draft.config.model;
+            ui.text_edit_singleline(model);
+
+            let search = &mut self.model_discovery.search;
+            egui::ComboBox::from_id_salt("model_list")
+                .selected_text("")
+                .width(24.0)
+                .height(320.0)
+                .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+                .show_ui(ui, |ui| {
+                    let Some(list) = &list else {
+                        ui.weak("Refresh to ask the server for its models");
+                        return;
+                    };
+                    ui.add(egui::TextEdit::singleline(search).hint_text("Search models"));
+                    let needle = search.to_lowercase();
+                    for info in list
+                        .models
+                        .iter()
+                        .filter(|info| info.id.to_lowercase().contains(&needle))
+                    {
+                        if ui
+                            .selectable_label(*model == info.id, info.label())
+                            .clicked()
+                        {
+                            model.clone_from(&info.id);
+                            ui.close();
+                        }
+                    }
+                })
+                .response
+                .on_hover_text("Models offered by the server");
+
+            if is_fetching {
+                ui.spinner();
+            } else if ui
+                .small_button("🔄")
+                .on_hover_text("Ask the server for its models")
+                .clicked()
+            {
+                refresh = true;
+            }
+        });
+
+        if let Some(error) = &self.model_discovery.error {
+            ui.colored_label(egui::Color32::RED, format!("Model list: {error}"));
+        } else if let Some(list) = &list {
+            let model = self.profile_draft.config.model.trim();
+            if !list.models.is_empty() && !list.contains(model) {
+                ui.colored_label(
+                    egui::Color32::from_rgb(0xC0, 0x80, 0x00),
+                    format!("⚠ `{model}` is not offered by this server"),
+                );
+            } else {
+                ui.weak(format!(
+                    "{} models, listed {}",
+                    list.models.len(),
+                    crate::timestamps::format_relative(list.fetched_at_ms, chrono::Local::now())
+                ));
+            }
+        }
+
+        if refresh {
+            let api_key = if std::env::var_os("LLM_API_KEY").is_some() {
+                self.api_key.clone()
+            } else {
+                self.temp_api_key.clone()
+            };
+            self.refresh_models(ui.ctx(), &draft, api_key, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_provider::ProviderKind;
+    use std::io::{BufRead as _, BufReader, Write as _};
+
+    /// Serves `GET /v1/models` to requests carrying `Bearer good-key`, and 401 to everyone else.
+    fn start_stub_server() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind stub server");
+        let address = listener.local_addr().expect("stub address");
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).expect("request line");
+                let mut authorized = false;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("header line");
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    authorized |= line.eq_ignore_ascii_case("authorization: Bearer good-key\r\n");
+                }
+
+                let (status, body) = if !request_line.starts_with("GET /v1/models ") {
+                    ("404 Not Found", r#"{"error":{"message":"no such route"}}"#)
+                } else if authorized {
+                    (
+                        "200 OK",
+                        r#"{"object":"list","data":[
+                            {"id":"qwen2.5","object":"model","context_length":32768},
+                            {"id":"llama3.1","object":"model"}
+                        ]}"#,
+                    )
+                } else {
+                    (
+                        "401 Unauthorized",
+                        r#"{"error":{"message":"Invalid API key"}}"#,
+                    )
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream
+                    .write_all(response.as_bytes())
+                    .expect("write response");
+            }
+        });
+        format!("http://{address}/v1")
+    }
+
+    fn connection(base_url: &str, api_key: &str) -> Connection {
+        Connection {
+            provider_kind: ProviderKind::OpenAiCompatible,
+            base_url: base_url.to_owned(),
+            api_key: api_key.to_owned(),
+            auth_style: AuthStyle::ProviderDefault,
+            headers: Vec::new(),
+            connect_timeout_secs: 5,
+            read_timeout_secs: 5,
+            stream_usage: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_models_and_reports_rejected_keys() {
+        let base_url = start_stub_server();
+
+        let models = fetch_models(&connection(&base_url, "good-key"), None)
+            .await
+            .expect("model list");
+        assert_eq!(
+            models,
+            vec![
+                ModelInfo {
+                    id: "llama3.1".to_owned(),
+                    context_length: None,
+                },
+                ModelInfo {
+                    id: "qwen2.5".to_owned(),
+                    context_length: Some(32_768),
+                },
+            ]
+        );
+        assert_eq!(models[1].label(), "qwen2.5 · 32k context");
+
+        let error = fetch_models(&connection(&base_url, "wrong-key"), None)
+            .await
+            .expect_err("a wrong key is rejected");
+        assert_eq!(error.status, Some(401));
+        assert_eq!(error.to_string(), "HTTP 401: Invalid API key");
+    }
+}
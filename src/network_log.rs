@@ -0,0 +1,504 @@
+use crate::app::TemplateApp;
+use crate::streaming::StreamEvent;
+use std::collections::VecDeque;
+use std::sync::mpsc;
+
+/// Entries kept in memory for the Network log window.
+const RING_CAPACITY: usize = 200;
+
+/// Entries kept in the workspace; older ones are deleted as new ones arrive.
+pub const STORED_ENTRIES: i64 = 1000;
+
+/// Longest request or response body kept per entry.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Recorded instead of the value of headers that carry credentials.
+pub const REDACTED: &str = "[redacted]";
+
+/// Whether the value of header `name` may hold a credential.
+fn is_secret_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    matches!(
+        name.as_str(),
+        "authorization" | "proxy-authorization" | "cookie"
+    ) || ["key", "token", "secret", "auth", "password"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+/// One request to an LLM API as recorded for the network log.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkLogEntry {
+    pub id: Option<i64>,
+    pub started_at_ms: i64,
+    pub method: String,
+    pub url: String,
+    /// With credentials replaced by [`REDACTED`].
+    pub request_headers: Vec<(String, String)>,
+    pub status: Option<u16>,
+    /// Until the response headers arrived.
+    pub latency_ms: Option<u64>,
+    /// Until the response was read completely, failed or was cancelled.
+    pub duration_ms: u64,
+    pub attempts: u32,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub error: Option<String>,
+    /// Only recorded when bodies are turned on: they hold conversation content.
+    pub request_body: Option<String>,
+    pub response_body: Option<String>,
+}
+
+impl NetworkLogEntry {
+    pub fn is_failure(&self) -> bool {
+        self.error.is_some() || self.status.is_some_and(|status| status >= 400)
+    }
+}
+
+/// Hands out [`Exchange`]s that report to the app when they end.
+#[derive(Clone)]
+pub struct NetworkRecorder {
+    tx: mpsc::Sender<NetworkLogEntry>,
+    record_bodies: bool,
+}
+
+impl NetworkRecorder {
+    /// Start recording `request`, which is about to be sent.
+    pub fn start(&self, request: &reqwest::Request) -> Exchange {
+        let request_headers = request
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = if is_secret_header(name.as_str()) {
+                    REDACTED.to_owned()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (name.as_str().to_owned(), value)
+            })
+            .collect();
+        let request_body = self
+            .record_bodies
+            .then(|| request.body().and_then(|body| body.as_bytes()))
+            .flatten()
+            .map(|body| truncated_body(body, MAX_BODY_BYTES));
+
+        Exchange {
+            entry: NetworkLogEntry {
+                started_at_ms: crate::timestamps::now_ms(),
+                method: request.method().to_string(),
+                url: request.url().to_string(),
+                request_headers,
+                attempts: 1,
+                request_body,
+                response_body: self.record_bodies.then(String::new),
+                ..Default::default()
+            },
+            started: std::time::Instant::now(),
+            finished: false,
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+fn truncated_body(body: &[u8], max_bytes: usize) -> String {
+    let text = String::from_utf8_lossy(&body[..body.len().min(max_bytes)]).into_owned();
+    if body.len() > max_bytes {
+        format!("{text}\n… ({} bytes in total)", body.len())
+    } else {
+        text
+    }
+}
+
+/// A request being recorded; the entry is logged when it is dropped, so
+/// cancelled requests show up too.
+pub struct Exchange {
+    entry: NetworkLogEntry,
+    started: std::time::Instant,
+    finished: bool,
+    tx: mpsc::Sender<NetworkLogEntry>,
+}
+
+impl Exchange {
+    pub fn response(&mut self, status: reqwest::StatusCode) {
+        self.entry.status = Some(status.as_u16());
+        self.entry.latency_ms = Some(self.started.elapsed().as_millis() as u64);
+    }
+
+    pub fn response_body(&mut self, chunk: &[u8]) {
+        if let Some(body) = &mut self.entry.response_body {
+            if body.len() < MAX_BODY_BYTES {
+                body.push_str(&String::from_utf8_lossy(chunk));
+            }
+        }
+    }
+
+    pub fn fail(&mut self, message: String) {
+        self.entry.error = Some(message);
+        self.finished = true;
+    }
+
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Take what matters for the log from an event of the streamed answer.
+    pub fn observe(&mut self, event: &StreamEvent) {
+        match event {
+            StreamEvent::Usage {
+                prompt_tokens,
+                completion_tokens,
+            } => {
+                // Anthropic reports input tokens up front and output tokens at the end
+                let merge = |logged: Option<u64>, reported: u64| {
+                    Some(logged.map_or(reported, |n| n.max(reported)))
+                };
+                self.entry.prompt_tokens = merge(self.entry.prompt_tokens, *prompt_tokens);
+                self.entry.completion_tokens =
+                    merge(self.entry.completion_tokens, *completion_tokens);
+            }
+            StreamEvent::Retrying { retry, .. } => self.entry.attempts = retry + 1,
+            StreamEvent::Error { kind, status, body } => {
+                if status.is_some() {
+                    self.entry.status = *status;
+                }
+                self.fail(StreamEvent::error_message(*kind, *status, body));
+            }
+            StreamEvent::Done => self.finish(),
+            StreamEvent::Delta(_) | StreamEvent::FinishReason(_) => {}
+        }
+    }
+}
+
+impl Drop for Exchange {
+    fn drop(&mut self) {
+        let mut entry = std::mem::take(&mut self.entry);
+        entry.duration_ms = self.started.elapsed().as_millis() as u64;
+        if !self.finished {
+            entry.error = Some("Cancelled".to_owned());
+        }
+        _ = self.tx.send(entry);
+    }
+}
+
+pub struct NetworkLogState {
+    /// Newest last.
+    pub entries: VecDeque<NetworkLogEntry>,
+    pub selected_id: Option<i64>,
+    tx: mpsc::Sender<NetworkLogEntry>,
+    rx: mpsc::Receiver<NetworkLogEntry>,
+}
+
+impl Default for NetworkLogState {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            entries: VecDeque::new(),
+            selected_id: None,
+            tx,
+            rx,
+        }
+    }
+}
+
+impl TemplateApp {
+    /// Where requests report to, or `None` while the network log is off.
+    pub fn network_recorder(&self) -> Option<NetworkRecorder> {
+        self.network_log_enabled.then(|| NetworkRecorder {
+            tx: self.network_log.tx.clone(),
+            record_bodies: self.network_log_bodies,
+        })
+    }
+
+    /// Read the newest entries of the workspace into the ring buffer.
+    pub fn load_network_log(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        match db.get_network_log(RING_CAPACITY) {
+            Ok(entries) => self.network_log.entries = entries.into(),
+            Err(e) => log::error!("Failed to load the network log: {e}"),
+        }
+    }
+
+    /// Store entries of finished requests.
+    pub fn poll_network_log(&mut self) {
+        while let Ok(mut entry) = self.network_log.rx.try_recv() {
+            if let Some(db) = &self.database {
+                match db.save_network_log_entry(&entry) {
+                    Ok(id) => entry.id = Some(id),
+                    Err(e) => log::error!("Failed to store a network log entry: {e}"),
+                }
+            }
+            self.network_log.entries.push_back(entry);
+            if self.network_log.entries.len() > RING_CAPACITY {
+                self.network_log.entries.pop_front();
+            }
+        }
+    }
+
+    fn clear_network_log(&mut self) {
+        if let Some(db) = &self.database {
+            if let Err(e) = db.clear_network_log() {
+                log::error!("Failed to clear the network log: {e}");
+                self.last_error = Some(format!("Database error: {e}"));
+                return;
+            }
+        }
+        self.network_log.entries.clear();
+        self.network_log.selected_id = None;
+    }
+
+    pub fn render_network_log_window(&mut self, ctx: &egui::Context) {
+        if !self.show_network_log {
+            return;
+        }
+
+        let mut show_network_log = self.show_network_log;
+        let mut clear = false;
+        egui::Window::new("📡 Network log")
+            .open(&mut show_network_log)
+            .default_size([780.0, 480.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.network_log_enabled, "Record requests");
+                    ui.add_enabled(
+                        self.network_log_enabled,
+                        egui::Checkbox::new(&mut self.network_log_bodies, "Include bodies"),
+                    )
+                    .on_hover_text(
+                        "Bodies contain conversation content and are stored in the workspace",
+                    );
+                    if ui
+                        .add_enabled(
+                            !self.network_log.entries.is_empty(),
+                            egui::Button::new("🗑 Clear"),
+                        )
+                        .clicked()
+                    {
+                        clear = true;
+                    }
+                });
+                ui.weak("API keys and other credential headers are never recorded.");
+                ui.separator();
+
+                if self.network_log.entries.is_empty() {
+                    ui.colored_label(egui::Color32::GRAY, "No requests recorded.");
+                    return;
+                }
+
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_width(360.0);
+                        egui::ScrollArea::vertical()
+                            .id_salt("network_log_list")
+                            .auto_shrink([false, false])
+                            .show(ui, |ui| self.render_network_log_list(ui));
+                    });
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        egui::ScrollArea::vertical()
+                            .id_salt("network_log_details")
+                            .auto_shrink([false, false])
+                            .show(ui, |ui| self.render_network_log_details(ui));
+                    });
+                });
+            });
+
+        if clear {
+            self.clear_network_log();
+        }
+        self.show_network_log = show_network_log;
+    }
+
+    fn render_network_log_list(&mut self, ui: &mut egui::Ui) {
+        for entry in self.network_log.entries.iter().rev() {
+            let status = entry
+                .status
+                .map_or_else(|| "---".to_owned(), |status| status.to_string());
+            let path = reqwest::Url::parse(&entry.url)
+                .map_or_else(|_err| entry.url.clone(), |url| url.path().to_owned());
+            let mut text = egui::RichText::new(format!(
+                "{}  {status}  {} {path}  {:.1} s",
+                crate::timestamps::to_local(entry.started_at_ms)
+                    .map(|time| time.format("%H:%M:%S").to_string())
+                    .unwrap_or_default(),
+                entry.method,
+                entry.duration_ms as f64 / 1000.0,
+            ))
+            .monospace();
+            if entry.is_failure() {
+                text = text.color(egui::Color32::from_rgb(0xC0, 0x30, 0x30));
+            }
+            let selected = entry.id.is_some() && entry.id == self.network_log.selected_id;
+            if ui.selectable_label(selected, text).clicked() {
+                self.network_log.selected_id = entry.id;
+            }
+        }
+    }
+
+    fn render_network_log_details(&self, ui: &mut egui::Ui) {
+        let Some(entry) = self
+            .network_log
+            .entries
+            .iter()
+            .find(|entry| entry.id.is_some() && entry.id == self.network_log.selected_id)
+        else {
+            ui.colored_label(egui::Color32::GRAY, "Select a request to see its details.");
+            return;
+        };
+
+        egui::Grid::new("network_log_entry")
+            .num_columns(2)
+            .show(ui, |ui| {
+                let mut row = |label: &str, value: String| {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                };
+                row(
+                    "Started:",
+                    crate::timestamps::format_full(entry.started_at_ms),
+                );
+                row("Request:", format!("{} {}", entry.method, entry.url));
+                row(
+                    "Status:",
+                    entry
+                        .status
+                        .map_or_else(|| "no response".to_owned(), |status| status.to_string()),
+                );
+                row("Attempts:", entry.attempts.to_string());
+                if let Some(latency_ms) = entry.latency_ms {
+                    row("Latency:", format!("{latency_ms} ms"));
+                }
+                row("Duration:", format!("{} ms", entry.duration_ms));
+                if let (Some(prompt_tokens), Some(completion_tokens)) =
+                    (entry.prompt_tokens, entry.completion_tokens)
+                {
+                    row(
+                        "Tokens:",
+                        format!("{prompt_tokens} in, {completion_tokens} out"),
+                    );
+                }
+                if let Some(error) = &entry.error {
+                    row("Error:", error.clone());
+                }
+            });
+
+        egui::CollapsingHeader::new("Request headers")
+            .id_salt("network_log_headers")
+            .show(ui, |ui| {
+                for (name, value) in &entry.request_headers {
+                    ui.monospace(format!("{name}: {value}"));
+                }
+            });
+        for (label, body) in [
+            ("Request body", &entry.request_body),
+            ("Response body", &entry.response_body),
+        ] {
+            let Some(body) = body else {
+                continue;
+            };
+            egui::CollapsingHeader::new(label)
+                .id_salt(label)
+                .show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut body.as_str())
+                            .code_editor()
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_are_redacted_and_cancelled_requests_logged() {
+        let (tx, rx) = mpsc::channel();
+        let recorder = NetworkRecorder {
+            tx,
+            record_bodies: false,
+        };
+        let request = reqwest::Client::new()
+            .post("https://api.example.com/v1/chat/completions")
+            .header("Authorization", "Bearer sk-secret")
+            .header("X-Goog-Api-Key", "secret-too")
+            .header("X-Team", "legal")
+            .body(r#"{"messages":[{"role":"user","content":"confidential"}]}"#)
+            .build()
+            .expect("request");
+
+        let mut exchange = recorder.start(&request);
+        exchange.response(reqwest::StatusCode::OK);
+        // As Anthropic sends them: `message_start`, then `message_delta` without input tokens
+        exchange.observe(&StreamEvent::Usage {
+            prompt_tokens: 11,
+            completion_tokens: 1,
+        });
+        exchange.observe(&StreamEvent::Usage {
+            prompt_tokens: 0,
+            completion_tokens: 3,
+        });
+        drop(exchange);
+
+        let entry = rx.try_recv().expect("entry logged on drop");
+        assert_eq!(
+            entry.request_headers,
+            vec![
+                ("authorization".to_owned(), REDACTED.to_owned()),
+                ("x-goog-api-key".to_owned(), REDACTED.to_owned()),
+                ("x-team".to_owned(), "legal".to_owned()),
+            ]
+        );
+        assert_eq!(entry.request_body, None, "bodies are off");
+        assert_eq!(
+            (entry.prompt_tokens, entry.completion_tokens),
+            (Some(11), Some(3))
+        );
+        assert_eq!(entry.error.as_deref(), Some("Cancelled"), "never finished");
+    }
+
+    #[test]
+    fn entries_round_trip_through_the_database() {
+        let db = crate::database::Database::open(std::path::Path::new(":memory:"))
+            .expect("open database");
+        let entries: Vec<NetworkLogEntry> = (0..3)
+            .map(|index| NetworkLogEntry {
+                started_at_ms: 1_000 * index,
+                method: "POST".to_owned(),
+                url: "http://localhost:11434/v1/chat/completions".to_owned(),
+                request_headers: vec![("authorization".to_owned(), REDACTED.to_owned())],
+                status: Some(200),
+                latency_ms: Some(120),
+                duration_ms: 900,
+                attempts: 1,
+                response_body: Some("data: [DONE]".to_owned()),
+                ..Default::default()
+            })
+            .collect();
+        let mut saved = Vec::new();
+        for entry in entries {
+            let id = db.save_network_log_entry(&entry).expect("save");
+            saved.push(NetworkLogEntry {
+                id: Some(id),
+                ..entry
+            });
+        }
+
+        assert_eq!(
+            db.get_network_log(2).expect("load"),
+            saved[1..],
+            "newest two, oldest first"
+        );
+        db.clear_network_log().expect("clear");
+        assert!(
+            db.get_network_log(10).expect("load").is_empty(),
+            "log was not cleared"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Just enough of PDF 1.4 to lay out wrapped text on A4 pages with the
+//! standard Helvetica fonts, so exports need no font files.
+//!
+//! Those fonts only cover Latin-1 and a few punctuation marks; text with other
+//! characters, such as CJK, is refused rather than written as `?`.
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+const LINE_SPACING: f32 = 1.4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TextStyle {
+    Title,
+    Heading,
+    Body,
+    Note,
+}
+
+impl TextStyle {
+    fn font(self) -> &'static str {
+        match self {
+            Self::Title | Self::Heading => "F2",
+            Self::Body | Self::Note => "F1",
+        }
+    }
+
+    fn size(self) -> f32 {
+        match self {
+            Self::Title => 18.0,
+            Self::Heading => 12.0,
+            Self::Body => 10.5,
+            Self::Note => 8.5,
+        }
+    }
+
+    fn gray(self) -> f32 {
+        match self {
+            Self::Note => 0.45,
+            Self::Title | Self::Heading | Self::Body => 0.1,
+        }
+    }
+}
+
+/// Approximate Helvetica advance width in em, close enough for line breaking.
+fn char_width(c: char) -> f32 {
+    match c {
+        ' ' | 'i' | 'l' | 'j' | '.' | ',' | ':' | ';' | '\'' | '!' | '|' => 0.278,
+        'f' | 't' | 'r' | 'I' | '(' | ')' | '[' | ']' | '-' => 0.333,
+        'm' | 'w' | 'M' | 'W' | '@' => 0.86,
+        'A'..='Z' => 0.68,
+        _ => 0.556,
+    }
+}
+
+fn text_width(text: &str, style: TextStyle) -> f32 {
+    let bold_factor = if style.font() == "F2" { 1.05 } else { 1.0 };
+    text.chars().map(char_width).sum::<f32>() * style.size() * bold_factor
+}
+
+/// Byte of `c` in `WinAnsiEncoding`, `None` where the standard fonts have no glyph.
+fn win_ansi_byte(c: char) -> Option<u8> {
+    Some(match c {
+        // Stray control characters are not worth refusing an export for
+        '\0'..='\x1f' | '\x7f' => b' ',
+        ' '..='~' => c as u8,
+        '\u{a0}'..='\u{ff}' => c as u32 as u8,
+        '€' => 0x80,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        _ => return None,
+    })
+}
+
+/// PDF string literal, with everything outside printable ASCII octal-escaped.
+fn pdf_string(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('(');
+    for byte in text.chars().map(|c| win_ansi_byte(c).unwrap_or(b'?')) {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    literal.push(')');
+    literal
+}
+
+/// Break one paragraph into lines no wider than `max_width`.
+fn wrap(text: &str, style: TextStyle, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let candidate = if line.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{line} {word}")
+        };
+        if text_width(&candidate, style) <= max_width {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        // A single word wider than the page is split wherever it overflows
+        for c in word.chars() {
+            line.push(c);
+            if text_width(&line, style) > max_width {
+                line.pop();
+                lines.push(std::mem::take(&mut line));
+                line.push(c);
+            }
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// Text laid out top to bottom, starting a new page whenever one is full.
+pub struct PdfWriter {
+    title: String,
+    pages: Vec<String>,
+    current: String,
+    y: f32,
+    /// First character seen that the standard fonts can't show.
+    unencodable: Option<char>,
+}
+
+impl PdfWriter {
+    pub fn new(title: &str) -> Self {
+        let mut writer = Self {
+            title: title.to_owned(),
+            pages: Vec::new(),
+            current: String::new(),
+            y: PAGE_HEIGHT - MARGIN,
+            unencodable: None,
+        };
+        writer.check_encodable(title);
+        writer
+    }
+
+    fn check_encodable(&mut self, text: &str) {
+        if self.unencodable.is_none() {
+            self.unencodable = text.chars().find(|&c| win_ansi_byte(c).is_none());
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(std::mem::take(&mut self.current));
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn line(&mut self, text: &str, style: TextStyle) {
+        let line_height = style.size() * LINE_SPACING;
+        if self.y - line_height < MARGIN {
+            self.new_page();
+        }
+        self.y -= line_height;
+        if !text.is_empty() {
+            self.current.push_str(&format!(
+                "BT /{} {} Tf {gray} {gray} {gray} rg {MARGIN} {:.2} Td {} Tj ET\n",
+                style.font(),
+                style.size(),
+                self.y,
+                pdf_string(text),
+                gray = style.gray(),
+            ));
+        }
+    }
+
+    /// Add text, keeping its line breaks and wrapping long lines.
+    pub fn text(&mut self, text: &str, style: TextStyle) {
+        self.check_encodable(text);
+        let max_width = PAGE_WIDTH - 2.0 * MARGIN;
+        for paragraph in text.replace('\t', "    ").lines() {
+            for line in wrap(paragraph, style, max_width) {
+                self.line(&line, style);
+            }
+        }
+    }
+
+    pub fn space(&mut self, points: f32) {
+        self.y -= points;
+        if self.y < MARGIN {
+            self.new_page();
+        }
+    }
+
+    /// Thin horizontal rule across the text column.
+    pub fn rule(&mut self) {
+        self.space(6.0);
+        self.current.push_str(&format!(
+            "0.8 0.8 0.8 RG 0.5 w {MARGIN} {y:.2} m {right} {y:.2} l S\n",
+            y = self.y,
+            right = PAGE_WIDTH - MARGIN,
+        ));
+        self.space(6.0);
+    }
+
+    /// The finished file, or an `InvalidInput` error naming the first character
+    /// the standard fonts can't show.
+    pub fn finish(mut self) -> std::io::Result<Vec<u8>> {
+        if let Some(c) = self.unencodable {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "PDF export only supports Latin text and can't show “{c}”; \
+                     export as HTML or Markdown instead"
+                ),
+            ));
+        }
+        if !self.current.is_empty() || self.pages.is_empty() {
+            self.pages.push(std::mem::take(&mut self.current));
+        }
+
+        // 1 catalog, 2 page tree, 3-4 fonts, 5 info, then a page and its content per page
+        let first_page_object = 6;
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..self.pages.len())
+                    .map(|i| format!("{} 0 R", first_page_object + 2 * i))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                self.pages.len()
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                .to_owned(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+                .to_owned(),
+            format!("<< /Title {} /Producer (pbot) >>", pdf_string(&self.title)),
+        ];
+        for (i, content) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                first_page_object + 2 * i + 1
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{content}endstream",
+                content.len()
+            ));
+        }
+
+        let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{object}\nendobj\n", i + 1).as_bytes());
+        }
+        let xref_offset = pdf.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            xref.push_str(&format!("{offset:010} 00000 n \n"));
+        }
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            objects.len() + 1
+        ));
+        pdf.extend_from_slice(xref.as_bytes());
+        Ok(pdf)
+    }
+}
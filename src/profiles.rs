@@ -0,0 +1,496 @@
+use crate::app::TemplateApp;
+use crate::llm_provider::{
+    AuthStyle, Connection, DEFAULT_BASE_URL, DEFAULT_MODEL, GenerationParams, ProviderKind,
+};
+
+/// Name of the profile created from the `LLM_*` variables in a new workspace.
+pub const DEFAULT_PROFILE_NAME: &str = "Default";
+
+/// Everything about a provider profile except its name and its key.
+///
+/// Stored as JSON so settings added later load from older rows with their defaults.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub provider_kind: ProviderKind,
+    pub base_url: String,
+    pub model: String,
+    pub auth_style: AuthStyle,
+    pub headers: Vec<(String, String)>,
+    pub connect_timeout_secs: u64,
+    pub read_timeout_secs: u64,
+    /// Ask `OpenAI`-compatible servers for token usage; off for servers that reject it.
+    pub stream_usage: bool,
+    pub params: GenerationParams,
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self {
+            provider_kind: ProviderKind::OpenAiCompatible,
+            base_url: DEFAULT_BASE_URL.to_owned(),
+            model: DEFAULT_MODEL.to_owned(),
+            auth_style: AuthStyle::ProviderDefault,
+            headers: Vec::new(),
+            connect_timeout_secs: 10,
+            read_timeout_secs: 120,
+            stream_usage: true,
+            params: GenerationParams::default(),
+        }
+    }
+}
+
+impl ProfileConfig {
+    /// `LLM_PROVIDER`, `LLM_BASE_URL` and `LLM_MODEL`, with defaults for the rest.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            provider_kind: ProviderKind::from_env(),
+            base_url: std::env::var("LLM_BASE_URL").unwrap_or(defaults.base_url),
+            model: std::env::var("LLM_MODEL").unwrap_or(defaults.model),
+            ..Self::default()
+        }
+    }
+
+    pub fn connection(&self, api_key: String) -> Connection {
+        Connection {
+            provider_kind: self.provider_kind,
+            base_url: self.base_url.trim_end_matches('/').to_owned(),
+            api_key,
+            auth_style: self.auth_style,
+            headers: self.headers.clone(),
+            connect_timeout_secs: self.connect_timeout_secs,
+            read_timeout_secs: self.read_timeout_secs,
+            stream_usage: self.stream_usage,
+        }
+    }
+}
+
+/// A named set of API settings; `id` is `None` until it is first saved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderProfile {
+    pub id: Option<i64>,
+    pub name: String,
+    /// What the API key is filed under. Random, so profiles of the same name in
+    /// other workspaces keep keys of their own.
+    pub credential_id: String,
+    pub config: ProfileConfig,
+}
+
+impl ProviderProfile {
+    pub fn new(name: String, config: ProfileConfig) -> Self {
+        Self {
+            id: None,
+            name,
+            credential_id: new_credential_id(),
+            config,
+        }
+    }
+}
+
+impl Default for ProviderProfile {
+    fn default() -> Self {
+        Self::new(DEFAULT_PROFILE_NAME.to_owned(), ProfileConfig::from_env())
+    }
+}
+
+pub fn new_credential_id() -> String {
+    format!("profile-{}", uuid::Uuid::new_v4().simple())
+}
+
+/// `base` with a number appended, unless that name is free already.
+fn unique_profile_name(profiles: &[ProviderProfile], base: &str) -> String {
+    let taken = |name: &str| profiles.iter().any(|profile| profile.name == name);
+    if !taken(base) {
+        return base.to_owned();
+    }
+    (2..)
+        .map(|n| format!("{base} {n}"))
+        .find(|name| !taken(name))
+        .unwrap_or_default()
+}
+
+impl TemplateApp {
+    /// Read the workspace's profiles, creating the first one from the `LLM_*` variables,
+    /// and make the last used one active.
+    pub fn load_provider_profiles(&mut self) {
+        let Some(db) = &self.database else {
+            return;
+        };
+        let profiles = db.get_provider_profiles().and_then(|profiles| {
+            if !profiles.is_empty() {
+                return Ok(profiles);
+            }
+            db.save_provider_profile(&ProviderProfile::default())?;
+            db.get_provider_profiles()
+        });
+        match profiles {
+            Ok(profiles) => self.provider_profiles = profiles,
+            Err(e) => {
+                log::error!("Failed to load provider profiles: {e}");
+                self.last_error = Some(format!("Database error: {e}"));
+                return;
+            }
+        }
+
+        let active = self
+            .provider_profiles
+            .iter()
+            .find(|profile| profile.name == self.active_profile_name)
+            .or_else(|| self.provider_profiles.first())
+            .cloned();
+        if let Some(profile) = active {
+            self.activate_profile(profile);
+        }
+    }
+
+    /// Use `profile` for the next requests, along with its stored key.
+    pub fn activate_profile(&mut self, profile: ProviderProfile) {
+        self.model_discovery.validate_active_model = true;
+        self.active_profile_name.clone_from(&profile.name);
+        self.profile_draft = profile.clone();
+        self.active_profile = profile;
+        self.load_api_key();
+    }
+
+    pub fn switch_profile(&mut self, profile_id: i64) {
+        if let Some(profile) = self
+            .provider_profiles
+            .iter()
+            .find(|profile| profile.id == Some(profile_id))
+            .cloned()
+        {
+            self.activate_profile(profile);
+        }
+    }
+
+    /// The request settings of the active profile with its stored key.
+    pub fn connection(&self) -> Connection {
+        self.active_profile.config.connection(self.api_key.clone())
+    }
+
+    /// Save the profile being edited in Settings and switch to it.
+    ///
+    /// Returns `false` when it couldn't be saved and Settings should stay open.
+    pub fn apply_profile_draft(&mut self) -> bool {
+        let Some(db) = &self.database else {
+            return false;
+        };
+        let mut draft = self.profile_draft.clone();
+        draft.name = draft.name.trim().to_owned();
+        if draft.name.is_empty() {
+            self.credential_settings.status = Some(Err("The profile needs a name".to_owned()));
+            return false;
+        }
+        match db.save_provider_profile(&draft) {
+            Ok(id) => draft.id = Some(id),
+            Err(e) => {
+                log::error!("Failed to save provider profile {}: {e}", draft.name);
+                self.credential_settings.status = Some(Err(format!("Database error: {e}")));
+                return false;
+            }
+        }
+
+        let api_key = self.temp_api_key.clone();
+        let key_from_env = std::env::var_os("LLM_API_KEY").is_some();
+        if !key_from_env {
+            self.save_api_key(&draft, &api_key);
+        }
+
+        let status = self.credential_settings.status.take();
+        self.active_profile_name.clone_from(&draft.name);
+        self.load_provider_profiles();
+        if !key_from_env {
+            // Still used for this session when the store couldn't be written
+            self.api_key.clone_from(&api_key);
+            self.temp_api_key = api_key;
+        }
+        let saved = !matches!(status, Some(Err(_)));
+        self.credential_settings.status = status;
+        saved
+    }
+
+    fn delete_profile_draft(&mut self) {
+        let (Some(db), Some(profile_id)) = (&self.database, self.profile_draft.id) else {
+            return;
+        };
+        if let Err(e) = db.delete_provider_profile(profile_id) {
+            log::error!("Failed to delete provider profile {profile_id}: {e}");
+            self.credential_settings.status = Some(Err(format!("Database error: {e}")));
+            return;
+        }
+        if std::env::var_os("LLM_API_KEY").is_none() {
+            let profile = self.profile_draft.clone();
+            self.save_api_key(&profile, "");
+        }
+        self.load_provider_profiles();
+    }
+
+    /// Start editing another profile in Settings, with its stored key.
+    fn edit_profile(&mut self, profile: ProviderProfile) {
+        self.temp_api_key = self.stored_api_key(&profile);
+        self.profile_draft = profile;
+        self.credential_settings.status = None;
+    }
+
+    /// Profile switcher shown next to the role selector.
+    pub fn render_profile_selector(&mut self, ui: &mut egui::Ui) {
+        let mut switch_to = None;
+        egui::ComboBox::from_id_salt("profile_selector")
+            .selected_text(format!("🔌 {}", self.active_profile.name))
+            .show_ui(ui, |ui| {
+                for profile in &self.provider_profiles {
+                    let is_selected = profile.id == self.active_profile.id;
+                    let response = ui.selectable_label(is_selected, &profile.name);
+                    if response.clicked() && !is_selected {
+                        switch_to = profile.id;
+                    }
+                    response.on_hover_text(format!(
+                        "{} at {}",
+                        profile.config.model, profile.config.base_url
+                    ));
+                }
+            })
+            .response
+            .on_hover_text("Provider profile used for requests");
+        if let Some(profile_id) = switch_to {
+            self.switch_profile(profile_id);
+        }
+    }
+
+    /// Which profile Settings edits, with buttons to add and delete profiles.
+    fn render_profile_picker(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Profile:");
+            let mut edit = None;
+            egui::ComboBox::from_id_salt("profile_draft")
+                .selected_text(&self.profile_draft.name)
+                .show_ui(ui, |ui| {
+                    for profile in &self.provider_profiles {
+                        if ui
+                            .selectable_label(profile.id == self.profile_draft.id, &profile.name)
+                            .clicked()
+                        {
+                            edit = Some(profile.clone());
+                        }
+                    }
+                });
+            if ui
+                .small_button("➕ New")
+                .on_hover_text("Add a profile with default settings")
+                .clicked()
+            {
+                edit = Some(ProviderProfile::new(
+                    unique_profile_name(&self.provider_profiles, "New profile"),
+                    ProfileConfig::default(),
+                ));
+            }
+            if ui
+                .small_button("⧉ Duplicate")
+                .on_hover_text("Add a copy of this profile")
+                .clicked()
+            {
+                edit = Some(ProviderProfile::new(
+                    unique_profile_name(
+                        &self.provider_profiles,
+                        &format!("{} copy", self.profile_draft.name),
+                    ),
+                    self.profile_draft.config.clone(),
+                ));
+            }
+            let can_delete = self.profile_draft.id.is_some() && self.provider_profiles.len() > 1;
+            if ui
+                .add_enabled(can_delete, egui::Button::new("🗑").small())
+                .on_hover_text("Delete this profile and its stored key")
+                .clicked()
+            {
+                self.delete_profile_draft();
+            }
+            if let Some(profile) = edit {
+                self.edit_profile(profile);
+            }
+        });
+    }
+
+    /// The "LLM Configuration" part of Settings: choose, add and edit profiles.
+    #[expect(clippy::too_many_lines)]
+    pub fn render_profile_settings(&mut self, ui: &mut egui::Ui) {
+        self.render_profile_picker(ui);
+
+        egui::Grid::new("profile_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut self.profile_draft.name);
+                ui.end_row();
+
+                let config = &mut self.profile_draft.config;
+
+                ui.label("API Style:");
+                egui::ComboBox::from_id_salt("provider_kind")
+                    .selected_text(config.provider_kind.display_name())
+                    .show_ui(ui, |ui| {
+                        for kind in ProviderKind::ALL {
+                            ui.selectable_value(
+                                &mut config.provider_kind,
+                                kind,
+                                kind.display_name(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Base URL:");
+                ui.text_edit_singleline(&mut config.base_url);
+                ui.end_row();
+
+                ui.label("Model:");
+                ui.vertical(|ui| self.render_model_picker(ui));
+                ui.end_row();
+
+                let config = &mut self.profile_draft.config;
+
+                ui.label("Auth:");
+                egui::ComboBox::from_id_salt("auth_style")
+                    .selected_text(config.auth_style.display_name())
+                    .show_ui(ui, |ui| {
+                        for auth_style in AuthStyle::ALL {
+                            ui.selectable_value(
+                                &mut config.auth_style,
+                                auth_style,
+                                auth_style.display_name(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Timeouts (s):");
+                ui.horizontal(|ui| {
+                    ui.label("connect");
+                    ui.add(egui::DragValue::new(&mut config.connect_timeout_secs).range(1..=300));
+                    ui.label("read");
+                    ui.add(egui::DragValue::new(&mut config.read_timeout_secs).range(0..=3600))
+                        .on_hover_text(
+                            "Longest wait for the next part of an answer, 0 for no limit",
+                        );
+                });
+                ui.end_row();
+
+                if config.provider_kind == ProviderKind::OpenAiCompatible {
+                    ui.label("Token usage:");
+                    ui.checkbox(&mut config.stream_usage, "Request usage in the stream")
+                        .on_hover_text(
+                            "Sends stream_options; turn off if the server answers 400 Bad Request",
+                        );
+                    ui.end_row();
+                }
+            });
+
+        let config = &mut self.profile_draft.config;
+        ui.label("Extra headers:");
+        let mut header_to_remove = None;
+        egui::Grid::new("profile_headers_grid")
+            .num_columns(3)
+            .show(ui, |ui| {
+                for (index, (name, value)) in config.headers.iter_mut().enumerate() {
+                    ui.add(
+                        egui::TextEdit::singleline(name)
+                            .hint_text("Name")
+                            .desired_width(120.0),
+                    );
+                    ui.add(
+                        egui::TextEdit::singleline(value)
+                            .hint_text("Value")
+                            .desired_width(180.0),
+                    );
+                    if ui
+                        .small_button("✖")
+                        .on_hover_text("Remove header")
+                        .clicked()
+                    {
+                        header_to_remove = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(index) = header_to_remove {
+            config.headers.remove(index);
+        }
+        if ui.small_button("➕ Add header").clicked() {
+            config.headers.push((String::new(), String::new()));
+        }
+
+        egui::CollapsingHeader::new("🎛 Generation defaults")
+            .id_salt("profile_generation_params")
+            .show(ui, |ui| {
+                ui.weak(
+                    "Used unless the role sets a value; unchecked ones are left to the server.",
+                );
+                crate::generation_params::edit_generation_params(ui, &mut config.params, "profile");
+            });
+
+        self.render_api_key_settings(ui);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use std::path::Path;
+
+    #[test]
+    fn profiles_round_trip_through_the_database() {
+        let db = Database::open(Path::new(":memory:")).expect("open database");
+        let mut profile = ProviderProfile::new(
+            "local Ollama".to_owned(),
+            ProfileConfig {
+                base_url: "http://localhost:11434/v1".to_owned(),
+                model: "llama3.1".to_owned(),
+                auth_style: AuthStyle::None,
+                headers: vec![("X-Team".to_owned(), "legal".to_owned())],
+                params: GenerationParams {
+                    temperature: Some(0.0),
+                    stop: vec!["###".to_owned()],
+                    ..GenerationParams::default()
+                },
+                ..ProfileConfig::default()
+            },
+        );
+        profile.id = Some(db.save_provider_profile(&profile).expect("insert"));
+        profile.config.model = "qwen2.5".to_owned();
+        assert_eq!(
+            db.save_provider_profile(&profile).ok(),
+            profile.id,
+            "update keeps the id"
+        );
+
+        assert_eq!(
+            db.get_provider_profiles().expect("load"),
+            vec![profile.clone()]
+        );
+
+        db.delete_provider_profile(profile.id.expect("saved"))
+            .expect("delete");
+        assert!(
+            db.get_provider_profiles().expect("load").is_empty(),
+            "profile was not deleted"
+        );
+    }
+
+    #[test]
+    fn new_profile_names_do_not_collide() {
+        let profiles = [
+            ProviderProfile::default(),
+            ProviderProfile {
+                name: "Default 2".to_owned(),
+                ..ProviderProfile::default()
+            },
+        ];
+        assert_eq!(unique_profile_name(&profiles, "Default"), "Default 3");
+        assert_eq!(
+            unique_profile_name(&profiles, "Azure staging"),
+            "Azure staging"
+        );
+    }
+}
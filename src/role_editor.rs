@@ -0,0 +1,572 @@
+use crate::app::{AssistantRole, PromptRevision, TemplateApp};
+use crate::database::Database;
+use crate::generation_params::{RoleGenerationParams, edit_generation_params};
+use std::collections::HashMap;
+
+/// Panels that have their own system prompt.
+pub const PROMPT_PANELS: [(&str, &str); 3] = [
+    ("chat", "💬 Chat"),
+    ("digest", "📌 Digest"),
+    ("memory", "🗄 Memory"),
+];
+
+/// Unsaved edits of one role; `role_id` is `None` for a role that doesn't exist yet.
+#[derive(Clone, Default)]
+pub struct RoleDraft {
+    pub role_id: Option<i64>,
+    pub role_name: String,
+    pub display_name: String,
+    pub description: String,
+    pub is_active: bool,
+    pub prompts: HashMap<String, String>, // panel_type -> prompt_text
+    pub params: RoleGenerationParams,
+}
+
+#[derive(Default)]
+pub struct RoleEditorState {
+    pub roles: Vec<AssistantRole>,
+    pub draft: Option<RoleDraft>,
+    pub revisions: HashMap<String, Vec<PromptRevision>>, // panel_type -> revisions, newest first
+    pub diff_revision_id: Option<i64>,
+    pub error: Option<String>,
+}
+
+enum RoleEditorAction {
+    Select(i64),
+    New,
+    Clone,
+    Save,
+    Revert,
+    Pin(Option<i64>),
+}
+
+/// Name recorded as the author of prompt revisions.
+fn current_author() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+impl TemplateApp {
+    pub fn open_role_editor(&mut self) {
+        self.show_role_editor = true;
+        self.role_editor.error = None;
+        self.reload_role_editor_roles();
+
+        if self.role_editor.draft.is_none() {
+            if let Some(role_id) = self.current_assistant_role_id {
+                self.select_role_for_editing(role_id);
+            }
+        }
+    }
+
+    fn reload_role_editor_roles(&mut self) {
+        let Some(ref db) = self.database else {
+            return;
+        };
+
+        match db.get_all_assistant_roles() {
+            Ok(roles) => self.role_editor.roles = roles,
+            Err(e) => {
+                log::error!("Failed to load assistant roles: {e}");
+                self.role_editor.error = Some(format!("Database error: {e}"));
+            }
+        }
+    }
+
+    fn select_role_for_editing(&mut self, role_id: i64) {
+        let Some(role) = self
+            .role_editor
+            .roles
+            .iter()
+            .find(|role| role.id == role_id)
+        else {
+            return;
+        };
+        let Some(ref db) = self.database else {
+            return;
+        };
+
+        let mut revisions = HashMap::new();
+        for (panel_type, _) in PROMPT_PANELS {
+            match db.get_prompt_revisions(role_id, panel_type) {
+                Ok(panel_revisions) => {
+                    revisions.insert(panel_type.to_owned(), panel_revisions);
+                }
+                Err(e) => log::error!("Failed to load {panel_type} prompt revisions: {e}"),
+            }
+        }
+        self.role_editor.revisions = revisions;
+        self.role_editor.diff_revision_id = None;
+
+        let prompts = db.get_system_prompts_for_role(role_id).and_then(|prompts| {
+            Ok((prompts, db.get_role_generation_params(role_id)?))
+        });
+        match prompts {
+            Ok((prompts, params)) => {
+                self.role_editor.draft = Some(RoleDraft {
+                    role_id: Some(role.id),
+                    role_name: role.role_name.clone(),
+                    display_name: role.display_name.clone(),
+                    description: role.description.clone(),
+                    is_active: role.is_active,
+                    prompts,
+                    params,
+                });
+                self.role_editor.error = None;
+            }
+            Err(e) => {
+                log::error!("Failed to load system prompts for role {role_id}: {e}");
+                self.role_editor.error = Some(format!("Database error: {e}"));
+            }
+        }
+    }
+
+    fn save_role_draft(&mut self) {
+        let Some(draft) = self.role_editor.draft.clone() else {
+            return;
+        };
+        let role_name = draft.role_name.trim();
+        let display_name = draft.display_name.trim();
+        if role_name.is_empty() || display_name.is_empty() {
+            self.role_editor.error = Some("Role name and display name are required".to_owned());
+            return;
+        }
+        let Some(ref db) = self.database else {
+            return;
+        };
+
+        match write_role_draft(db, &draft, role_name, display_name, &current_author()) {
+            Ok(role_id) => {
+                log::info!("Saved assistant role {role_name} ({role_id})");
+                self.reload_role_editor_roles();
+                self.select_role_for_editing(role_id);
+                self.reload_assistant_roles();
+            }
+            Err(e) => {
+                log::error!("Failed to save assistant role {role_name}: {e}");
+                self.role_editor.error = Some(format!("Database error: {e}"));
+            }
+        }
+    }
+
+    #[expect(clippy::too_many_lines)]
+    pub fn render_role_editor_window(&mut self, ctx: &egui::Context) {
+        if !self.show_role_editor {
+            return;
+        }
+
+        let mut show_role_editor = self.show_role_editor;
+        let mut action: Option<RoleEditorAction> = None;
+
+        egui::Window::new("👥 Role Manager")
+            .open(&mut show_role_editor)
+            .default_size([720.0, 520.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("➕ New role").clicked() {
+                        action = Some(RoleEditorAction::New);
+                    }
+                    if ui
+                        .add_enabled(
+                            self.role_editor.draft.is_some(),
+                            egui::Button::new("⧉ Clone"),
+                        )
+                        .on_hover_text("Start a new role from a copy of this one")
+                        .clicked()
+                    {
+                        action = Some(RoleEditorAction::Clone);
+                    }
+                });
+
+                if let Some(error) = &self.role_editor.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                ui.separator();
+
+                ui.horizontal_top(|ui| {
+                    // Role list
+                    ui.vertical(|ui| {
+                        ui.set_width(180.0);
+                        egui::ScrollArea::vertical()
+                            .id_salt("role_editor_list")
+                            .auto_shrink([false, false])
+                            .show(ui, |ui| {
+                                let editing_id =
+                                    self.role_editor.draft.as_ref().and_then(|d| d.role_id);
+                                for role in &self.role_editor.roles {
+                                    let mut text = egui::RichText::new(&role.display_name);
+                                    if !role.is_active {
+                                        text = text.color(egui::Color32::GRAY).italics();
+                                    }
+                                    let response = ui
+                                        .selectable_label(editing_id == Some(role.id), text)
+                                        .on_hover_text(&role.description);
+                                    if response.clicked() {
+                                        action = Some(RoleEditorAction::Select(role.id));
+                                    }
+                                }
+                            });
+                    });
+
+                    ui.separator();
+
+                    // Role form
+                    ui.vertical(|ui| {
+                        let pinned_revision_id =
+                            self.pinned_chat_prompt.as_ref().map(|revision| revision.id);
+                        let revisions = &self.role_editor.revisions;
+                        let diff_revision_id = &mut self.role_editor.diff_revision_id;
+                        let Some(draft) = &mut self.role_editor.draft else {
+                            ui.colored_label(
+                                egui::Color32::GRAY,
+                                "Select a role to edit, or create a new one.",
+                            );
+                            return;
+                        };
+
+                        egui::ScrollArea::vertical()
+                            .id_salt("role_editor_form")
+                            .auto_shrink([false, false])
+                            .show(ui, |ui| {
+                                egui::Grid::new("role_editor_fields").num_columns(2).show(
+                                    ui,
+                                    |ui| {
+                                        ui.label("Display name:");
+                                        ui.text_edit_singleline(&mut draft.display_name);
+                                        ui.end_row();
+
+                                        ui.label("Role name:");
+                                        ui.text_edit_singleline(&mut draft.role_name)
+                                            .on_hover_text("Unique identifier of the role");
+                                        ui.end_row();
+
+                                        ui.label("Description:");
+                                        ui.text_edit_singleline(&mut draft.description);
+                                        ui.end_row();
+
+                                        ui.label("Active:");
+                                        ui.checkbox(&mut draft.is_active, "Show in role selector");
+                                        ui.end_row();
+                                    },
+                                );
+
+                                for (panel_type, label) in PROMPT_PANELS {
+                                    ui.add_space(6.0);
+                                    ui.label(format!("{label} system prompt:"));
+                                    let prompt =
+                                        draft.prompts.entry(panel_type.to_owned()).or_default();
+                                    ui.add(
+                                        egui::TextEdit::multiline(prompt)
+                                            .desired_rows(5)
+                                            .desired_width(f32::INFINITY)
+                                            .hint_text("No prompt"),
+                                    );
+
+                                    let panel_revisions = revisions
+                                        .get(panel_type)
+                                        .map_or(&[][..], Vec::as_slice);
+                                    if panel_revisions.is_empty() {
+                                        continue;
+                                    }
+                                    egui::CollapsingHeader::new(format!(
+                                        "🕘 History ({})",
+                                        panel_revisions.len()
+                                    ))
+                                    .id_salt(("prompt_history", panel_type))
+                                    .show(ui, |ui| {
+                                        for (index, revision) in
+                                            panel_revisions.iter().enumerate()
+                                        {
+                                            let previous = panel_revisions.get(index + 1);
+                                            ui.horizontal(|ui| {
+                                                let current = if index == 0 { " (current)" } else { "" };
+                                                ui.label(format!(
+                                                    "r{} · {} · {}{current}",
+                                                    revision.id,
+                                                    revision.author,
+                                                    revision.created_at
+                                                ));
+                                                if pinned_revision_id == Some(revision.id) {
+                                                    ui.colored_label(
+                                                        egui::Color32::BROWN,
+                                                        "📌 pinned",
+                                                    );
+                                                }
+                                                let showing_diff =
+                                                    *diff_revision_id == Some(revision.id);
+                                                if ui
+                                                    .selectable_label(showing_diff, "Diff")
+                                                    .on_hover_text("Changes made by this revision")
+                                                    .clicked()
+                                                {
+                                                    *diff_revision_id =
+                                                        (!showing_diff).then_some(revision.id);
+                                                }
+                                                if ui
+                                                    .small_button("↩ Roll back")
+                                                    .on_hover_text(
+                                                        "Load this revision into the editor; save to make it current",
+                                                    )
+                                                    .clicked()
+                                                {
+                                                    prompt.clone_from(&revision.prompt_text);
+                                                }
+                                                if panel_type == "chat" {
+                                                    if pinned_revision_id == Some(revision.id) {
+                                                        if ui
+                                                            .small_button("Unpin")
+                                                            .on_hover_text("Use the role's current prompt again")
+                                                            .clicked()
+                                                        {
+                                                            action = Some(RoleEditorAction::Pin(None));
+                                                        }
+                                                    } else if ui
+                                                        .small_button("📌 Pin")
+                                                        .on_hover_text(
+                                                            "Always use this revision in the current conversation",
+                                                        )
+                                                        .clicked()
+                                                    {
+                                                        action = Some(RoleEditorAction::Pin(Some(
+                                                            revision.id,
+                                                        )));
+                                                    }
+                                                }
+                                            });
+
+                                            if *diff_revision_id == Some(revision.id) {
+                                                let old_text = previous
+                                                    .map_or("", |previous| previous.prompt_text.as_str());
+                                                egui::Frame::new()
+                                                    .fill(ui.visuals().extreme_bg_color)
+                                                    .inner_margin(6.0)
+                                                    .show(ui, |ui| {
+                                                        ui.label(diff_layout_job(
+                                                            ui,
+                                                            old_text,
+                                                            &revision.prompt_text,
+                                                        ));
+                                                    });
+                                            }
+                                        }
+                                    });
+                                }
+
+                                ui.add_space(6.0);
+                                egui::CollapsingHeader::new("🎛 Generation parameters")
+                                    .id_salt("role_generation_params")
+                                    .show(ui, |ui| {
+                                        ui.weak("Unchecked values come from the provider profile.");
+                                        ui.label("All panels:");
+                                        edit_generation_params(ui, &mut draft.params.all_panels, "all");
+                                        for (panel_type, label) in PROMPT_PANELS {
+                                            ui.add_space(4.0);
+                                            ui.label(format!("{label} overrides:"));
+                                            let params = draft
+                                                .params
+                                                .panels
+                                                .entry(panel_type.to_owned())
+                                                .or_default();
+                                            edit_generation_params(ui, params, panel_type);
+                                        }
+                                    });
+
+                                ui.add_space(8.0);
+                                ui.horizontal(|ui| {
+                                    let save_label = if draft.role_id.is_some() {
+                                        "💾 Save"
+                                    } else {
+                                        "💾 Create"
+                                    };
+                                    if ui.button(save_label).clicked() {
+                                        action = Some(RoleEditorAction::Save);
+                                    }
+                                    if ui.button("↺ Revert").clicked() {
+                                        action = Some(RoleEditorAction::Revert);
+                                    }
+                                });
+                            });
+                    });
+                });
+            });
+
+        match action {
+            Some(RoleEditorAction::Select(role_id)) => self.select_role_for_editing(role_id),
+            Some(RoleEditorAction::New) => {
+                self.role_editor.draft = Some(RoleDraft {
+                    is_active: true,
+                    ..Default::default()
+                });
+                self.role_editor.error = None;
+            }
+            Some(RoleEditorAction::Clone) => {
+                if let Some(draft) = &mut self.role_editor.draft {
+                    draft.role_id = None;
+                    draft.role_name = format!("{}_copy", draft.role_name);
+                    draft.display_name = format!("{} (copy)", draft.display_name);
+                    draft.is_active = true;
+                }
+                self.role_editor.error = None;
+            }
+            Some(RoleEditorAction::Save) => self.save_role_draft(),
+            Some(RoleEditorAction::Pin(revision_id)) => self.pin_prompt_revision(revision_id),
+            Some(RoleEditorAction::Revert) => {
+                match self
+                    .role_editor
+                    .draft
+                    .as_ref()
+                    .and_then(|draft| draft.role_id)
+                {
+                    Some(role_id) => self.select_role_for_editing(role_id),
+                    None => self.role_editor.draft = None,
+                }
+            }
+            None => {}
+        }
+
+        self.show_role_editor = show_role_editor;
+    }
+}
+
+/// Create or update the role and all of its prompts, all or nothing.
+fn write_role_draft(
+    db: &Database,
+    draft: &RoleDraft,
+    role_name: &str,
+    display_name: &str,
+    author: &str,
+) -> rusqlite::Result<i64> {
+    db.transaction(|| {
+        let role_id = match draft.role_id {
+            Some(role_id) => {
+                db.update_assistant_role(role_id, role_name, display_name, &draft.description)?;
+                role_id
+            }
+            None => db.create_assistant_role(role_name, display_name, &draft.description)?,
+        };
+        db.set_assistant_role_active(role_id, draft.is_active)?;
+        for (panel_type, _) in PROMPT_PANELS {
+            let prompt_text = draft.prompts.get(panel_type).map_or("", String::as_str);
+            db.save_system_prompt(role_id, panel_type, prompt_text, author)?;
+        }
+        let mut params = draft.params.clone();
+        params.panels.retain(|_, panel_params| !panel_params.is_unset());
+        db.set_role_generation_params(role_id, &params)?;
+        Ok(role_id)
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum DiffPart<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Word-level diff based on the longest common subsequence; prompts are short
+/// enough for the quadratic table.
+fn diff_words<'a>(old: &'a str, new: &'a str) -> Vec<DiffPart<'a>> {
+    let old: Vec<&str> = old.split_inclusive(char::is_whitespace).collect();
+    let new: Vec<&str> = new.split_inclusive(char::is_whitespace).collect();
+
+    let mut lcs = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut parts = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            parts.push(DiffPart::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            // Removed words come before the words replacing them
+            parts.push(DiffPart::Removed(old[i]));
+            i += 1;
+        } else {
+            parts.push(DiffPart::Added(new[j]));
+            j += 1;
+        }
+    }
+    parts
+}
+
+fn diff_layout_job(ui: &egui::Ui, old: &str, new: &str) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let text_color = ui.visuals().text_color();
+
+    let mut job = egui::text::LayoutJob::default();
+    job.wrap.max_width = ui.available_width();
+    for part in diff_words(old, new) {
+        let (text, format) = match part {
+            DiffPart::Same(text) => (text, egui::TextFormat::simple(font_id.clone(), text_color)),
+            DiffPart::Removed(text) => (
+                text,
+                egui::TextFormat {
+                    font_id: font_id.clone(),
+                    color: egui::Color32::from_rgb(0xB0, 0x20, 0x20),
+                    background: egui::Color32::from_rgb(0xFF, 0xE0, 0xE0),
+                    strikethrough: egui::Stroke::new(
+                        1.0,
+                        egui::Color32::from_rgb(0xB0, 0x20, 0x20),
+                    ),
+                    ..Default::default()
+                },
+            ),
+            DiffPart::Added(text) => (
+                text,
+                egui::TextFormat {
+                    font_id: font_id.clone(),
+                    color: egui::Color32::from_rgb(0x10, 0x70, 0x20),
+                    background: egui::Color32::from_rgb(0xDD, 0xF4, 0xDD),
+                    ..Default::default()
+                },
+            ),
+        };
+        job.append(text, 0.0, format);
+    }
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_words_marks_removed_and_added_words() {
+        use DiffPart::{Added, Removed, Same};
+        assert_eq!(
+            diff_words("Answer in short sentences.", "Answer in long, clear sentences."),
+            [
+                Same("Answer "),
+                Same("in "),
+                Removed("short "),
+                Added("long, "),
+                Added("clear "),
+                Same("sentences."),
+            ]
+        );
+        assert_eq!(diff_words("", "Be brief."), [Added("Be "), Added("brief.")]);
+        assert_eq!(diff_words("Be brief.", ""), [Removed("Be "), Removed("brief.")]);
+        assert_eq!(diff_words("same", "same"), [Same("same")]);
+    }
+
+    #[test]
+    fn whitespace_changes_show_in_the_diff() {
+        use DiffPart::{Added, Removed, Same};
+        assert_eq!(
+            diff_words("one two", "one\ntwo"),
+            [Removed("one "), Added("one\n"), Same("two")]
+        );
+    }
+}
@@ -0,0 +1,686 @@
+use crate::app::TemplateApp;
+
+/// Characters of context shown on each side of the first match.
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// Most results one search returns.
+const SEARCH_RESULT_LIMIT: usize = 200;
+
+/// The trigram index can't match shorter terms; those are filtered with `LIKE`.
+pub const MIN_INDEXED_TERM_CHARS: usize = 3;
+
+/// Narrowing of a global search; empty fields don't filter.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SearchFilters {
+    pub panel_type: Option<String>,
+    pub role_or_source: Option<String>,
+    pub from_date: String, // YYYY-MM-DD, inclusive
+    pub to_date: String,   // YYYY-MM-DD, inclusive
+}
+
+/// One stored item matching a search, as shown in one panel.
+#[derive(Clone)]
+pub struct SearchHit {
+    pub original_id: String,
+    pub panel_type: String,
+    pub role_or_source: String,
+    pub conversation_id: Option<i64>,
+    pub conversation_title: Option<String>,
+    pub timestamp_ms: i64,
+    pub content: String,
+}
+
+#[derive(Default)]
+pub struct SearchState {
+    pub query: String,
+    pub filters: SearchFilters,
+    pub sources: Vec<String>, // distinct role_or_source values to filter by
+    pub results: Vec<SearchHit>,
+    pub searched: Option<(String, SearchFilters)>, // what `results` were found for
+    pub error: Option<String>,
+    pub jump_to: Option<String>, // original id to scroll to on the next frame
+    pub highlighted: Option<String>, // original id of the last item jumped to
+}
+
+/// Words of a query, without the quotes FTS5 would otherwise interpret.
+pub fn search_terms(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .map(|term| term.replace('"', ""))
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+/// FTS5 `MATCH` expression requiring every indexable term, or `None` if no term is long enough.
+pub fn fts_match_expression(terms: &[String]) -> Option<String> {
+    let phrases: Vec<String> = terms
+        .iter()
+        .filter(|term| term.chars().count() >= MIN_INDEXED_TERM_CHARS)
+        .map(|term| format!("\"{term}\""))
+        .collect();
+    (!phrases.is_empty()).then(|| phrases.join(" "))
+}
+
+/// Unix millisecond range covered by the date filters, `[from, to)`.
+pub fn date_range(filters: &SearchFilters) -> Result<(Option<i64>, Option<i64>), String> {
+    let parse = |text: &str, label: &str| -> Result<Option<chrono::NaiveDate>, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|e| format!("{label} date must look like 2024-05-31 ({e})"))
+    };
+    let local_midnight = |date: chrono::NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|time| time.and_local_timezone(chrono::Local).earliest())
+            .map(|time| time.timestamp_millis())
+    };
+
+    let from = parse(&filters.from_date, "From")?.and_then(local_midnight);
+    let to = parse(&filters.to_date, "To")?
+        .and_then(|date| date.succ_opt())
+        .and_then(local_midnight);
+    Ok((from, to))
+}
+
+/// Piece of `content` around the first term found, split into plain and highlighted parts.
+pub fn snippet(content: &str, terms: &[String]) -> Vec<(String, bool)> {
+    let chars: Vec<char> = content.chars().collect();
+    let lower: Vec<char> = content.to_lowercase().chars().collect();
+    // Lowercasing may change the length of some characters; give up highlighting then
+    let lower_terms: Vec<Vec<char>> = if lower.len() == chars.len() {
+        terms
+            .iter()
+            .map(|term| term.to_lowercase().chars().collect())
+            .filter(|term: &Vec<char>| !term.is_empty())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let matches_at = |pos: usize| {
+        lower_terms
+            .iter()
+            .filter(|term| lower[pos..].starts_with(term))
+            .map(Vec::len)
+            .max()
+    };
+
+    let first_match = (0..chars.len()).find(|&pos| matches_at(pos).is_some());
+    let start = first_match.map_or(0, |pos| pos.saturating_sub(SNIPPET_CONTEXT_CHARS));
+    let end = first_match
+        .map_or(2 * SNIPPET_CONTEXT_CHARS, |pos| pos + SNIPPET_CONTEXT_CHARS)
+        .min(chars.len());
+
+    let mut parts: Vec<(String, bool)> = Vec::new();
+    let mut push = |text: String, highlighted: bool| match parts.last_mut() {
+        Some((last, last_highlighted)) if *last_highlighted == highlighted => last.push_str(&text),
+        _ => parts.push((text, highlighted)),
+    };
+
+    if start > 0 {
+        push("…".to_owned(), false);
+    }
+    let mut pos = start;
+    while pos < end {
+        if let Some(len) = matches_at(pos) {
+            let match_end = (pos + len).min(chars.len());
+            push(chars[pos..match_end].iter().collect(), true);
+            pos = match_end;
+        } else {
+            // Keep snippets on one line
+            let c = if chars[pos].is_whitespace() {
+                ' '
+            } else {
+                chars[pos]
+            };
+            push(c.to_string(), false);
+            pos += 1;
+        }
+    }
+    if pos < chars.len() {
+        push("…".to_owned(), false);
+    }
+    parts
+}
+
+fn snippet_layout_job(ui: &egui::Ui, parts: &[(String, bool)]) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let mut job = egui::text::LayoutJob::default();
+    job.wrap.max_width = ui.available_width();
+    for (text, highlighted) in parts {
+        let format = if *highlighted {
+            egui::TextFormat {
+                font_id: font_id.clone(),
+                color: egui::Color32::DARK_RED,
+                background: egui::Color32::from_rgb(0xFF, 0xF1, 0xB8),
+                ..Default::default()
+            }
+        } else {
+            egui::TextFormat::simple(font_id.clone(), ui.visuals().text_color())
+        };
+        job.append(text, 0.0, format);
+    }
+    job
+}
+
+/// Panels content can be stored in; memory items are kept under `longterm`.
+const CONTENT_PANELS: [(&str, &str); 3] = [
+    ("chat", "💬 Chat"),
+    ("digest", "📌 Digest"),
+    ("longterm", "🗄 Memory"),
+];
+
+fn panel_label(panel_type: &str) -> &str {
+    CONTENT_PANELS
+        .iter()
+        .find(|(panel, _)| *panel == panel_type)
+        .map_or(panel_type, |(_, label)| label)
+}
+
+impl TemplateApp {
+    pub fn open_search_window(&mut self) {
+        self.show_search = true;
+        self.search.error = None;
+        if let Some(db) = &self.database {
+            match db.get_content_sources() {
+                Ok(sources) => self.search.sources = sources,
+                Err(e) => log::error!("Failed to load content sources: {e}"),
+            }
+        }
+        // Results may be stale after edits elsewhere
+        self.search.searched = None;
+    }
+
+    fn run_search(&mut self) {
+        let query = self.search.query.trim().to_owned();
+        let filters = self.search.filters.clone();
+        self.search.searched = Some((query.clone(), filters.clone()));
+        self.search.results.clear();
+        self.search.error = None;
+
+        let terms = search_terms(&query);
+        if terms.is_empty() {
+            return;
+        }
+        let Some(db) = &self.database else {
+            self.search.error = Some("Database not available".to_owned());
+            return;
+        };
+        let date_range = match date_range(&filters) {
+            Ok(date_range) => date_range,
+            Err(e) => {
+                self.search.error = Some(e);
+                return;
+            }
+        };
+
+        match db.search_content(&terms, &filters, date_range, SEARCH_RESULT_LIMIT) {
+            Ok(results) => self.search.results = results,
+            Err(e) => {
+                log::error!("Search for {query:?} failed: {e}");
+                self.search.error = Some(format!("Database error: {e}"));
+            }
+        }
+    }
+
+    /// Show a search hit in its panel, switching conversations for chat messages.
+    fn jump_to_search_hit(&mut self, hit: &SearchHit) {
+        match hit.panel_type.as_str() {
+            "chat" => {
+                if let Some(conversation_id) = hit.conversation_id {
+                    if self.current_conversation_id != Some(conversation_id) {
+                        self.switch_conversation(conversation_id);
+                    }
+                    if !self.conversations.iter().any(|c| c.id == conversation_id) {
+                        // Archived conversations are hidden from the sidebar otherwise
+                        self.show_archived_conversations = true;
+                        self.load_conversations();
+                    }
+                }
+                self.chat_search.clear();
+                self.should_scroll_chat = false;
+            }
+            "digest" => self.digest_search.clear(),
+            "longterm" => self.memory_search.clear(),
+            _ => {}
+        }
+        self.search.jump_to = Some(hit.original_id.clone());
+        self.search.highlighted = Some(hit.original_id.clone());
+    }
+
+    /// Scroll to the item `id` if a search result asked for it; call before rendering the item.
+    pub fn scroll_to_search_hit(&mut self, ui: &mut egui::Ui, id: &str) {
+        if self.search.jump_to.as_deref() == Some(id) {
+            ui.scroll_to_cursor(Some(egui::Align::Min));
+            self.search.jump_to = None;
+        }
+        if self.search.highlighted.as_deref() == Some(id) {
+            ui.colored_label(
+                egui::Color32::from_rgb(0xB0, 0x80, 0x00),
+                "🔎 Search result",
+            );
+        }
+    }
+
+    #[expect(clippy::too_many_lines)]
+    pub fn render_search_window(&mut self, ctx: &egui::Context) {
+        if ctx.input_mut(|i| {
+            i.consume_shortcut(&egui::KeyboardShortcut::new(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::F,
+            ))
+        }) {
+            self.open_search_window();
+        }
+        if !self.show_search {
+            return;
+        }
+
+        let mut show_search = self.show_search;
+        let mut jump_to: Option<SearchHit> = None;
+
+        egui::Window::new("🔎 Search Everything")
+            .open(&mut show_search)
+            .default_size([640.0, 520.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.search.query)
+                            .desired_width(ui.available_width())
+                            .hint_text("Search chats, digest and memory…"),
+                    );
+                });
+
+                ui.horizontal_wrapped(|ui| {
+                    let filters = &mut self.search.filters;
+                    egui::ComboBox::from_id_salt("search_panel_filter")
+                        .selected_text(
+                            filters
+                                .panel_type
+                                .as_deref()
+                                .map_or("All panels", panel_label),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut filters.panel_type, None, "All panels");
+                            for (panel_type, label) in CONTENT_PANELS {
+                                ui.selectable_value(
+                                    &mut filters.panel_type,
+                                    Some(panel_type.to_owned()),
+                                    label,
+                                );
+                            }
+                        });
+
+                    egui::ComboBox::from_id_salt("search_source_filter")
+                        .selected_text(
+                            filters
+                                .role_or_source
+                                .as_deref()
+                                .unwrap_or("Any role / source"),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut filters.role_or_source,
+                                None,
+                                "Any role / source",
+                            );
+                            for source in &self.search.sources {
+                                ui.selectable_value(
+                                    &mut filters.role_or_source,
+                                    Some(source.clone()),
+                                    source,
+                                );
+                            }
+                        });
+
+                    ui.label("From");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut filters.from_date)
+                            .desired_width(90.0)
+                            .hint_text("YYYY-MM-DD"),
+                    );
+                    ui.label("to");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut filters.to_date)
+                            .desired_width(90.0)
+                            .hint_text("YYYY-MM-DD"),
+                    );
+                });
+
+                // Search as you type
+                let current = (
+                    self.search.query.trim().to_owned(),
+                    self.search.filters.clone(),
+                );
+                if self.search.searched.as_ref() != Some(&current) {
+                    self.run_search();
+                }
+
+                if let Some(error) = &self.search.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                ui.separator();
+
+                if !self.search.query.trim().is_empty() && self.search.error.is_none() {
+                    let count = self.search.results.len();
+                    let suffix = if count >= SEARCH_RESULT_LIMIT {
+                        "+"
+                    } else {
+                        ""
+                    };
+                    ui.colored_label(egui::Color32::GRAY, format!("{count}{suffix} results"));
+                }
+
+                let terms = search_terms(&self.search.query);
+                let now = chrono::Local::now();
+                egui::ScrollArea::vertical()
+                    .id_salt("search_results")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for hit in &self.search.results {
+                            let other_conversation = hit.panel_type == "chat"
+                                && hit.conversation_id != self.current_conversation_id;
+                            let can_jump = !(other_conversation && self.is_waiting_response);
+
+                            let frame = egui::Frame::new()
+                                .inner_margin(6.0)
+                                .corner_radius(4.0)
+                                .fill(ui.visuals().faint_bg_color);
+                            let response = frame
+                                .show(ui, |ui| {
+                                    ui.set_width(ui.available_width());
+                                    ui.horizontal(|ui| {
+                                        ui.label(
+                                            egui::RichText::new(panel_label(&hit.panel_type))
+                                                .strong(),
+                                        );
+                                        if let Some(title) = &hit.conversation_title {
+                                            ui.label(format!("· {title}"));
+                                        }
+                                        ui.colored_label(
+                                            egui::Color32::GRAY,
+                                            format!("· {}", hit.role_or_source),
+                                        );
+                                        if hit.timestamp_ms > 0 {
+                                            ui.colored_label(
+                                                egui::Color32::GRAY,
+                                                format!(
+                                                    "· {}",
+                                                    crate::timestamps::format_relative(
+                                                        hit.timestamp_ms,
+                                                        now
+                                                    )
+                                                ),
+                                            )
+                                            .on_hover_text(crate::timestamps::format_full(
+                                                hit.timestamp_ms,
+                                            ));
+                                        }
+                                    });
+                                    ui.label(snippet_layout_job(
+                                        ui,
+                                        &snippet(&hit.content, &terms),
+                                    ));
+                                })
+                                .response
+                                .interact(egui::Sense::click());
+
+                            let response = if can_jump {
+                                response.on_hover_text("Show in its panel")
+                            } else {
+                                response.on_hover_text("Wait for the current answer to finish")
+                            };
+                            if can_jump && response.clicked() {
+                                jump_to = Some(hit.clone());
+                            }
+                            ui.add_space(4.0);
+                        }
+                    });
+            });
+
+        self.show_search = show_search;
+        if let Some(hit) = jump_to {
+            self.jump_to_search_hit(&hit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use chrono::TimeZone as _;
+    use std::path::Path;
+
+    fn terms(query: &str) -> Vec<String> {
+        search_terms(query)
+    }
+
+    fn filters(from_date: &str, to_date: &str) -> SearchFilters {
+        SearchFilters {
+            from_date: from_date.to_owned(),
+            to_date: to_date.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn local_ms(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> i64 {
+        chrono::Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .earliest()
+            .expect("local time")
+            .timestamp_millis()
+    }
+
+    fn search(db: &Database, query: &str, filters: &SearchFilters) -> Vec<String> {
+        let range = date_range(filters).expect("valid dates");
+        db.search_content(&terms(query), filters, range, SEARCH_RESULT_LIMIT)
+            .expect("search")
+            .into_iter()
+            .map(|hit| hit.content)
+            .collect()
+    }
+
+    #[test]
+    fn quotes_are_dropped_from_terms() {
+        assert_eq!(
+            terms(r#" "exact phrase"  a"b "" "#),
+            ["exact", "phrase", "ab"]
+        );
+    }
+
+    #[test]
+    fn only_indexable_terms_are_matched_with_fts() {
+        assert_eq!(
+            fts_match_expression(&terms("AI budget Q3")),
+            Some(r#""budget""#.to_owned())
+        );
+        assert_eq!(
+            fts_match_expression(&terms("NDA 秘密保持")),
+            Some(r#""NDA" "秘密保持""#.to_owned())
+        );
+        assert_eq!(
+            fts_match_expression(&terms("AI q3")),
+            None,
+            "short terms use LIKE"
+        );
+        assert_eq!(fts_match_expression(&[]), None);
+    }
+
+    #[test]
+    fn date_range_includes_the_whole_to_day() {
+        let (from, to) = date_range(&filters("2024-05-01", "2024-05-31")).expect("valid dates");
+        assert_eq!(from, Some(local_ms(2024, 5, 1, 0, 0)));
+        assert_eq!(
+            to,
+            Some(local_ms(2024, 6, 1, 0, 0)),
+            "up to the next midnight"
+        );
+
+        assert_eq!(date_range(&filters(" ", "")), Ok((None, None)));
+        let error = date_range(&filters("", "31/05/2024")).expect_err("bad date");
+        assert!(
+            error.starts_with("To date must look like 2024-05-31"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn snippet_highlights_every_term_around_the_first_match() {
+        let parts = snippet("The Budget for\nQ3: budget review", &terms("budget q3"));
+        assert_eq!(
+            parts,
+            [
+                ("The ".to_owned(), false),
+                ("Budget".to_owned(), true),
+                (" for ".to_owned(), false),
+                ("Q3".to_owned(), true),
+                (": ".to_owned(), false),
+                ("budget".to_owned(), true),
+                (" review".to_owned(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_snippets_are_cut_around_the_first_match() {
+        let content = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let parts = snippet(&content, &terms("needle"));
+        let expected = [
+            (format!("…{}", "a".repeat(SNIPPET_CONTEXT_CHARS)), false),
+            ("needle".to_owned(), true),
+            (
+                format!("{}…", "b".repeat(SNIPPET_CONTEXT_CHARS - "needle".len())),
+                false,
+            ),
+        ];
+        assert_eq!(parts, expected);
+
+        let parts = snippet(&"c".repeat(200), &terms("needle"));
+        assert_eq!(
+            parts,
+            [(format!("{}…", "c".repeat(2 * SNIPPET_CONTEXT_CHARS)), false)],
+            "start without a match"
+        );
+    }
+
+    #[test]
+    fn short_terms_are_found_with_like() {
+        let db = Database::open(Path::new(":memory:")).expect("open in-memory database");
+        for content in ["AI roadmap", "5% discount", "50 units", "other text"] {
+            db.save_panel_content(content, "user", 1_000, "digest")
+                .expect("save");
+        }
+
+        assert_eq!(search(&db, "ai", &SearchFilters::default()), ["AI roadmap"]);
+        assert_eq!(
+            search(&db, "5%", &SearchFilters::default()),
+            ["5% discount"],
+            "% is literal"
+        );
+        assert_eq!(
+            search(&db, "AI roadmap", &SearchFilters::default()),
+            ["AI roadmap"]
+        );
+        assert_eq!(
+            search(&db, "AI units", &SearchFilters::default()),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            search(&db, r#""roadmap""#, &SearchFilters::default()),
+            ["AI roadmap"]
+        );
+    }
+
+    #[test]
+    fn filters_narrow_the_results() {
+        let db = Database::open(Path::new(":memory:")).expect("open in-memory database");
+        let conversation_id = db.create_conversation("Plans").expect("conversation");
+        let may_31_late = local_ms(2024, 5, 31, 23, 30);
+        let june_1 = local_ms(2024, 6, 1, 0, 0);
+        db.save_content(
+            "budget in chat",
+            "user",
+            may_31_late,
+            Some(conversation_id),
+            &["chat"],
+        )
+        .expect("save");
+        db.save_content(
+            "budget answer",
+            "assistant",
+            june_1,
+            Some(conversation_id),
+            &["chat"],
+        )
+        .expect("save");
+        db.save_panel_content("budget in digest", "user", june_1, "digest")
+            .expect("save");
+        let removed = db
+            .save_panel_content("budget removed", "user", june_1, "longterm")
+            .expect("save");
+        db.remove_panel_association(&removed, "longterm")
+            .expect("remove");
+
+        let by_panel = SearchFilters {
+            panel_type: Some("chat".to_owned()),
+            ..Default::default()
+        };
+        let mut found = search(&db, "budget", &by_panel);
+        found.sort();
+        assert_eq!(found, ["budget answer", "budget in chat"]);
+
+        let by_role = SearchFilters {
+            role_or_source: Some("assistant".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(search(&db, "budget", &by_role), ["budget answer"]);
+
+        assert_eq!(
+            search(&db, "budget", &filters("2024-05-01", "2024-05-31")),
+            ["budget in chat"],
+            "the to date is inclusive"
+        );
+        let mut found = search(&db, "budget", &filters("2024-06-01", ""));
+        found.sort();
+        assert_eq!(
+            found,
+            ["budget answer", "budget in digest"],
+            "removed items are not found"
+        );
+    }
+
+    #[test]
+    fn indexed_results_are_ranked_by_relevance() {
+        let db = Database::open(Path::new(":memory:")).expect("open in-memory database");
+        db.save_panel_content("budget budget budget", "user", 1_000, "digest")
+            .expect("save");
+        db.save_panel_content(
+            "notes from the long planning meeting, which also touched on the budget",
+            "user",
+            2_000,
+            "digest",
+        )
+        .expect("save");
+
+        let found = search(&db, "budget", &SearchFilters::default());
+        assert_eq!(
+            found[0], "budget budget budget",
+            "bm25 ranks above the newer item"
+        );
+
+        // Without an indexable term the newest item comes first
+        db.save_panel_content("an AI note", "user", 0, "digest")
+            .expect("save");
+        db.save_panel_content("another AI note", "user", 3_000, "digest")
+            .expect("save");
+        assert_eq!(
+            search(&db, "ai", &SearchFilters::default()),
+            ["another AI note", "an AI note"]
+        );
+    }
+}
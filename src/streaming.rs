@@ -0,0 +1,715 @@
+use crate::llm_provider::{ChatRequest, Connection, LlmProvider};
+use crate::network_log::{Exchange, NetworkRecorder};
+use futures::StreamExt as _;
+use std::sync::mpsc;
+
+/// What went wrong while talking to the LLM API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamErrorKind {
+    /// The request could not be built, e.g. because of a malformed base URL.
+    InvalidRequest,
+    /// The server could not be reached or the connection dropped.
+    Connection,
+    /// The server answered with a non-success status.
+    Http,
+    /// The server reported an error inside the event stream.
+    Stream,
+}
+
+/// Progress of one streamed answer, sent from the request task to the UI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StreamEvent {
+    Delta(String),
+    Usage {
+        prompt_tokens: u64,
+        completion_tokens: u64,
+    },
+    FinishReason(String),
+    /// A transient failure happened before any tokens arrived; trying again after `delay_ms`.
+    Retrying {
+        retry: u32,
+        max_retries: u32,
+        delay_ms: u64,
+        reason: String,
+    },
+    Error {
+        kind: StreamErrorKind,
+        status: Option<u16>,
+        body: String,
+    },
+    Done,
+}
+
+impl StreamEvent {
+    /// Human readable description of an `Error` event.
+    pub fn error_message(kind: StreamErrorKind, status: Option<u16>, body: &str) -> String {
+        match (kind, status) {
+            (StreamErrorKind::InvalidRequest, _) => format!("Invalid request: {body}"),
+            (StreamErrorKind::Connection, _) => format!("Connection error: {body}"),
+            (StreamErrorKind::Http, Some(status)) => format!("HTTP {status}: {body}"),
+            (StreamErrorKind::Http | StreamErrorKind::Stream, _) => format!("API error: {body}"),
+        }
+    }
+}
+
+/// How often and how patiently to retry requests that failed transiently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff before retry number `retry` (starting at 1).
+    pub fn backoff(&self, retry: u32) -> std::time::Duration {
+        let factor = 2u64.saturating_pow(retry.saturating_sub(1));
+        let delay_ms = self
+            .initial_backoff_ms
+            .saturating_mul(factor)
+            .min(self.max_backoff_ms);
+        std::time::Duration::from_millis(delay_ms)
+    }
+}
+
+/// Rate limits and server-side failures are worth another try; other client errors are not.
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parse a `Retry-After` header given either in seconds or as an HTTP date.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<std::time::Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(std::time::Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or_default())
+}
+
+/// Execute `http_request`, retrying connection failures, 429 and 5xx responses.
+///
+/// Returns the successful response, or `None` after reporting the final error.
+async fn send_with_retry(
+    client: &reqwest::Client,
+    http_request: &reqwest::Request,
+    provider: &dyn LlmProvider,
+    retry_policy: RetryPolicy,
+    send: &impl Fn(StreamEvent),
+) -> Option<reqwest::Response> {
+    let max_attempts = retry_policy.max_retries + 1;
+    let mut attempt = 1;
+
+    loop {
+        let Some(request) = http_request.try_clone() else {
+            send(StreamEvent::Error {
+                kind: StreamErrorKind::InvalidRequest,
+                status: None,
+                body: "Request body cannot be resent".to_owned(),
+            });
+            return None;
+        };
+
+        let (error, server_delay) = match client.execute(request).await {
+            Ok(resp) if resp.status().is_success() => return Some(resp),
+            Ok(resp) => {
+                let status = resp.status();
+                let server_delay = retry_after(resp.headers());
+                let error_body = resp.text().await.unwrap_or_default();
+                let error = StreamEvent::Error {
+                    kind: StreamErrorKind::Http,
+                    status: Some(status.as_u16()),
+                    body: provider.parse_error(&error_body),
+                };
+                if !is_retryable_status(status) {
+                    send(error);
+                    return None;
+                }
+                (error, server_delay)
+            }
+            Err(e) => (
+                StreamEvent::Error {
+                    kind: StreamErrorKind::Connection,
+                    status: None,
+                    body: e.to_string(),
+                },
+                None,
+            ),
+        };
+
+        if attempt >= max_attempts {
+            send(error);
+            return None;
+        }
+
+        let delay = server_delay
+            .unwrap_or_else(|| retry_policy.backoff(attempt))
+            .min(std::time::Duration::from_millis(
+                retry_policy.max_backoff_ms,
+            ));
+        let reason = match &error {
+            StreamEvent::Error { kind, status, body } => {
+                StreamEvent::error_message(*kind, *status, body)
+            }
+            _ => String::new(),
+        };
+        send(StreamEvent::Retrying {
+            retry: attempt,
+            max_retries: retry_policy.max_retries,
+            delay_ms: delay.as_millis() as u64,
+            reason,
+        });
+        attempt += 1;
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// One dispatched server-sent event.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Incremental parser for `text/event-stream` bodies.
+///
+/// Bytes are buffered until a full line is available, so multi-byte UTF-8
+/// sequences split across network chunks are decoded intact.
+#[derive(Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data_lines: Vec<String>,
+    skip_leading_lf: bool,
+}
+
+impl SseParser {
+    /// Feed the next chunk of the body and return every event it completed.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        loop {
+            // A CR at the end of the previous chunk may be the first half of a CRLF
+            if self.skip_leading_lf && !self.buffer.is_empty() {
+                if self.buffer[0] == b'\n' {
+                    self.buffer.remove(0);
+                }
+                self.skip_leading_lf = false;
+            }
+
+            let Some(line_end) = self.buffer.iter().position(|&b| b == b'\n' || b == b'\r') else {
+                break;
+            };
+
+            let line_bytes: Vec<u8> = self.buffer.drain(..=line_end).collect();
+            let terminator = line_bytes[line_end];
+            if terminator == b'\r' {
+                if self.buffer.first() == Some(&b'\n') {
+                    self.buffer.remove(0);
+                } else if self.buffer.is_empty() {
+                    self.skip_leading_lf = true;
+                }
+            }
+
+            let line = String::from_utf8_lossy(&line_bytes[..line_end]);
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+        }
+
+        events
+    }
+
+    /// Dispatch whatever is left once the body has ended without a trailing blank line.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.buffer.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+            if let Some(event) = self.process_line(&line) {
+                return Some(event);
+            }
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        // Comments, used by some servers as keep-alives
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event = Some(value.to_owned()),
+            "data" => self.data_lines.push(value.to_owned()),
+            _ => {} // `id`, `retry` and unknown fields are not needed here
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data_lines.is_empty() {
+            return None;
+        }
+
+        let data = std::mem::take(&mut self.data_lines).join("\n");
+        Some(SseEvent { event, data })
+    }
+}
+
+fn lock(
+    exchange: &std::sync::Mutex<Option<Exchange>>,
+) -> std::sync::MutexGuard<'_, Option<Exchange>> {
+    exchange
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Send `request` through `provider` and forward the streamed answer over `tx`.
+///
+/// Every path ends with exactly one `Done` or `Error` event. With a
+/// `recorder`, the exchange is added to the network log when it ends.
+pub async fn stream_chat(
+    connection: Connection,
+    request: ChatRequest,
+    retry_policy: RetryPolicy,
+    tx: mpsc::Sender<StreamEvent>,
+    ctx: egui::Context,
+    recorder: Option<NetworkRecorder>,
+) {
+    let exchange = std::sync::Mutex::new(None);
+    let send = |event: StreamEvent| {
+        if let Some(exchange) = lock(&exchange).as_mut() {
+            exchange.observe(&event);
+        }
+        _ = tx.send(event);
+        ctx.request_repaint();
+    };
+
+    let provider = connection.provider();
+    let client = match connection.client() {
+        Ok(client) => client,
+        Err(e) => {
+            send(StreamEvent::Error {
+                kind: StreamErrorKind::InvalidRequest,
+                status: None,
+                body: e.to_string(),
+            });
+            return;
+        }
+    };
+    let http_request = match connection
+        .build_request(&client, provider.as_ref(), &request)
+        .build()
+    {
+        Ok(http_request) => http_request,
+        Err(e) => {
+            send(StreamEvent::Error {
+                kind: StreamErrorKind::InvalidRequest,
+                status: None,
+                body: e.to_string(),
+            });
+            return;
+        }
+    };
+
+    if let Some(recorder) = &recorder {
+        *lock(&exchange) = Some(recorder.start(&http_request));
+    }
+
+    let Some(resp) = send_with_retry(
+        &client,
+        &http_request,
+        provider.as_ref(),
+        retry_policy,
+        &send,
+    )
+    .await
+    else {
+        return;
+    };
+    if let Some(exchange) = lock(&exchange).as_mut() {
+        exchange.response(resp.status());
+    }
+
+    let mut stream = resp.bytes_stream();
+    let mut parser = SseParser::default();
+
+    while let Some(chunk_result) = stream.next().await {
+        let chunk = match chunk_result {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                send(StreamEvent::Error {
+                    kind: StreamErrorKind::Connection,
+                    status: None,
+                    body: e.to_string(),
+                });
+                return;
+            }
+        };
+        if let Some(exchange) = lock(&exchange).as_mut() {
+            exchange.response_body(&chunk);
+        }
+
+        for event in parser.feed(&chunk) {
+            if forward_event(provider.as_ref(), &event, &send) {
+                return;
+            }
+        }
+    }
+
+    if let Some(event) = parser.finish() {
+        if forward_event(provider.as_ref(), &event, &send) {
+            return;
+        }
+    }
+
+    // The server closed the stream without an explicit end marker
+    send(StreamEvent::Done);
+}
+
+/// Returns `true` once the answer is complete, successfully or not.
+fn forward_event(
+    provider: &dyn LlmProvider,
+    event: &SseEvent,
+    send: &impl Fn(StreamEvent),
+) -> bool {
+    for stream_event in provider.parse_stream_chunk(event.event.as_deref(), &event.data) {
+        let is_final = matches!(stream_event, StreamEvent::Done | StreamEvent::Error { .. });
+        send(stream_event);
+        if is_final {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_provider::OpenAiCompatibleProvider;
+    use std::io::{BufRead as _, BufReader, Read as _, Write as _};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// A recorded `/chat/completions` stream with CRLF line endings, a keep-alive
+    /// comment and a multi-byte character.
+    const OPENAI_STREAM: &[u8] = b": keep-alive\r\n\r\n\
+data: {\"choices\":[{\"delta\":{\"content\":\"Gr\xC3\xBC\xC3\x9Fe\"}}]}\r\n\r\n\
+data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\r\n\r\n\
+data: [DONE]\r\n\r\n";
+
+    fn parse_chunks(chunks: &[&[u8]]) -> Vec<SseEvent> {
+        let mut parser = SseParser::default();
+        let mut events: Vec<SseEvent> =
+            chunks.iter().flat_map(|chunk| parser.feed(chunk)).collect();
+        events.extend(parser.finish());
+        events
+    }
+
+    fn data(data: &str) -> SseEvent {
+        SseEvent {
+            event: None,
+            data: data.to_owned(),
+        }
+    }
+
+    #[test]
+    fn recorded_stream_parses_the_same_at_every_chunk_boundary() {
+        let expected = parse_chunks(&[OPENAI_STREAM]);
+        assert_eq!(expected.len(), 3, "comment produces no event: {expected:?}");
+        assert_eq!(expected[2], data("[DONE]"));
+
+        for split in 1..OPENAI_STREAM.len() {
+            let halves: [&[u8]; 2] = OPENAI_STREAM.split_at(split).into();
+            assert_eq!(parse_chunks(&halves), expected, "split at byte {split}");
+        }
+        let bytes: Vec<&[u8]> = OPENAI_STREAM.chunks(1).collect();
+        assert_eq!(parse_chunks(&bytes), expected, "one byte at a time");
+    }
+
+    #[test]
+    fn utf8_split_across_chunks_is_decoded_intact() {
+        let provider = OpenAiCompatibleProvider { stream_usage: true };
+        // Split between the two bytes of "ü"
+        let split = OPENAI_STREAM
+            .windows(2)
+            .position(|pair| pair == b"\xC3\xBC")
+            .expect("fixture has an umlaut")
+            + 1;
+        let halves: [&[u8]; 2] = OPENAI_STREAM.split_at(split).into();
+
+        let stream_events: Vec<StreamEvent> = parse_chunks(&halves)
+            .iter()
+            .flat_map(|event| provider.parse_stream_chunk(event.event.as_deref(), &event.data))
+            .collect();
+        assert_eq!(
+            stream_events,
+            vec![
+                StreamEvent::Delta("Grüße".to_owned()),
+                StreamEvent::FinishReason("stop".to_owned()),
+                StreamEvent::Done,
+            ]
+        );
+    }
+
+    #[test]
+    fn event_fields_and_multi_line_data_are_combined() {
+        let events = parse_chunks(&[
+            b"event: content_block_delta\ndata: first line\nda",
+            b"ta: second line\n",
+            b"id: 7\n\nevent: ping\ndata:no space\n\n",
+        ]);
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: Some("content_block_delta".to_owned()),
+                    data: "first line\nsecond line".to_owned(),
+                },
+                SseEvent {
+                    event: Some("ping".to_owned()),
+                    data: "no space".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_event_without_blank_line_is_flushed_by_finish() {
+        let mut parser = SseParser::default();
+        assert_eq!(parser.feed(b"data: {\"a\":1}\r\ndata: last"), Vec::new());
+        assert_eq!(parser.finish(), Some(data("{\"a\":1}\nlast")));
+        assert_eq!(parser.finish(), None, "nothing left after flushing");
+    }
+
+    #[test]
+    fn lone_cr_ends_lines_too() {
+        assert_eq!(
+            parse_chunks(&[b"data: a\r\r", b"data: b\r", b"\r"]),
+            vec![data("a"), data("b")]
+        );
+    }
+
+    /// Answers the n-th request with the n-th `(status line, extra headers)`;
+    /// returns the base URL and the number of requests received so far.
+    fn start_scripted_server(script: &[(&str, &str)]) -> (String, Arc<AtomicUsize>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind stub server");
+        let address = listener.local_addr().expect("stub address");
+        let requests = Arc::new(AtomicUsize::new(0));
+        let script: Vec<(String, String)> = script
+            .iter()
+            .map(|(status, headers)| ((*status).to_owned(), (*headers).to_owned()))
+            .collect();
+        let counter = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            for (stream, (status, headers)) in listener.incoming().zip(script) {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("header line");
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().expect("content length");
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).expect("request body");
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let body = r#"{"error":{"message":"scripted"}}"#;
+                let response = format!(
+                    "HTTP/1.1 {status}\r\n{headers}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream
+                    .write_all(response.as_bytes())
+                    .expect("write response");
+            }
+        });
+        (format!("http://{address}"), requests)
+    }
+
+    /// Run `send_with_retry` against the scripted server and collect what it reported.
+    async fn send_scripted(
+        script: &[(&str, &str)],
+        retry_policy: RetryPolicy,
+    ) -> (Option<u16>, Vec<StreamEvent>, usize) {
+        let (base_url, requests) = start_scripted_server(script);
+        let client = reqwest::Client::new();
+        let http_request = client
+            .post(format!("{base_url}/chat/completions"))
+            .body(r#"{"model":"test"}"#)
+            .build()
+            .expect("request");
+        let events = Mutex::new(Vec::new());
+        let send = |event: StreamEvent| events.lock().expect("events").push(event);
+
+        let response = send_with_retry(
+            &client,
+            &http_request,
+            &OpenAiCompatibleProvider { stream_usage: true },
+            retry_policy,
+            &send,
+        )
+        .await;
+        let status = response.map(|response| response.status().as_u16());
+        (
+            status,
+            events.into_inner().expect("events"),
+            requests.load(Ordering::SeqCst),
+        )
+    }
+
+    const FAST_RETRIES: RetryPolicy = RetryPolicy {
+        max_retries: 2,
+        initial_backoff_ms: 10,
+        max_backoff_ms: 30_000,
+    };
+
+    fn retry_delays(events: &[StreamEvent]) -> Vec<u64> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::Retrying { delay_ms, .. } => Some(*delay_ms),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried_with_backoff() {
+        let (status, events, requests) = send_scripted(
+            &[("500 Internal Server Error", ""), ("200 OK", "")],
+            FAST_RETRIES,
+        )
+        .await;
+        assert_eq!(status, Some(200));
+        assert_eq!(requests, 2);
+        assert_eq!(retry_delays(&events), vec![10]);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_honour_retry_after() {
+        let slow_backoff = RetryPolicy {
+            initial_backoff_ms: 20_000,
+            ..FAST_RETRIES
+        };
+        let started = std::time::Instant::now();
+        let (status, events, requests) = send_scripted(
+            &[
+                ("429 Too Many Requests", "Retry-After: 1\r\n"),
+                ("200 OK", ""),
+            ],
+            slow_backoff,
+        )
+        .await;
+        assert_eq!(status, Some(200));
+        assert_eq!(requests, 2);
+        assert_eq!(
+            retry_delays(&events),
+            vec![1000],
+            "the server's delay wins over the backoff"
+        );
+        assert!(
+            started.elapsed() >= std::time::Duration::from_secs(1),
+            "did not wait"
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let (status, events, requests) =
+            send_scripted(&[("503 Service Unavailable", ""); 4], FAST_RETRIES).await;
+        assert_eq!(status, None);
+        assert_eq!(requests, 3, "one attempt plus two retries");
+        assert_eq!(retry_delays(&events), vec![10, 20]);
+        assert_eq!(
+            events.last(),
+            Some(&StreamEvent::Error {
+                kind: StreamErrorKind::Http,
+                status: Some(503),
+                body: "scripted".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (status, events, requests) =
+            send_scripted(&[("401 Unauthorized", ""), ("200 OK", "")], FAST_RETRIES).await;
+        assert_eq!(status, None);
+        assert_eq!(requests, 1);
+        assert_eq!(
+            events,
+            vec![StreamEvent::Error {
+                kind: StreamErrorKind::Http,
+                status: Some(401),
+                body: "scripted".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_and_retry_after_accepts_dates() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 5000,
+        };
+        let delays: Vec<u128> = (1..=5)
+            .map(|retry| policy.backoff(retry).as_millis())
+            .collect();
+        assert_eq!(delays, vec![1000, 2000, 4000, 5000, 5000]);
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, "7".parse().expect("header"));
+        assert_eq!(
+            retry_after(&headers),
+            Some(std::time::Duration::from_secs(7))
+        );
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().expect("header"),
+        );
+        assert_eq!(
+            retry_after(&headers),
+            Some(std::time::Duration::ZERO),
+            "dates in the past mean now"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::{DateTime, Datelike as _, Local, NaiveDate, TimeZone as _, Utc};
+
+/// Current time in Unix milliseconds, the precision every stored item keeps.
+pub fn now_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// Local time of a timestamp; `None` for items saved before timestamps were kept.
+pub fn to_local(timestamp_ms: i64) -> Option<DateTime<Local>> {
+    if timestamp_ms <= 0 {
+        return None;
+    }
+    Local.timestamp_millis_opt(timestamp_ms).single()
+}
+
+pub fn local_date(timestamp_ms: i64) -> Option<NaiveDate> {
+    to_local(timestamp_ms).map(|time| time.date_naive())
+}
+
+/// Short time relative to `now`: `14:03`, `yesterday 14:03`, `Mon 14:03`,
+/// `May 31 14:03` or `2023-05-31 14:03`.
+pub fn format_relative(timestamp_ms: i64, now: DateTime<Local>) -> String {
+    let Some(time) = to_local(timestamp_ms) else {
+        return String::new();
+    };
+    let days_ago = (now.date_naive() - time.date_naive()).num_days();
+    match days_ago {
+        0 => time.format("%H:%M").to_string(),
+        1 => time.format("yesterday %H:%M").to_string(),
+        2..=6 => time.format("%a %H:%M").to_string(),
+        _ if time.year() == now.year() => time.format("%b %-d %H:%M").to_string(),
+        _ => time.format("%Y-%m-%d %H:%M").to_string(),
+    }
+}
+
+/// Heading of a day in the chat: `Today`, `Yesterday` or `Friday, May 31, 2024`.
+pub fn day_label(date: NaiveDate, now: DateTime<Local>) -> String {
+    match (now.date_naive() - date).num_days() {
+        0 => "Today".to_owned(),
+        1 => "Yesterday".to_owned(),
+        _ => date.format("%A, %B %-d, %Y").to_string(),
+    }
+}
+
+/// Local date and time such as `2024-05-31 14:03`, for exports and prompts.
+pub fn format_local(timestamp_ms: i64) -> String {
+    to_local(timestamp_ms)
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "unknown time".to_owned())
+}
+
+/// Exact time in local time and UTC, e.g. for hover texts and exports.
+pub fn format_full(timestamp_ms: i64) -> String {
+    let Some(time) = to_local(timestamp_ms) else {
+        return "unknown time".to_owned();
+    };
+    format!(
+        "{} ({} UTC)",
+        time.format("%Y-%m-%d %H:%M:%S %:z"),
+        time.with_timezone(&Utc).format("%Y-%m-%d %H:%M:%S")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .earliest()
+            .expect("local time")
+    }
+
+    fn ms(time: DateTime<Local>) -> i64 {
+        time.timestamp_millis()
+    }
+
+    #[test]
+    fn relative_times_get_shorter_the_closer_they_are() {
+        let now = local(2024, 5, 31, 0, 10);
+        assert_eq!(format_relative(ms(local(2024, 5, 31, 0, 5)), now), "00:05");
+        assert_eq!(
+            format_relative(ms(local(2024, 5, 30, 23, 50)), now),
+            "yesterday 23:50",
+            "twenty minutes ago, but before midnight"
+        );
+        assert_eq!(
+            format_relative(ms(local(2024, 5, 28, 14, 3)), now),
+            "Tue 14:03"
+        );
+        assert_eq!(
+            format_relative(ms(local(2024, 1, 5, 9, 0)), now),
+            "Jan 5 09:00"
+        );
+        assert_eq!(
+            format_relative(ms(local(2023, 12, 31, 23, 0)), now),
+            "2023-12-31 23:00"
+        );
+        assert_eq!(format_relative(0, now), "", "no timestamp");
+    }
+
+    #[test]
+    fn future_times_are_never_called_yesterday() {
+        let now = local(2024, 5, 31, 23, 50);
+        assert_eq!(
+            format_relative(ms(local(2024, 5, 31, 23, 55)), now),
+            "23:55"
+        );
+        assert_eq!(
+            format_relative(ms(local(2024, 6, 1, 0, 5)), now),
+            "Jun 1 00:05"
+        );
+        assert_eq!(
+            format_relative(ms(local(2025, 1, 2, 8, 0)), now),
+            "2025-01-02 08:00"
+        );
+        assert_eq!(
+            day_label(NaiveDate::from_ymd_opt(2024, 6, 1).expect("date"), now),
+            "Saturday, June 1, 2024"
+        );
+    }
+
+    #[test]
+    fn day_labels_follow_the_local_date() {
+        let just_after_midnight = local(2024, 5, 31, 0, 1);
+        let date = |day| NaiveDate::from_ymd_opt(2024, 5, day).expect("date");
+        assert_eq!(day_label(date(31), just_after_midnight), "Today");
+        assert_eq!(day_label(date(30), just_after_midnight), "Yesterday");
+        assert_eq!(
+            day_label(date(29), just_after_midnight),
+            "Wednesday, May 29, 2024"
+        );
+    }
+
+    #[test]
+    fn full_times_show_local_time_and_utc() {
+        let timestamp_ms = 1_717_158_896_000; // 2024-05-31 12:34:56 UTC
+        let full = format_full(timestamp_ms);
+        let (local_part, utc_part) = full.split_once(" (").expect("two parts");
+        assert_eq!(utc_part, "2024-05-31 12:34:56 UTC)");
+        let parsed = DateTime::parse_from_str(local_part, "%Y-%m-%d %H:%M:%S %:z").expect("parse");
+        assert_eq!(
+            parsed.timestamp_millis(),
+            timestamp_ms,
+            "both name the same instant"
+        );
+        assert_eq!(format_full(0), "unknown time");
+    }
+}
@@ -0,0 +1,101 @@
+/// Tokens consumed by one assistant answer.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TokenUsage {
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// Price of a model in currency units per million tokens.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ModelPrice {
+    pub model: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+pub fn default_model_prices() -> Vec<ModelPrice> {
+    vec![
+        ModelPrice {
+            model: "deepseek-chat".to_owned(),
+            input_per_million: 0.27,
+            output_per_million: 1.10,
+        },
+        ModelPrice {
+            model: "deepseek-reasoner".to_owned(),
+            input_per_million: 0.55,
+            output_per_million: 2.19,
+        },
+    ]
+}
+
+/// Estimated cost of `usage`, or `None` when its model has no price configured.
+pub fn estimate_cost(prices: &[ModelPrice], usage: &TokenUsage) -> Option<f64> {
+    let price = prices.iter().find(|price| price.model == usage.model)?;
+    Some(
+        (usage.prompt_tokens as f64 * price.input_per_million
+            + usage.completion_tokens as f64 * price.output_per_million)
+            / 1_000_000.0,
+    )
+}
+
+/// Sum the usage of several answers, along with the cost of those that have a
+/// price; the cost is `None` when none of them has.
+pub fn summarize<'a>(
+    prices: &[ModelPrice],
+    usages: impl Iterator<Item = &'a TokenUsage>,
+) -> (u64, u64, Option<f64>) {
+    let mut prompt_tokens = 0;
+    let mut completion_tokens = 0;
+    let mut cost = None;
+    for usage in usages {
+        prompt_tokens += usage.prompt_tokens;
+        completion_tokens += usage.completion_tokens;
+        if let Some(answer_cost) = estimate_cost(prices, usage) {
+            *cost.get_or_insert(0.0) += answer_cost;
+        }
+    }
+    (prompt_tokens, completion_tokens, cost)
+}
+
+/// Short label such as `1200 → 350 tokens · $0.0007`.
+pub fn format_usage(prompt_tokens: u64, completion_tokens: u64, cost: Option<f64>) -> String {
+    match cost {
+        Some(cost) => format!("{prompt_tokens} → {completion_tokens} tokens · ${cost:.4}"),
+        None => format!("{prompt_tokens} → {completion_tokens} tokens"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(model: &str, prompt_tokens: u64, completion_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            model: model.to_owned(),
+            prompt_tokens,
+            completion_tokens,
+        }
+    }
+
+    #[test]
+    fn unpriced_models_have_no_cost() {
+        let prices = [ModelPrice {
+            model: "priced".to_owned(),
+            input_per_million: 1.0,
+            output_per_million: 2.0,
+        }];
+        let local = [usage("llama3.1", 1_000, 500), usage("llama3.1", 2_000, 100)];
+        assert_eq!(summarize(&prices, local.iter()), (3_000, 600, None));
+        assert_eq!(format_usage(3_000, 600, None), "3000 → 600 tokens");
+
+        let mixed = [usage("priced", 1_000_000, 500_000), usage("llama3.1", 10, 10)];
+        assert_eq!(summarize(&prices, mixed.iter()), (1_000_010, 500_010, Some(2.0)));
+    }
+}
@@ -0,0 +1,340 @@
+use crate::app::TemplateApp;
+use crate::context_window::ContextBudget;
+use crate::database::Database;
+use std::path::{Path, PathBuf};
+
+/// Command line flag naming the database to open, e.g. `--workspace ~/clients/acme.db`.
+pub const WORKSPACE_ARG: &str = "--workspace";
+
+/// Environment variable naming the database to open when no flag is given.
+pub const WORKSPACE_ENV_VAR: &str = "PBOT_WORKSPACE";
+
+/// How many workspaces the File menu remembers.
+const MAX_RECENT_WORKSPACES: usize = 8;
+
+#[derive(Default)]
+pub struct WorkspaceDialogState {
+    pub path_input: String,
+    pub error: Option<String>,
+}
+
+/// Path given with `--workspace <path>` or `--workspace=<path>`; a missing or empty
+/// path leaves the choice to the other sources.
+fn workspace_from_args(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        let path = if arg == WORKSPACE_ARG {
+            args.next()
+        } else if let Some(path) = arg
+            .strip_prefix(WORKSPACE_ARG)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            Some(path.to_owned())
+        } else {
+            continue;
+        };
+        return path
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from);
+    }
+    None
+}
+
+/// Database to open at startup: command line, then environment, then the last
+/// workspace used, then the default location.
+pub fn startup_workspace(recent_workspaces: &[PathBuf]) -> PathBuf {
+    workspace_from_args(std::env::args().skip(1))
+        .or_else(|| {
+            std::env::var(WORKSPACE_ENV_VAR)
+                .ok()
+                .filter(|path| !path.trim().is_empty())
+                .map(PathBuf::from)
+        })
+        .or_else(|| recent_workspaces.first().cloned())
+        .unwrap_or_else(Database::default_path)
+}
+
+/// Settings of the window that the command line follows too, as last saved.
+#[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct SavedSettings {
+    /// Most recent first.
+    pub recent_workspaces: Vec<PathBuf>,
+    pub context_budgets: Vec<ContextBudget>,
+    pub reserved_output_tokens: usize,
+}
+
+impl Default for SavedSettings {
+    fn default() -> Self {
+        Self {
+            recent_workspaces: Vec::new(),
+            context_budgets: crate::context_window::default_context_budgets(),
+            reserved_output_tokens: crate::context_window::DEFAULT_RESERVED_OUTPUT_TOKENS,
+        }
+    }
+}
+
+impl SavedSettings {
+    /// Read from eframe's `app.ron`; defaults when the window never saved any.
+    pub fn load() -> Self {
+        eframe::storage_dir(crate::APP_NAME)
+            .and_then(|dir| std::fs::read_to_string(dir.join("app.ron")).ok())
+            .map(|saved| Self::from_ron(&saved))
+            .unwrap_or_default()
+    }
+
+    /// The app state in `app.ron`, which maps keys to RON strings.
+    fn from_ron(saved: &str) -> Self {
+        ron::from_str::<std::collections::HashMap<String, String>>(saved)
+            .ok()
+            .and_then(|values| ron::from_str(values.get(eframe::APP_KEY)?).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Short name of a workspace for display, e.g. `acme` for `~/clients/acme.db`.
+pub fn workspace_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+impl TemplateApp {
+    pub fn open_workspace_dialog(&mut self) {
+        self.show_workspace_dialog = true;
+        self.workspace_dialog.error = None;
+        if self.workspace_dialog.path_input.is_empty() {
+            if let Some(db) = &self.database {
+                self.workspace_dialog.path_input = db.path().display().to_string();
+            }
+        }
+    }
+
+    /// Move `path` to the front of the recent workspaces.
+    pub fn remember_workspace(&mut self, path: &Path) {
+        self.recent_workspaces.retain(|recent| recent != path);
+        self.recent_workspaces.insert(0, path.to_path_buf());
+        self.recent_workspaces.truncate(MAX_RECENT_WORKSPACES);
+    }
+
+    /// Switch to the database at `path`, keeping the current one if it can't be opened.
+    pub fn switch_workspace(&mut self, path: &Path) {
+        if self.database.as_ref().is_some_and(|db| db.path() == path) {
+            self.show_workspace_dialog = false;
+            return;
+        }
+
+        let db = match Database::open(path) {
+            Ok(db) => db,
+            Err(e) => {
+                log::error!("Failed to open workspace {}: {e}", path.display());
+                self.workspace_dialog.error =
+                    Some(format!("Could not open {}: {e}", path.display()));
+                return;
+            }
+        };
+
+        // Finish with the old database before anything from the new one is shown
+        self.stop_streaming();
+        self.database = Some(db);
+        log::info!("Switched to workspace {}", path.display());
+
+        self.reset_workspace_state();
+        self.reload_workspace();
+        self.remember_workspace(path);
+        self.workspace_dialog = WorkspaceDialogState::default();
+        self.show_workspace_dialog = false;
+    }
+
+    /// Forget everything loaded from the previous database.
+    pub fn reset_workspace_state(&mut self) {
+        self.chat_messages.clear();
+        self.current_response.clear();
+        self.last_error = None;
+        self.current_conversation_id = None;
+        self.conversations.clear();
+        self.renaming_conversation = None;
+        self.context_summary = None;
+        self.pending_context_summary = None;
+        self.pinned_chat_prompt = None;
+        self.digest_items.clear();
+        self.long_term_memory_items.clear();
+        self.selected_text.clear();
+        self.current_assistant_role_id = None;
+        self.temp_assistant_role_id = None;
+        self.available_roles.clear();
+        self.current_system_prompts.clear();
+        self.current_prompt_revisions.clear();
+        self.current_role_params = Default::default();
+        self.request_params = Default::default();
+        self.provider_profiles.clear();
+        self.role_editor = Default::default();
+        self.model_discovery = Default::default();
+        self.network_log.entries.clear();
+        self.network_log.selected_id = None;
+        self.show_role_editor = false;
+        self.search = Default::default();
+        self.show_search = false;
+        self.export_dialog = Default::default();
+        self.show_export_dialog = false;
+        self.import_dialog = Default::default();
+        self.show_import_dialog = false;
+    }
+
+    /// Fill every panel from the current database.
+    pub fn reload_workspace(&mut self) {
+        self.load_assistant_roles();
+        self.load_provider_profiles();
+        self.load_data_from_database();
+        self.load_network_log();
+    }
+
+    pub fn render_workspace_dialog(&mut self, ctx: &egui::Context) {
+        if !self.show_workspace_dialog {
+            return;
+        }
+
+        let mut show_workspace_dialog = self.show_workspace_dialog;
+        let mut open_path: Option<PathBuf> = None;
+        let mut forget_path: Option<PathBuf> = None;
+        let current_path = self.database.as_ref().map(|db| db.path().to_path_buf());
+
+        egui::Window::new("📂 Open Workspace")
+            .open(&mut show_workspace_dialog)
+            .default_width(520.0)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label("Each workspace is a separate database file. A new one is created if the file does not exist.");
+                ui.add_space(6.0);
+
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.workspace_dialog.path_input)
+                            .desired_width(ui.available_width() - 60.0)
+                            .hint_text("/path/to/client.db"),
+                    );
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    let path_input = self.workspace_dialog.path_input.trim();
+                    if (ui
+                        .add_enabled(!path_input.is_empty(), egui::Button::new("Open"))
+                        .clicked()
+                        || submitted)
+                        && !path_input.is_empty()
+                    {
+                        open_path = Some(PathBuf::from(path_input));
+                    }
+                });
+
+                if let Some(error) = &self.workspace_dialog.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+
+                ui.separator();
+                ui.label(egui::RichText::new("Recent workspaces").strong());
+                if self.recent_workspaces.is_empty() {
+                    ui.colored_label(egui::Color32::GRAY, "No recent workspaces");
+                }
+                for recent in &self.recent_workspaces {
+                    ui.horizontal(|ui| {
+                        let is_current = current_path.as_ref() == Some(recent);
+                        if ui
+                            .selectable_label(is_current, workspace_name(recent))
+                            .on_hover_text(recent.display().to_string())
+                            .clicked()
+                        {
+                            open_path = Some(recent.clone());
+                        }
+                        ui.colored_label(egui::Color32::GRAY, recent.display().to_string());
+                        if !is_current
+                            && ui
+                                .small_button("✖")
+                                .on_hover_text("Remove from this list; the file is kept")
+                                .clicked()
+                        {
+                            forget_path = Some(recent.clone());
+                        }
+                    });
+                }
+
+                ui.separator();
+                if ui.button("Default location").clicked() {
+                    open_path = Some(Database::default_path());
+                }
+            });
+
+        self.show_workspace_dialog = show_workspace_dialog;
+        if let Some(path) = forget_path {
+            self.recent_workspaces.retain(|recent| *recent != path);
+        }
+        if let Some(path) = open_path {
+            self.switch_workspace(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_args(args: &[&str]) -> Option<PathBuf> {
+        workspace_from_args(args.iter().map(|arg| (*arg).to_owned()))
+    }
+
+    #[test]
+    fn workspace_is_read_from_either_argument_form() {
+        let expected = Some(PathBuf::from("/clients/acme.db"));
+        assert_eq!(from_args(&["--workspace", "/clients/acme.db"]), expected);
+        assert_eq!(from_args(&["--workspace=/clients/acme.db"]), expected);
+        assert_eq!(
+            from_args(&[
+                "--verbose",
+                "--workspace=/clients/acme.db",
+                "--workspace",
+                "/other.db"
+            ]),
+            expected,
+            "the first one wins"
+        );
+    }
+
+    #[test]
+    fn missing_workspace_values_are_ignored() {
+        assert_eq!(from_args(&[]), None);
+        assert_eq!(from_args(&["--workspace"]), None);
+        assert_eq!(from_args(&["--workspace="]), None);
+        assert_eq!(from_args(&["--workspace", " "]), None);
+        assert_eq!(from_args(&["--workspaces=/clients/acme.db"]), None);
+    }
+
+    #[test]
+    fn settings_are_read_from_saved_app_state() {
+        let app = TemplateApp {
+            recent_workspaces: vec![
+                PathBuf::from("/clients/acme.db"),
+                PathBuf::from("/clients/globex.db"),
+            ],
+            context_budgets: vec![ContextBudget {
+                model: "llama3.1".to_owned(),
+                max_tokens: 131_072,
+            }],
+            reserved_output_tokens: 1024,
+            ..TemplateApp::default()
+        };
+        let values = std::collections::HashMap::from([(
+            eframe::APP_KEY.to_owned(),
+            ron::to_string(&app).expect("serialize app"),
+        )]);
+        let saved = ron::to_string(&values).expect("serialize storage");
+
+        assert_eq!(
+            SavedSettings::from_ron(&saved),
+            SavedSettings {
+                recent_workspaces: app.recent_workspaces,
+                context_budgets: app.context_budgets,
+                reserved_output_tokens: 1024,
+            }
+        );
+        assert_eq!(SavedSettings::from_ron("not ron"), SavedSettings::default());
+    }
+}
@@ -0,0 +1,88 @@
+-- chat_data.db as created by the context_summary release, before schema versioning
+
+CREATE TABLE IF NOT EXISTS conversations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title TEXT NOT NULL,
+    is_archived BOOLEAN DEFAULT 0,
+    context_summary TEXT,
+    context_summary_through TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS content_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    content TEXT NOT NULL,
+    role_or_source TEXT NOT NULL,
+    timestamp_unix INTEGER,
+    timestamp_display TEXT,
+    original_id TEXT UNIQUE,
+    conversation_id INTEGER,
+    is_truncated BOOLEAN DEFAULT 0,
+    model_name TEXT,
+    prompt_tokens INTEGER,
+    completion_tokens INTEGER,
+    is_active BOOLEAN DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (conversation_id) REFERENCES conversations(id)
+);
+
+CREATE TABLE IF NOT EXISTS panel_associations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    content_id INTEGER NOT NULL,
+    panel_type TEXT NOT NULL,
+    is_active BOOLEAN DEFAULT 1,
+    added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (content_id) REFERENCES content_items(id),
+    UNIQUE(content_id, panel_type)
+);
+
+CREATE TABLE IF NOT EXISTS assistant_roles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    role_name TEXT UNIQUE NOT NULL,
+    display_name TEXT NOT NULL,
+    description TEXT,
+    is_active BOOLEAN DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS system_prompts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    role_id INTEGER NOT NULL,
+    panel_type TEXT NOT NULL,
+    prompt_text TEXT NOT NULL,
+    is_active BOOLEAN DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (role_id) REFERENCES assistant_roles(id),
+    UNIQUE(role_id, panel_type)
+);
+
+CREATE INDEX IF NOT EXISTS idx_content_original_id ON content_items(original_id);
+
+CREATE INDEX IF NOT EXISTS idx_panel_associations_content_id ON panel_associations(content_id);
+
+CREATE INDEX IF NOT EXISTS idx_panel_associations_panel_type ON panel_associations(panel_type);
+
+CREATE INDEX IF NOT EXISTS idx_system_prompts_role_id ON system_prompts(role_id);
+
+CREATE INDEX IF NOT EXISTS idx_system_prompts_panel_type ON system_prompts(panel_type);
+
+CREATE INDEX IF NOT EXISTS idx_content_conversation_id ON content_items(conversation_id);
+
+INSERT INTO assistant_roles (role_name, display_name, description)
+VALUES ('contract_template_selection', 'Contract Template Selection', 'Contracts');
+
+INSERT INTO system_prompts (role_id, panel_type, prompt_text)
+VALUES (1, 'chat', 'You are a legal expert.'), (1, 'digest', 'Summarize the clauses.');
+
+INSERT INTO content_items (content, role_or_source, timestamp_unix, timestamp_display, original_id)
+VALUES
+    ('Which NDA template should I use?', 'user', 1700000000, '22:13', 'chat-1'),
+    ('Use the mutual NDA.', 'assistant', 1700000005, '22:13', 'chat-2'),
+    ('Mutual NDA preferred for partnerships', 'chat', 1700000010, '22:13', 'digest-1');
+
+INSERT INTO panel_associations (content_id, panel_type)
+VALUES (1, 'chat'), (2, 'chat'), (3, 'digest');
+
+INSERT INTO conversations (title) VALUES ('NDA questions');
+UPDATE content_items SET conversation_id = 1 WHERE id IN (1, 2);